@@ -28,11 +28,12 @@ extern "C" fn rust_main() -> ! {
     syscall::init_io(&SyscallContext);
     syscall::init_process(&SyscallContext);
     // 批处理
-    for (i, app) in linker::AppMeta::locate().iter().enumerate() {
-        let app_base = app.as_ptr() as usize;
-        log::info!("load app{i} to {app_base:#x}");
+    for app in linker::AppMeta::locate().iter() {
+        let name = app.name();
+        app.load();
+        log::info!("load {name} to {:#x}", app.entry());
         // 初始化上下文
-        let mut ctx = LocalContext::user(app_base);
+        let mut ctx = LocalContext::user(app.entry());
         // 设置用户栈（使用 MaybeUninit 避免 release 模式下零初始化的问题）
         let mut user_stack: core::mem::MaybeUninit<[usize; 256]> = core::mem::MaybeUninit::uninit();
         let user_stack_ptr = user_stack.as_mut_ptr() as *mut usize;
@@ -46,11 +47,24 @@ extern "C" fn rust_main() -> ! {
                     use SyscallResult::*;
                     match handle_syscall(&mut ctx) {
                         Done => continue,
-                        Exit(code) => log::info!("app{i} exit with code {code}"),
-                        Error(id) => log::error!("app{i} call an unsupported syscall {}", id.0),
+                        Exit(code) => log::info!("{name} exit with code {code}"),
+                        Error(id) => log::error!("{name} call an unsupported syscall {}", id.0),
                     }
                 }
-                trap => log::error!("app{i} was killed because of {trap:?}"),
+                trap => {
+                    // 用户栈在内核栈上，只允许读取用户栈
+                    let stack = user_stack_ptr as usize..user_stack_ptr as usize + 256 * 8;
+                    let backtrace = kernel_backtrace::Backtrace::user(ctx.pc(), ctx.x(8), |addr| {
+                        stack
+                            .contains(&addr)
+                            .then(|| unsafe { *(addr as *const usize) })
+                    });
+                    log::error!(
+                        "{name} was killed because of {trap:?}, sepc = {:#x}, stval = {:#x}\n{backtrace}",
+                        ctx.pc(),
+                        stval::read()
+                    )
+                }
             }
             // 清除指令缓存
             unsafe { core::arch::asm!("fence.i") };
@@ -69,6 +83,7 @@ extern "C" fn rust_main() -> ! {
 #[panic_handler]
 fn panic(info: &core::panic::PanicInfo) -> ! {
     println!("{info}");
+    println!("{}", kernel_backtrace::Backtrace::capture());
     system_reset(Shutdown, SystemFailure);
     loop {}
 }
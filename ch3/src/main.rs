@@ -20,13 +20,18 @@ const APP_CAPACITY: usize = 32;
 // 定义内核入口。
 linker::boot0!(rust_main; stack = (APP_CAPACITY + 2) * 4096);
 
-extern "C" fn rust_main() -> ! {
+extern "C" fn rust_main(hartid: usize, dtb: usize) -> ! {
+    // 硬件线程号保存在 `tp` 里，内核不使用线程局部存储
+    unsafe { core::arch::asm!("mv tp, {}", in(reg) hartid) };
     // bss 段清零
     unsafe { linker::KernelLayout::locate().zero_bss() };
     // 初始化 `console`
     rcore_console::init_console(&Console);
     rcore_console::set_log_level(option_env!("LOG"));
     rcore_console::test_log();
+    // 解析设备树
+    let platform = unsafe { platform::init(dtb) };
+    log::info!("{platform}");
     // 初始化 syscall
     syscall::init_io(&SyscallContext);
     syscall::init_process(&SyscallContext);
@@ -36,10 +41,11 @@ extern "C" fn rust_main() -> ! {
     let mut tcbs = [TaskControlBlock::ZERO; APP_CAPACITY];
     let mut index_mod = 0;
     // 初始化
-    for (i, app) in linker::AppMeta::locate().iter().enumerate() {
-        let entry = app.as_ptr() as usize;
-        log::info!("load app{i} to {entry:#x}");
-        tcbs[i].init(entry);
+    for app in linker::AppMeta::locate().iter() {
+        app.load();
+        let entry = app.entry();
+        log::info!("load {} to {entry:#x}", app.name());
+        tcbs[app.index()].init(entry);
         index_mod += 1;
     }
     println!();
@@ -50,17 +56,18 @@ extern "C" fn rust_main() -> ! {
     let mut i = 0usize;
     while remain > 0 {
         let tcb = &mut tcbs[i];
+        let name = linker::AppMeta::locate().get(i).unwrap().name();
         if !tcb.finish {
             loop {
                 #[cfg(not(feature = "coop"))]
-                sbi_rt::set_timer(time::read64() + 12500);
+                sbi_rt::set_timer(time::read64() + platform.timebase_frequency as u64 / 1000);
                 unsafe { tcb.execute() };
 
                 use scause::*;
                 let finish = match scause::read().cause() {
                     Trap::Interrupt(Interrupt::SupervisorTimer) => {
                         sbi_rt::set_timer(u64::MAX);
-                        log::trace!("app{i} timeout");
+                        log::trace!("{name} timeout");
                         false
                     }
                     Trap::Exception(Exception::UserEnvCall) => {
@@ -68,25 +75,30 @@ extern "C" fn rust_main() -> ! {
                         match tcb.handle_syscall() {
                             Event::None => continue,
                             Event::Exit(code) => {
-                                log::info!("app{i} exit with code {code}");
+                                log::info!("{name} exit with code {code}");
                                 true
                             }
                             Event::Yield => {
-                                log::debug!("app{i} yield");
+                                log::debug!("{name} yield");
                                 false
                             }
                             Event::UnsupportedSyscall(id) => {
-                                log::error!("app{i} call an unsupported syscall {}", id.0);
+                                log::error!("{name} call an unsupported syscall {}", id.0);
                                 true
                             }
                         }
                     }
                     Trap::Exception(e) => {
-                        log::error!("app{i} was killed by {e:?}");
+                        log::error!(
+                            "{name} was killed by {e:?}, sepc = {:#x}, stval = {:#x}\n{}",
+                            tcb.pc(),
+                            stval::read(),
+                            tcb.backtrace()
+                        );
                         true
                     }
                     Trap::Interrupt(ir) => {
-                        log::error!("app{i} was killed by an unexpected interrupt {ir:?}");
+                        log::error!("{name} was killed by an unexpected interrupt {ir:?}");
                         true
                     }
                 };
@@ -107,6 +119,7 @@ extern "C" fn rust_main() -> ! {
 #[panic_handler]
 fn panic(info: &core::panic::PanicInfo) -> ! {
     println!("{info}");
+    println!("{}", kernel_backtrace::Backtrace::capture());
     system_reset(Shutdown, SystemFailure);
     loop {}
 }
@@ -123,6 +136,23 @@ mod impls {
             #[allow(deprecated)]
             sbi_rt::legacy::console_putchar(c as _);
         }
+
+        /// 以 `time` 寄存器计时，解析设备树之前不打印时间戳。
+        #[inline]
+        fn timestamp(&self) -> Option<core::time::Duration> {
+            let timebase = platform::try_get()?.timebase_frequency as u64;
+            let time = riscv::register::time::read64();
+            let nanos = (time % timebase) * 1_000_000_000 / timebase;
+            Some(core::time::Duration::new(time / timebase, nanos as _))
+        }
+
+        /// 硬件线程号在启动时保存在 `tp` 里。
+        #[inline]
+        fn hart_id(&self) -> Option<usize> {
+            let hartid: usize;
+            unsafe { core::arch::asm!("mv {}, tp", out(reg) hartid) };
+            Some(hartid)
+        }
     }
 
     pub struct SyscallContext;
@@ -167,10 +197,11 @@ mod impls {
         fn clock_gettime(&self, _caller: syscall::Caller, clock_id: ClockId, tp: usize) -> isize {
             match clock_id {
                 ClockId::CLOCK_MONOTONIC => {
-                    let time = riscv::register::time::read() * 10000 / 125;
+                    let timebase = platform::get().timebase_frequency;
+                    let time = riscv::register::time::read();
                     *unsafe { &mut *(tp as *mut TimeSpec) } = TimeSpec {
-                        tv_sec: time / 1_000_000_000,
-                        tv_nsec: time % 1_000_000_000,
+                        tv_sec: time / timebase,
+                        tv_nsec: time % timebase * 1_000_000_000 / timebase,
                     };
                     0
                 }
@@ -1,4 +1,5 @@
-﻿use kernel_context::LocalContext;
+﻿use kernel_backtrace::Backtrace;
+use kernel_context::LocalContext;
 use syscall::{Caller, SyscallId};
 
 /// 任务控制块。
@@ -39,6 +40,23 @@ impl TaskControlBlock {
         self.ctx.execute();
     }
 
+    /// 用户程序异常时的 `sepc`。
+    #[inline]
+    pub fn pc(&self) -> usize {
+        self.ctx.pc()
+    }
+
+    /// 回溯用户调用栈，只读取这个任务的用户栈。
+    pub fn backtrace(&self) -> Backtrace {
+        let stack = self.stack.as_ptr_range();
+        let stack = stack.start as usize..stack.end as usize;
+        Backtrace::user(self.ctx.pc(), self.ctx.x(8), |addr| {
+            stack
+                .contains(&addr)
+                .then(|| unsafe { *(addr as *const usize) })
+        })
+    }
+
     /// 处理系统调用，返回是否应该终止程序。
     pub fn handle_syscall(&mut self) -> SchedulingEvent {
         use syscall::{SyscallId as Id, SyscallResult as Ret};
@@ -3,6 +3,7 @@
 // #![deny(warnings)]
 
 mod process;
+mod timer;
 
 #[macro_use]
 extern crate rcore_console;
@@ -10,7 +11,7 @@ extern crate rcore_console;
 extern crate alloc;
 
 use crate::{
-    impls::{Sv39Manager, SyscallContext},
+    impls::{SvManager, SyscallContext},
     process::Process,
 };
 use alloc::{alloc::alloc, vec::Vec};
@@ -18,8 +19,8 @@ use core::alloc::Layout;
 use impls::Console;
 use kernel_context::{foreign::MultislotPortal, LocalContext};
 use kernel_vm::{
-    page_table::{MmuMeta, Sv39, VAddr, VmFlags, VmMeta, PPN, VPN},
-    AddressSpace,
+    page_table::{MmuMeta, VAddr, VmFlags, VmMeta, PPN, VPN},
+    AddressSpace, VmLayout,
 };
 use rcore_console::log;
 use riscv::register::*;
@@ -30,15 +31,24 @@ use xmas_elf::ElfFile;
 // 应用程序内联进来。
 core::arch::global_asm!(include_str!(env!("APP_ASM")));
 // 定义内核入口。
-linker::boot0!(rust_main; stack = 6 * 4096);
-// 物理内存容量 = 24 MiB。
-const MEMORY: usize = 24 << 20;
+linker::boot0!(rust_main; stack = STACK_SIZE);
+// 内核栈容量，启动栈和换栈后堆上的栈一样大。
+const STACK_SIZE: usize = 6 * 4096;
+// 内核堆容量最多 8 MiB，其余物理页交给页帧分配器。
+const HEAP: usize = 8 << 20;
+// 页表格式，启用 `sv48` 特性时使用 Sv48。
+#[cfg(not(feature = "sv48"))]
+type Sv = kernel_vm::page_table::Sv39;
+#[cfg(feature = "sv48")]
+type Sv = kernel_vm::page_table::Sv48;
 // 传送门所在虚页。
-const PROTAL_TRANSIT: VPN<Sv39> = VPN::MAX;
+const PROTAL_TRANSIT: VPN<Sv> = VPN::MAX;
 // 进程列表。
 static mut PROCESSES: Vec<Process> = Vec::new();
 
-extern "C" fn rust_main() -> ! {
+extern "C" fn rust_main(hartid: usize, dtb: usize) -> ! {
+    // 硬件线程号保存在 `tp` 里，内核不使用线程局部存储
+    unsafe { core::arch::asm!("mv tp, {}", in(reg) hartid) };
     let layout = linker::KernelLayout::locate();
     // bss 段清零
     unsafe { layout.zero_bss() };
@@ -46,46 +56,73 @@ extern "C" fn rust_main() -> ! {
     rcore_console::init_console(&Console);
     rcore_console::set_log_level(option_env!("LOG"));
     rcore_console::test_log();
+    // 解析设备树
+    let platform = unsafe { platform::init(dtb) };
+    log::info!("{platform}");
     // 初始化内核堆
+    let (_, heap) = memory(&layout);
     kernel_alloc::init(layout.start() as _);
+    unsafe { kernel_alloc::transfer(core::slice::from_raw_parts_mut(layout.end() as _, heap)) };
+    // 换到堆上的栈，以便回收启动栈
+    const STACK: Layout = unsafe { Layout::from_size_align_unchecked(STACK_SIZE, 4096) };
+    let stack = unsafe { alloc(STACK) };
+    assert!(!stack.is_null(), "failed to allocate the kernel stack");
+    unsafe { linker::switch_stack(stack as usize + STACK_SIZE, kernel_main) }
+}
+
+extern "C" fn kernel_main() -> ! {
+    let layout = linker::KernelLayout::locate();
+    // 回收启动栈
+    let boot = layout.boot();
+    let reclaimed = unsafe {
+        kernel_alloc::add_region(core::slice::from_raw_parts_mut(boot.start as _, boot.len()))
+    };
+    log::info!("reclaimed {reclaimed} bytes from .boot");
+    // 初始化页帧分配器
+    let (memory, heap) = memory(&layout);
     unsafe {
-        kernel_alloc::transfer(core::slice::from_raw_parts_mut(
-            layout.end() as _,
-            MEMORY - layout.len(),
-        ))
+        kernel_frame::init(
+            Sv::PAGE_BITS,
+            core::slice::from_raw_parts_mut(
+                (layout.end() + heap) as _,
+                memory - layout.len() - heap,
+            ),
+        )
     };
     // 建立异界传送门
     let portal_size = MultislotPortal::calculate_size(1);
-    let portal_layout = Layout::from_size_align(portal_size, 1 << Sv39::PAGE_BITS).unwrap();
+    let portal_layout = Layout::from_size_align(portal_size, 1 << Sv::PAGE_BITS).unwrap();
     let portal_ptr = unsafe { alloc(portal_layout) };
-    assert!(portal_layout.size() < 1 << Sv39::PAGE_BITS);
+    assert!(portal_layout.size() < 1 << Sv::PAGE_BITS);
     // 建立内核地址空间
-    let mut ks = kernel_space(layout, MEMORY, portal_ptr as _);
-    let portal_idx = PROTAL_TRANSIT.index_in(Sv39::MAX_LEVEL);
+    let mut ks = kernel_space(layout, memory, portal_ptr as _);
+    let portal_idx = PROTAL_TRANSIT.index_in(Sv::MAX_LEVEL);
     // 加载应用程序
-    for (i, elf) in linker::AppMeta::locate().iter().enumerate() {
+    for app in linker::AppMeta::locate().iter() {
+        let elf = app.load();
         let base = elf.as_ptr() as usize;
-        log::info!("detect app[{i}]: {base:#x}..{:#x}", base + elf.len());
-        if let Some(process) = Process::new(ElfFile::new(elf).unwrap()) {
-            // 映射异界传送门
-            process.address_space.root()[portal_idx] = ks.root()[portal_idx];
+        log::info!("detect {}: {base:#x}..{:#x}", app.name(), base + elf.len());
+        if let Some(process) = Process::new(app.name(), ElfFile::new(elf).unwrap()) {
+            // 映射异界传送门，页表属于内核地址空间，复制的表项不带所有权标记，进程结束时不会释放
+            process.address_space.root()[portal_idx] =
+                VmFlags::VALID.build_pte(ks.root()[portal_idx].ppn());
             unsafe { PROCESSES.push(process) };
         }
     }
 
     // 建立调度栈
-    const PAGE: Layout =
-        unsafe { Layout::from_size_align_unchecked(2 << Sv39::PAGE_BITS, 1 << Sv39::PAGE_BITS) };
     let pages = 2;
-    let stack = unsafe { alloc(PAGE) };
+    let stack = kernel_frame::alloc(pages, kernel_frame::Owner::Stack)
+        .unwrap()
+        .as_ptr();
     ks.map_extern(
-        VPN::new((1 << 26) - pages)..VPN::new(1 << 26),
-        PPN::new(stack as usize >> Sv39::PAGE_BITS),
+        VmLayout::<Sv>::top_pages(pages),
+        PPN::new(linker::virt_to_phys(stack as usize) >> Sv::PAGE_BITS),
         VmFlags::build_from_str("_WRV"),
     );
     // 建立调度线程，目的是划分异常域。调度线程上发生内核异常时会回到这个控制流处理
     let mut scheduling = LocalContext::thread(schedule as _, false);
-    *scheduling.sp_mut() = 1 << 38;
+    *scheduling.sp_mut() = VmLayout::<Sv>::USER_TOP;
     unsafe { scheduling.execute() };
     log::error!("stval = {:#x}", stval::read());
     panic!("trap from scheduling thread: {:?}", scause::read().cause());
@@ -97,12 +134,22 @@ extern "C" fn schedule() -> ! {
     // 初始化 syscall
     syscall::init_io(&SyscallContext);
     syscall::init_process(&SyscallContext);
+    syscall::init_memory(&SyscallContext);
     syscall::init_scheduling(&SyscallContext);
     syscall::init_clock(&SyscallContext);
+    timer::init();
     while !unsafe { PROCESSES.is_empty() } {
+        let name = unsafe { PROCESSES[0].name };
         let ctx = unsafe { &mut PROCESSES[0].context };
+        timer::set_next();
         unsafe { ctx.execute(portal, ()) };
         match scause::read().cause() {
+            scause::Trap::Interrupt(scause::Interrupt::SupervisorTimer) => {
+                timer::stop();
+                log::trace!("{name} timeout");
+                // 轮到下一个进程
+                unsafe { PROCESSES.rotate_left(1) };
+            }
             scause::Trap::Exception(scause::Exception::UserEnvCall) => {
                 use syscall::{SyscallId as Id, SyscallResult as Ret};
 
@@ -112,6 +159,7 @@ extern "C" fn schedule() -> ! {
                 match syscall::handle(Caller { entity: 0, flow: 0 }, id, args) {
                     Ret::Done(ret) => match id {
                         Id::EXIT => unsafe {
+                            log::info!("{name} exit with code {}", args[0] as isize);
                             PROCESSES.remove(0);
                         },
                         _ => {
@@ -120,39 +168,71 @@ extern "C" fn schedule() -> ! {
                         }
                     },
                     Ret::Unsupported(_) => {
-                        log::info!("id = {id:?}");
+                        log::info!("{name} call an unsupported syscall {id:?}");
                         unsafe { PROCESSES.remove(0) };
                     }
                 }
             }
             e => {
-                log::error!(
-                    "unsupported trap: {e:?}, stval = {:#x}, sepc = {:#x}",
-                    stval::read(),
-                    ctx.context.pc()
-                );
+                report_user_fault(name, e, &ctx.context, unsafe {
+                    &PROCESSES[0].address_space
+                });
                 unsafe { PROCESSES.remove(0) };
             }
         }
     }
+    kernel_alloc::report();
+    log::info!("{}", kernel_frame::stats());
     system_reset(Shutdown, NoReason);
     unreachable!()
 }
 
+/// 打印用户程序的异常现场和调用栈。
+fn report_user_fault(
+    who: impl core::fmt::Display,
+    trap: scause::Trap,
+    ctx: &kernel_context::LocalContext,
+    space: &AddressSpace<Sv, SvManager>,
+) {
+    const READABLE: VmFlags<Sv> = VmFlags::build_from_str("RV");
+    let backtrace = kernel_backtrace::Backtrace::user(ctx.pc(), ctx.x(8), |addr| {
+        space
+            .translate::<usize>(VAddr::new(addr), READABLE)
+            .map(|ptr| unsafe { *ptr.as_ptr() })
+    });
+    log::error!(
+        "{who} was killed by {trap:?}, sepc = {:#x}, stval = {:#x}\n{backtrace}",
+        ctx.pc(),
+        stval::read()
+    );
+}
+
 /// Rust 异常处理函数，以异常方式关机。
 #[panic_handler]
 fn panic(info: &core::panic::PanicInfo) -> ! {
     log::error!("{info}");
+    log::error!("{}", kernel_backtrace::Backtrace::capture());
     system_reset(Shutdown, SystemFailure);
     loop {}
 }
 
+/// 内核之后的物理内存都归内核管理，返回从内核起始处算起的内存总量和内核堆的容量。
+///
+/// 内存不多时内核堆只占内核之后的一半，另一半留给页帧分配器。
+fn memory(layout: &linker::KernelLayout) -> (usize, usize) {
+    let memory = linker::phys_to_virt(platform::get().memory.end) - layout.start();
+    let free = memory
+        .checked_sub(layout.len())
+        .expect("physical memory is smaller than the kernel image");
+    (memory, HEAP.min((free / 2) & !((1 << Sv::PAGE_BITS) - 1)))
+}
+
 fn kernel_space(
     layout: linker::KernelLayout,
     memory: usize,
     portal: usize,
-) -> AddressSpace<Sv39, Sv39Manager> {
-    let mut space = AddressSpace::<Sv39, Sv39Manager>::new();
+) -> AddressSpace<Sv, SvManager> {
+    let mut space = AddressSpace::<Sv, SvManager>::new();
     for region in layout.iter() {
         log::info!("{region}");
         use linker::KernelRegionTitle::*;
@@ -161,11 +241,11 @@ fn kernel_space(
             Rodata => "__RV",
             Data | Boot => "_WRV",
         };
-        let s = VAddr::<Sv39>::new(region.range.start);
-        let e = VAddr::<Sv39>::new(region.range.end);
+        let s = VAddr::<Sv>::new(region.range.start);
+        let e = VAddr::<Sv>::new(region.range.end);
         space.map_extern(
             s.floor()..e.ceil(),
-            PPN::new(s.floor().val()),
+            PPN::new(linker::virt_to_phys(region.range.start) >> Sv::PAGE_BITS),
             VmFlags::build_from_str(flags),
         )
     }
@@ -174,96 +254,107 @@ fn kernel_space(
         layout.end(),
         layout.start() + memory
     );
-    let s = VAddr::<Sv39>::new(layout.end());
-    let e = VAddr::<Sv39>::new(layout.start() + memory);
+    let s = VAddr::<Sv>::new(layout.end());
+    let e = VAddr::<Sv>::new(layout.start() + memory);
     space.map_extern(
         s.floor()..e.ceil(),
-        PPN::new(s.floor().val()),
+        PPN::new(linker::virt_to_phys(layout.end()) >> Sv::PAGE_BITS),
         VmFlags::build_from_str("_WRV"),
     );
     space.map_extern(
         PROTAL_TRANSIT..PROTAL_TRANSIT + 1,
-        PPN::new(portal >> Sv39::PAGE_BITS),
+        PPN::new(linker::virt_to_phys(portal) >> Sv::PAGE_BITS),
         VmFlags::build_from_str("__G_XWRV"),
     );
     println!();
-    unsafe { satp::set(satp::Mode::Sv39, 0, space.root_ppn().val()) };
+    unsafe { satp::write(VmLayout::<Sv>::satp(space.root_ppn())) };
     space
 }
 
 /// 各种接口库的实现。
 mod impls {
-    use crate::PROCESSES;
-    use alloc::alloc::alloc_zeroed;
-    use core::{alloc::Layout, ptr::NonNull};
+    use crate::{Sv, PROCESSES};
+    use core::ptr::NonNull;
+    use kernel_frame::Owner;
     use kernel_vm::{
-        page_table::{MmuMeta, Pte, Sv39, VAddr, VmFlags, PPN, VPN},
+        page_table::{MmuMeta, Pte, VAddr, VmFlags, PPN},
         PageManager,
     };
     use rcore_console::log;
     use syscall::*;
 
     #[repr(transparent)]
-    pub struct Sv39Manager(NonNull<Pte<Sv39>>);
+    pub struct SvManager(NonNull<Pte<Sv>>);
 
-    impl Sv39Manager {
-        const OWNED: VmFlags<Sv39> = unsafe { VmFlags::from_raw(1 << 8) };
+    impl SvManager {
+        const OWNED: VmFlags<Sv> = unsafe { VmFlags::from_raw(1 << 8) };
+        const USER: VmFlags<Sv> = VmFlags::build_from_str("U____");
 
+        /// 给 `flags` 加上所有权标记，这样映射的页帧在地址空间回收时释放。
         #[inline]
-        fn page_alloc<T>(count: usize) -> *mut T {
-            unsafe {
-                alloc_zeroed(Layout::from_size_align_unchecked(
-                    count << Sv39::PAGE_BITS,
-                    1 << Sv39::PAGE_BITS,
-                ))
-            }
-            .cast()
+        pub fn owned(mut flags: VmFlags<Sv>) -> VmFlags<Sv> {
+            flags |= Self::OWNED;
+            flags
+        }
+
+        #[inline]
+        fn page_alloc<T>(count: usize, owner: Owner) -> *mut T {
+            kernel_frame::alloc(count, owner).unwrap().as_ptr().cast()
         }
     }
 
-    impl PageManager<Sv39> for Sv39Manager {
+    impl PageManager<Sv> for SvManager {
         #[inline]
         fn new_root() -> Self {
-            Self(NonNull::new(Self::page_alloc(1)).unwrap())
+            Self(NonNull::new(Self::page_alloc(1, Owner::PageTable)).unwrap())
         }
 
         #[inline]
-        fn root_ppn(&self) -> PPN<Sv39> {
-            PPN::new(self.0.as_ptr() as usize >> Sv39::PAGE_BITS)
+        fn root_ppn(&self) -> PPN<Sv> {
+            PPN::new(linker::virt_to_phys(self.0.as_ptr() as usize) >> Sv::PAGE_BITS)
         }
 
         #[inline]
-        fn root_ptr(&self) -> NonNull<Pte<Sv39>> {
+        fn root_ptr(&self) -> NonNull<Pte<Sv>> {
             self.0
         }
 
         #[inline]
-        fn p_to_v<T>(&self, ppn: PPN<Sv39>) -> NonNull<T> {
-            unsafe { NonNull::new_unchecked(VPN::<Sv39>::new(ppn.val()).base().as_mut_ptr()) }
+        fn p_to_v<T>(&self, ppn: PPN<Sv>) -> NonNull<T> {
+            let va = linker::phys_to_virt(ppn.val() << Sv::PAGE_BITS);
+            unsafe { NonNull::new_unchecked(va as *mut T) }
         }
 
         #[inline]
-        fn v_to_p<T>(&self, ptr: NonNull<T>) -> PPN<Sv39> {
-            PPN::new(VAddr::<Sv39>::new(ptr.as_ptr() as _).floor().val())
+        fn v_to_p<T>(&self, ptr: NonNull<T>) -> PPN<Sv> {
+            PPN::new(linker::virt_to_phys(ptr.as_ptr() as usize) >> Sv::PAGE_BITS)
         }
 
         #[inline]
-        fn check_owned(&self, pte: Pte<Sv39>) -> bool {
+        fn check_owned(&self, pte: Pte<Sv>) -> bool {
             pte.flags().contains(Self::OWNED)
         }
 
         #[inline]
-        fn allocate(&mut self, len: usize, flags: &mut VmFlags<Sv39>) -> NonNull<u8> {
+        fn allocate(&mut self, len: usize, flags: &mut VmFlags<Sv>) -> NonNull<u8> {
+            // 页表中间节点只有 V 位，带 U 位的是用户页
+            let owner = if flags.contains(Self::USER) {
+                Owner::User
+            } else {
+                Owner::PageTable
+            };
             *flags |= Self::OWNED;
-            NonNull::new(Self::page_alloc(len)).unwrap()
+            NonNull::new(Self::page_alloc(len, owner)).unwrap()
         }
 
-        fn deallocate(&mut self, _pte: Pte<Sv39>, _len: usize) -> usize {
-            todo!()
+        #[inline]
+        fn deallocate(&mut self, pte: Pte<Sv>, len: usize) -> usize {
+            kernel_frame::release(self.p_to_v(pte.ppn()), len)
         }
 
+        #[inline]
         fn drop_root(&mut self) {
-            todo!()
+            kernel_frame::release(self.0.cast(), 1);
         }
     }
 
@@ -275,6 +366,23 @@ mod impls {
             #[allow(deprecated)]
             sbi_rt::legacy::console_putchar(c as _);
         }
+
+        /// 以 `time` 寄存器计时，解析设备树之前不打印时间戳。
+        #[inline]
+        fn timestamp(&self) -> Option<core::time::Duration> {
+            let timebase = platform::try_get()?.timebase_frequency as u64;
+            let time = riscv::register::time::read64();
+            let nanos = (time % timebase) * 1_000_000_000 / timebase;
+            Some(core::time::Duration::new(time / timebase, nanos as _))
+        }
+
+        /// 硬件线程号在启动时保存在 `tp` 里。
+        #[inline]
+        fn hart_id(&self) -> Option<usize> {
+            let hartid: usize;
+            unsafe { core::arch::asm!("mv {}, tp", out(reg) hartid) };
+            Some(hartid)
+        }
     }
 
     pub struct SyscallContext;
@@ -283,7 +391,7 @@ mod impls {
         fn write(&self, caller: Caller, fd: usize, buf: usize, count: usize) -> isize {
             match fd {
                 STDOUT | STDDEBUG => {
-                    const READABLE: VmFlags<Sv39> = VmFlags::build_from_str("RV");
+                    const READABLE: VmFlags<Sv> = VmFlags::build_from_str("RV");
                     if let Some(ptr) = unsafe { PROCESSES.get_mut(caller.entity) }
                         .unwrap()
                         .address_space
@@ -316,6 +424,14 @@ mod impls {
         }
     }
 
+    impl Memory for SyscallContext {
+        fn brk(&self, caller: Caller, addr: usize) -> isize {
+            unsafe { PROCESSES.get_mut(caller.entity) }
+                .unwrap()
+                .brk(addr) as _
+        }
+    }
+
     impl Scheduling for SyscallContext {
         #[inline]
         fn sched_yield(&self, _caller: Caller) -> isize {
@@ -326,7 +442,7 @@ mod impls {
     impl Clock for SyscallContext {
         #[inline]
         fn clock_gettime(&self, caller: Caller, clock_id: ClockId, tp: usize) -> isize {
-            const WRITABLE: VmFlags<Sv39> = VmFlags::build_from_str("W_V");
+            const WRITABLE: VmFlags<Sv> = VmFlags::build_from_str("W_V");
             match clock_id {
                 ClockId::CLOCK_MONOTONIC => {
                     if let Some(mut ptr) = unsafe { PROCESSES.get(caller.entity) }
@@ -334,10 +450,11 @@ mod impls {
                         .address_space
                         .translate(VAddr::new(tp), WRITABLE)
                     {
-                        let time = riscv::register::time::read() * 10000 / 125;
+                        let timebase = platform::get().timebase_frequency;
+                        let time = riscv::register::time::read();
                         *unsafe { ptr.as_mut() } = TimeSpec {
-                            tv_sec: time / 1_000_000_000,
-                            tv_nsec: time % 1_000_000_000,
+                            tv_sec: time / timebase,
+                            tv_nsec: time % timebase * 1_000_000_000 / timebase,
                         };
                         0
                     } else {
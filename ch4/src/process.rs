@@ -1,10 +1,10 @@
-﻿use crate::Sv39Manager;
-use alloc::alloc::alloc_zeroed;
-use core::{alloc::Layout, str::FromStr};
+use crate::{Sv, SvManager};
+use core::str::FromStr;
 use kernel_context::{foreign::ForeignContext, LocalContext};
+use kernel_frame::Owner;
 use kernel_vm::{
-    page_table::{MmuMeta, Sv39, VAddr, VmFlags, PPN, VPN},
-    AddressSpace,
+    page_table::{MmuMeta, VAddr, VmFlags, VmMeta, PPN, VPN},
+    AddressSpace, VmLayout,
 };
 use rcore_console::log;
 use xmas_elf::{
@@ -12,14 +12,23 @@ use xmas_elf::{
     program, ElfFile,
 };
 
+/// 程序断点的上限，堆不能长进用户栈和跳板页。
+const HEAP_TOP: usize = VmLayout::<Sv>::USER_TOP >> 2;
+
 /// 进程。
 pub struct Process {
+    /// 应用程序名字
+    pub name: &'static str,
     pub context: ForeignContext,
-    pub address_space: AddressSpace<Sv39, Sv39Manager>,
+    pub address_space: AddressSpace<Sv, SvManager>,
+    /// 堆底，紧接在 ELF 的最后一个段之后
+    pub heap_bottom: usize,
+    /// 程序断点（program break），即堆顶
+    pub program_brk: usize,
 }
 
 impl Process {
-    pub fn new(elf: ElfFile) -> Option<Self> {
+    pub fn new(name: &'static str, elf: ElfFile) -> Option<Self> {
         let entry = match elf.header.pt2 {
             HeaderPt2::Header64(pt2)
                 if pt2.type_.as_type() == header::Type::Executable
@@ -30,10 +39,11 @@ impl Process {
             _ => None?,
         };
 
-        const PAGE_SIZE: usize = 1 << Sv39::PAGE_BITS;
+        const PAGE_SIZE: usize = 1 << Sv::PAGE_BITS;
         const PAGE_MASK: usize = PAGE_SIZE - 1;
 
         let mut address_space = AddressSpace::new();
+        let mut heap_bottom = 0;
         for program in elf.program_iter() {
             if !matches!(program.get_type(), Ok(program::Type::Load)) {
                 continue;
@@ -43,6 +53,7 @@ impl Process {
             let len_file = program.file_size() as usize;
             let off_mem = program.virtual_addr() as usize;
             let end_mem = off_mem + program.mem_size() as usize;
+            heap_bottom = heap_bottom.max(end_mem);
             assert_eq!(off_file & PAGE_MASK, off_mem & PAGE_MASK);
 
             let mut flags: [u8; 5] = *b"U___V";
@@ -62,26 +73,80 @@ impl Process {
                 VmFlags::from_str(unsafe { core::str::from_utf8_unchecked(&flags) }).unwrap(),
             );
         }
-        let stack = unsafe {
-            alloc_zeroed(Layout::from_size_align_unchecked(
-                2 << Sv39::PAGE_BITS,
-                1 << Sv39::PAGE_BITS,
-            ))
-        };
+        // 堆从 ELF 最后一个段之后的页开始
+        let heap_bottom = (heap_bottom + PAGE_MASK) & !PAGE_MASK;
+        let stack = kernel_frame::alloc(2, Owner::Stack).unwrap().as_ptr();
         address_space.map_extern(
-            VPN::new((1 << 26) - 2)..VPN::new(1 << 26),
-            PPN::new(stack as usize >> Sv39::PAGE_BITS),
-            VmFlags::build_from_str("U_WRV"),
+            VmLayout::<Sv>::top_pages(2),
+            PPN::new(linker::virt_to_phys(stack as usize) >> Sv::PAGE_BITS),
+            SvManager::owned(VmFlags::build_from_str("U_WRV")),
         );
 
         log::info!("process entry = {:#x}", entry);
 
         let mut context = LocalContext::user(entry);
-        let satp = (8 << 60) | address_space.root_ppn().val();
-        *context.sp_mut() = 1 << 38;
+        let satp = VmLayout::<Sv>::satp(address_space.root_ppn());
+        *context.sp_mut() = VmLayout::<Sv>::USER_TOP;
         Some(Self {
+            name,
             context: ForeignContext { context, satp },
             address_space,
+            heap_bottom,
+            program_brk: heap_bottom,
         })
     }
+
+    /// 把程序断点调整到 `addr`，返回调整后的程序断点。
+    ///
+    /// `addr` 低于堆底或高于 [`HEAP_TOP`] 时不调整，可以用 `brk(0)` 查询当前的程序断点。
+    /// 堆收缩时已映射的页保留，再次扩张时直接使用。
+    /// 新的堆区域与其他映射重叠或者页帧不足时不调整。
+    pub fn brk(&mut self, addr: usize) -> usize {
+        const VALID: VmFlags<Sv> = VmFlags::build_from_str("V");
+        if addr < self.heap_bottom || addr > HEAP_TOP {
+            return self.program_brk;
+        }
+        let end = VAddr::<Sv>::new(addr).ceil().val();
+        let space = &self.address_space;
+        let mapped = |vpn: usize| {
+            space
+                .translate::<u8>(VPN::<Sv>::new(vpn).base(), VALID)
+                .is_some()
+        };
+        // 堆总是从堆底连续映射，找到第一个未映射的页，从那里到新的堆顶都不能有映射
+        let start = (VAddr::<Sv>::new(self.program_brk).ceil().val()..end)
+            .find(|&vpn| !mapped(vpn))
+            .unwrap_or(end);
+        if (start..end).any(mapped) {
+            return self.program_brk;
+        }
+        // 逐页映射，页帧不足时撤销这次映射的页
+        for vpn in start..end {
+            // 留出映射这一页可能需要的页表页
+            let frame = if kernel_frame::stats().free() > Sv::MAX_LEVEL {
+                kernel_frame::alloc(1, Owner::User)
+            } else {
+                None
+            };
+            let Some(frame) = frame else {
+                for vpn in start..vpn {
+                    let page = VPN::<Sv>::new(vpn);
+                    let ptr = self
+                        .address_space
+                        .translate::<u8>(page.base(), VALID)
+                        .unwrap();
+                    self.address_space.unmap(page..VPN::new(vpn + 1));
+                    kernel_frame::release(ptr, 1);
+                }
+                return self.program_brk;
+            };
+            self.address_space.map_extern(
+                VPN::new(vpn)..VPN::new(vpn + 1),
+                PPN::new(linker::virt_to_phys(frame.as_ptr() as usize) >> Sv::PAGE_BITS),
+                SvManager::owned(VmFlags::build_from_str("U_WRV")),
+            );
+        }
+        self.program_brk = addr;
+        addr
+    }
 }
@@ -0,0 +1,57 @@
+//! 时钟中断。
+//!
+//! 每次运行用户程序之前设置一个时间片之后的时钟中断，用户程序用完时间片时回到内核的调度循环。
+//! 时间片以微秒计，默认 10 ms，构建时可以用 `--quantum <us>` 指定。
+
+use riscv::register::{scause, sie, time};
+
+/// 时间片，单位微秒。
+const QUANTUM_US: u64 = match option_env!("QUANTUM") {
+    Some(s) => parse_us(s),
+    None => 10_000,
+};
+
+/// 打开时钟中断。
+#[inline]
+pub fn init() {
+    unsafe { sie::set_stimer() };
+}
+
+/// 从现在起一个时间片之后产生时钟中断。
+pub fn set_next() {
+    let ticks = platform::get().timebase_frequency as u64 * QUANTUM_US / 1_000_000;
+    sbi_rt::set_timer(time::read64() + ticks.max(1));
+}
+
+/// 取消时钟中断，同时清除已经发生但还没处理的时钟中断。
+#[inline]
+pub fn stop() {
+    sbi_rt::set_timer(u64::MAX);
+}
+
+/// 用户程序是否因为用完时间片回到内核。
+#[inline]
+pub fn expired() -> bool {
+    matches!(
+        scause::read().cause(),
+        scause::Trap::Interrupt(scause::Interrupt::SupervisorTimer)
+    )
+}
+
+/// 在编译期解析时间片，不是正整数时编译失败。
+const fn parse_us(s: &str) -> u64 {
+    let bytes = s.as_bytes();
+    assert!(!bytes.is_empty(), "QUANTUM must be a positive integer");
+    let mut ans = 0u64;
+    let mut i = 0;
+    while i < bytes.len() {
+        assert!(
+            bytes[i].is_ascii_digit(),
+            "QUANTUM must be a positive integer"
+        );
+        ans = ans * 10 + (bytes[i] - b'0') as u64;
+        i += 1;
+    }
+    assert!(ans > 0, "QUANTUM must be a positive integer");
+    ans
+}
@@ -0,0 +1,45 @@
+//! 控制台终端。
+//!
+//! 本章没有信号，Ctrl-C 只丢弃正在编辑的行。
+
+use rcore_console::tty::{Tty, TtyMode};
+use spin::Mutex;
+use syscall::{Termios, EAGAIN};
+
+/// 控制台终端。
+static TTY: Mutex<Tty> = Mutex::new(Tty::new());
+
+/// 从终端读取，还没有输入时返回 `-EAGAIN`。
+pub fn read(buf: &mut [u8]) -> isize {
+    let mut tty = TTY.lock();
+    tty.poll();
+    tty.read(buf).map_or(-EAGAIN, |n| n as _)
+}
+
+/// 当前的终端设置。
+pub fn termios() -> Termios {
+    let tty = TTY.lock();
+    let mut lflag = 0;
+    if tty.isig() {
+        lflag |= Termios::ISIG;
+    }
+    if tty.mode() == TtyMode::Canonical {
+        lflag |= Termios::ICANON;
+    }
+    if tty.echo() {
+        lflag |= Termios::ECHO;
+    }
+    Termios { lflag }
+}
+
+/// 修改终端设置。
+pub fn set_termios(termios: &Termios) {
+    let mut tty = TTY.lock();
+    tty.set_isig(termios.lflag & Termios::ISIG != 0);
+    tty.set_mode(if termios.lflag & Termios::ICANON != 0 {
+        TtyMode::Canonical
+    } else {
+        TtyMode::Raw
+    });
+    tty.set_echo(termios.lflag & Termios::ECHO != 0);
+}
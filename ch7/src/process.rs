@@ -1,11 +1,13 @@
-use crate::{map_portal, Sv39Manager};
-use alloc::{alloc::alloc_zeroed, boxed::Box, vec::Vec};
-use core::{alloc::Layout, str::FromStr};
+use crate::{map_portal, Sv, SvManager};
+use alloc::{boxed::Box, vec::Vec};
+use core::str::FromStr;
 use easy_fs::FileHandle;
 use kernel_context::{foreign::ForeignContext, LocalContext};
+use kernel_frame::Owner;
+use kernel_mmap::Mappings;
 use kernel_vm::{
-    page_table::{MmuMeta, Sv39, VAddr, VmFlags, PPN, VPN},
-    AddressSpace,
+    page_table::{MmuMeta, VAddr, VmFlags, VmMeta, PPN, VPN},
+    AddressSpace, VmLayout,
 };
 use rcore_task_manage::ProcId;
 use signal::Signal;
@@ -16,39 +18,60 @@ use xmas_elf::{
     program, ElfFile,
 };
 
+/// 程序断点的上限，堆不能长进 mmap 区域、用户栈和跳板页。
+const HEAP_TOP: usize = VmLayout::<Sv>::USER_TOP >> 2;
+
 /// 进程。
 pub struct Process {
     /// 不可变
     pub pid: ProcId,
     /// 可变
     pub context: ForeignContext,
-    pub address_space: AddressSpace<Sv39, Sv39Manager>,
+    pub address_space: AddressSpace<Sv, SvManager>,
+    /// 堆底，紧接在 ELF 的最后一个段之后
+    pub heap_bottom: usize,
+    /// 程序断点（program break），即堆顶
+    pub program_brk: usize,
 
     /// 文件描述符表
     pub fd_table: Vec<Option<Mutex<FileHandle>>>,
 
     /// 信号模块
     pub signal: Box<dyn Signal>,
+
+    /// `mmap` 建立的映射
+    pub mmaps: Mappings<Sv>,
 }
 
 impl Process {
     pub fn exec(&mut self, elf: ElfFile) {
-        let proc = Process::from_elf(elf).unwrap();
-        self.address_space = proc.address_space;
-        self.context = proc.context;
+        let mut proc = Process::from_elf(self.pid, elf).unwrap();
+        // 换下的地址空间和映射随 `proc` 一起释放，共享映射的脏页在那时写回
+        core::mem::swap(&mut self.address_space, &mut proc.address_space);
+        core::mem::swap(&mut self.mmaps, &mut proc.mmaps);
+        core::mem::swap(&mut self.context, &mut proc.context);
+        self.heap_bottom = proc.heap_bottom;
+        self.program_brk = proc.program_brk;
     }
 
-    pub fn fork(&mut self) -> Option<Process> {
-        // 子进程 pid
-        let pid = ProcId::new();
+    pub fn fork(&mut self, pid: ProcId) -> Option<Process> {
+        // 空闲页帧不足以复制地址空间时失败，而不是复制到一半时耗尽
+        let areas = &self.address_space.areas;
+        let pages: usize = areas.iter().map(|r| r.end.val() - r.start.val()).sum();
+        // 每个页表页有 512 项，区域的首尾可能各多占一个页表页
+        let tables = (pages >> 9) + areas.len() * 2 * (Sv::MAX_LEVEL + 1);
+        if kernel_frame::stats().free() < pages + tables + self.mmaps.fork_cost() {
+            return None;
+        }
         // 复制父进程地址空间
         let parent_addr_space = &self.address_space;
-        let mut address_space: AddressSpace<Sv39, Sv39Manager> = AddressSpace::new();
+        let mut address_space: AddressSpace<Sv, SvManager> = AddressSpace::new();
         parent_addr_space.cloneself(&mut address_space);
+        let mmaps = self.mmaps.fork(&mut self.address_space, &mut address_space);
         map_portal(&address_space);
         // 复制父进程上下文
         let context = self.context.context.clone();
-        let satp = (8 << 60) | address_space.root_ppn().val();
+        let satp = VmLayout::<Sv>::satp(address_space.root_ppn());
         let foreign_ctx = ForeignContext { context, satp };
         // 复制父进程文件符描述表
         let mut new_fd_table: Vec<Option<Mutex<FileHandle>>> = Vec::new();
@@ -63,12 +86,69 @@ impl Process {
             pid,
             context: foreign_ctx,
             address_space,
+            heap_bottom: self.heap_bottom,
+            program_brk: self.program_brk,
             fd_table: new_fd_table,
             signal: self.signal.from_fork(),
+            mmaps,
         })
     }
 
-    pub fn from_elf(elf: ElfFile) -> Option<Self> {
+    /// 把程序断点调整到 `addr`，返回调整后的程序断点。
+    ///
+    /// `addr` 低于堆底或高于 [`HEAP_TOP`] 时不调整，可以用 `brk(0)` 查询当前的程序断点。
+    /// 堆收缩时已映射的页保留，再次扩张时直接使用。
+    /// 新的堆区域与其他映射重叠或者页帧不足时不调整。
+    pub fn brk(&mut self, addr: usize) -> usize {
+        const VALID: VmFlags<Sv> = VmFlags::build_from_str("V");
+        if addr < self.heap_bottom || addr > HEAP_TOP {
+            return self.program_brk;
+        }
+        let end = VAddr::<Sv>::new(addr).ceil().val();
+        let space = &self.address_space;
+        let mapped = |vpn: usize| {
+            space
+                .translate::<u8>(VPN::<Sv>::new(vpn).base(), VALID)
+                .is_some()
+        };
+        // 堆总是从堆底连续映射，找到第一个未映射的页，从那里到新的堆顶都不能有映射
+        let start = (VAddr::<Sv>::new(self.program_brk).ceil().val()..end)
+            .find(|&vpn| !mapped(vpn))
+            .unwrap_or(end);
+        if (start..end).any(mapped) {
+            return self.program_brk;
+        }
+        // 逐页映射，页帧不足时撤销这次映射的页
+        for vpn in start..end {
+            // 留出映射这一页可能需要的页表页
+            let frame = if kernel_frame::stats().free() > Sv::MAX_LEVEL {
+                kernel_frame::alloc(1, Owner::User)
+            } else {
+                None
+            };
+            let Some(frame) = frame else {
+                for vpn in start..vpn {
+                    let page = VPN::<Sv>::new(vpn);
+                    let ptr = self
+                        .address_space
+                        .translate::<u8>(page.base(), VALID)
+                        .unwrap();
+                    self.address_space.unmap(page..VPN::new(vpn + 1));
+                    kernel_frame::release(ptr, 1);
+                }
+                return self.program_brk;
+            };
+            self.address_space.map_extern(
+                VPN::new(vpn)..VPN::new(vpn + 1),
+                PPN::new(linker::virt_to_phys(frame.as_ptr() as usize) >> Sv::PAGE_BITS),
+                SvManager::owned(VmFlags::build_from_str("U_WRV")),
+            );
+        }
+        self.program_brk = addr;
+        addr
+    }
+
+    pub fn from_elf(pid: ProcId, elf: ElfFile) -> Option<Self> {
         let entry = match elf.header.pt2 {
             HeaderPt2::Header64(pt2)
                 if pt2.type_.as_type() == header::Type::Executable
@@ -79,10 +159,11 @@ impl Process {
             _ => None?,
         };
 
-        const PAGE_SIZE: usize = 1 << Sv39::PAGE_BITS;
+        const PAGE_SIZE: usize = 1 << Sv::PAGE_BITS;
         const PAGE_MASK: usize = PAGE_SIZE - 1;
 
         let mut address_space = AddressSpace::new();
+        let mut heap_bottom = 0;
         for program in elf.program_iter() {
             if !matches!(program.get_type(), Ok(program::Type::Load)) {
                 continue;
@@ -92,6 +173,7 @@ impl Process {
             let len_file = program.file_size() as usize;
             let off_mem = program.virtual_addr() as usize;
             let end_mem = off_mem + program.mem_size() as usize;
+            heap_bottom = heap_bottom.max(end_mem);
             assert_eq!(off_file & PAGE_MASK, off_mem & PAGE_MASK);
 
             let mut flags: [u8; 5] = *b"U___V";
@@ -111,28 +193,27 @@ impl Process {
                 VmFlags::from_str(unsafe { core::str::from_utf8_unchecked(&flags) }).unwrap(),
             );
         }
+        // 堆从 ELF 最后一个段之后的页开始
+        let heap_bottom = (heap_bottom + PAGE_MASK) & !PAGE_MASK;
         // 映射用户栈
-        let stack = unsafe {
-            alloc_zeroed(Layout::from_size_align_unchecked(
-                2 << Sv39::PAGE_BITS,
-                1 << Sv39::PAGE_BITS,
-            ))
-        };
+        let stack = kernel_frame::alloc(2, Owner::Stack).unwrap().as_ptr();
         address_space.map_extern(
-            VPN::new((1 << 26) - 2)..VPN::new(1 << 26),
-            PPN::new(stack as usize >> Sv39::PAGE_BITS),
-            VmFlags::build_from_str("U_WRV"),
+            VmLayout::<Sv>::top_pages(2),
+            PPN::new(linker::virt_to_phys(stack as usize) >> Sv::PAGE_BITS),
+            SvManager::owned(VmFlags::build_from_str("U_WRV")),
         );
         // 映射异界传送门
         map_portal(&address_space);
 
         let mut context = LocalContext::user(entry);
-        let satp = (8 << 60) | address_space.root_ppn().val();
-        *context.sp_mut() = 1 << 38;
+        let satp = VmLayout::<Sv>::satp(address_space.root_ppn());
+        *context.sp_mut() = VmLayout::<Sv>::USER_TOP;
         Some(Self {
-            pid: ProcId::new(),
+            pid,
             context: ForeignContext { context, satp },
             address_space,
+            heap_bottom,
+            program_brk: heap_bottom,
             fd_table: vec![
                 // Stdin
                 Some(Mutex::new(FileHandle::empty(true, false))),
@@ -140,6 +221,20 @@ impl Process {
                 Some(Mutex::new(FileHandle::empty(false, true))),
             ],
             signal: Box::new(SignalImpl::new()),
+            mmaps: Mappings::new(),
         })
     }
+
+    /// 处理缺页，返回是否已解决。
+    #[inline]
+    pub fn page_fault(&mut self, addr: usize) -> bool {
+        self.mmaps.page_fault(&mut self.address_space, addr)
+    }
+}
+
+impl Drop for Process {
+    fn drop(&mut self) {
+        // 进程无论以何种方式结束，共享映射的脏页都要写回
+        self.mmaps.sync_all(&self.address_space);
+    }
 }
@@ -1,20 +1,25 @@
-use crate::KERNEL_SPACE;
-use alloc::{
-    alloc::{alloc_zeroed, dealloc},
-    sync::Arc,
-};
-use core::{alloc::Layout, ptr::NonNull};
+use crate::{Sv, KERNEL_SPACE};
+use alloc::sync::Arc;
+use core::ptr::NonNull;
 use easy_fs::BlockDevice;
-use kernel_vm::page_table::{MmuMeta, Sv39, VAddr, VmFlags};
+use kernel_frame::Owner;
+use kernel_vm::page_table::{VAddr, VmFlags};
 use spin::{Lazy, Mutex};
 use virtio_drivers::{Hal, VirtIOBlk, VirtIOHeader};
 
-const VIRTIO0: usize = 0x10001000;
+/// virtio 块设备的设备号。
+const VIRTIO_BLOCK: u32 = 2;
 
 pub static BLOCK_DEVICE: Lazy<Arc<dyn BlockDevice>> = Lazy::new(|| {
+    // 未插入设备的槽位设备号为 0，取第一个块设备
+    let base = platform::get()
+        .virtio()
+        .map(|device| linker::phys_to_virt(device.range.start))
+        .find(|base| unsafe { ((base + 8) as *const u32).read_volatile() } == VIRTIO_BLOCK)
+        .expect("no virtio block device");
     Arc::new(unsafe {
         VirtIOBlock(Mutex::new(
-            VirtIOBlk::new(&mut *(VIRTIO0 as *mut VirtIOHeader)).unwrap(),
+            VirtIOBlk::new(&mut *(base as *mut VirtIOHeader)).unwrap(),
         ))
     })
 });
@@ -41,39 +46,32 @@ struct VirtioHal;
 impl Hal for VirtioHal {
     fn dma_alloc(pages: usize) -> usize {
         // warn!("dma_alloc");
-        unsafe {
-            alloc_zeroed(Layout::from_size_align_unchecked(
-                pages << Sv39::PAGE_BITS,
-                1 << Sv39::PAGE_BITS,
-            )) as _
-        }
+        linker::virt_to_phys(kernel_frame::alloc(pages, Owner::Dma).unwrap().as_ptr() as _)
     }
 
     fn dma_dealloc(paddr: usize, pages: usize) -> i32 {
         // warn!("dma_dealloc");
-        unsafe {
-            dealloc(
-                paddr as _,
-                Layout::from_size_align_unchecked(pages << Sv39::PAGE_BITS, 1 << Sv39::PAGE_BITS),
-            )
-        }
+        kernel_frame::release(
+            NonNull::new(linker::phys_to_virt(paddr) as _).unwrap(),
+            pages,
+        );
         0
     }
 
     fn phys_to_virt(paddr: usize) -> usize {
         // warn!("p2v");
-        paddr
+        linker::phys_to_virt(paddr)
     }
 
     fn virt_to_phys(vaddr: usize) -> usize {
         // warn!("v2p");
-        const VALID: VmFlags<Sv39> = VmFlags::build_from_str("__V");
+        const VALID: VmFlags<Sv> = VmFlags::build_from_str("__V");
         let ptr: NonNull<u8> = unsafe {
             KERNEL_SPACE
                 .assume_init_ref()
                 .translate(VAddr::new(vaddr), VALID)
                 .unwrap()
         };
-        ptr.as_ptr() as usize
+        linker::virt_to_phys(ptr.as_ptr() as usize)
     }
 }
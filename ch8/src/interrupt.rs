@@ -0,0 +1,65 @@
+//! 外部中断。
+//!
+//! 串口收到输入时经 PLIC 发出 S 态外部中断，内核把输入交给终端并唤醒等待输入的线程。
+//! 用户态的外部中断经异界传送门回到内核的调度循环。
+
+use crate::{impls::UART, tty};
+use kernel_driver::Plic;
+use rcore_console::log;
+use riscv::register::sie;
+use spin::Once;
+
+/// 只有一个硬件线程，外部中断都送到 0 号硬件线程的 S 态。
+const CONTEXT: usize = Plic::supervisor_context(0);
+
+struct External {
+    plic: Plic,
+    uart_irq: usize,
+}
+
+static EXTERNAL: Once<External> = Once::new();
+
+/// 打开串口接收中断。平台没有 PLIC 或串口中断时不打开，终端仍可查询输入。
+pub fn init() {
+    let platform = platform::get();
+    let (Some(plic), Some(uart)) = (&platform.plic, UART.get()) else {
+        return;
+    };
+    let Some(uart_irq) = platform.uart.as_ref().and_then(|uart| uart.irq) else {
+        return;
+    };
+    let plic = unsafe { Plic::new(linker::phys_to_virt(plic.range.start)) };
+    plic.set_priority(uart_irq, 1);
+    plic.set_threshold(CONTEXT, 0);
+    plic.enable(CONTEXT, uart_irq);
+    EXTERNAL.call_once(|| External { plic, uart_irq });
+    uart.set_rx_interrupt(true);
+    unsafe { sie::set_sext() };
+    log::info!("uart irq {uart_irq} enabled");
+}
+
+/// 处理所有待处理的外部中断。
+pub fn handle() {
+    let Some(external) = EXTERNAL.get() else {
+        return;
+    };
+    while let Some(irq) = external.plic.claim(CONTEXT) {
+        if irq == external.uart_irq {
+            tty::poll();
+        } else {
+            log::warn!("unexpected irq {irq}");
+        }
+        external.plic.complete(CONTEXT, irq);
+    }
+}
+
+/// 所有线程都在等待终端输入时等待下一个外部中断，没有打开中断时直接查询输入。
+pub fn wait() {
+    if EXTERNAL.get().is_some() {
+        // `sstatus.SIE` 关闭时，`sie` 中打开的中断仍能唤醒 `wfi`
+        unsafe { riscv::asm::wfi() };
+        handle();
+    } else {
+        tty::poll();
+    }
+}
@@ -3,8 +3,12 @@
 // #![deny(warnings)]
 
 mod fs;
+mod interrupt;
 mod process;
 mod processor;
+mod shm;
+mod timer;
+mod tty;
 mod virtio_block;
 
 #[macro_use]
@@ -15,22 +19,23 @@ extern crate alloc;
 
 use crate::{
     fs::{read_all, FS},
-    impls::{Sv39Manager, SyscallContext},
+    impls::{SvManager, SyscallContext},
     process::{Process, Thread},
     processor::{ProcManager, ThreadManager},
 };
 use alloc::alloc::alloc;
 use core::{alloc::Layout, mem::MaybeUninit};
 use easy_fs::{FSManager, OpenFlags};
-use impls::Console;
+use impls::{Console, UART};
 use kernel_context::foreign::MultislotPortal;
+use kernel_driver::Ns16550;
 use kernel_vm::{
-    page_table::{MmuMeta, Sv39, VAddr, VmFlags, VmMeta, PPN, VPN},
-    AddressSpace,
+    page_table::{MmuMeta, VAddr, VmFlags, VmMeta, PPN, VPN},
+    AddressSpace, VmLayout,
 };
 pub use processor::PROCESSOR;
 use rcore_console::log;
-use rcore_task_manage::ProcId;
+use rcore_task_manage::{ProcId, ThreadId};
 use riscv::register::*;
 use sbi_rt::*;
 use signal::SignalResult;
@@ -38,15 +43,24 @@ use syscall::Caller;
 use xmas_elf::ElfFile;
 
 // 定义内核入口。
-linker::boot0!(rust_main; stack = 32 * 4096);
-// 物理内存容量 = 48 MiB。
-const MEMORY: usize = 48 << 20;
+linker::boot0!(rust_main; stack = STACK_SIZE);
+// 内核栈容量，启动栈和换栈后堆上的栈一样大。
+const STACK_SIZE: usize = 32 * 4096;
+// 内核堆容量最多 16 MiB，其余物理页交给页帧分配器。
+const HEAP: usize = 16 << 20;
+// 页表格式，启用 `sv48` 特性时使用 Sv48。
+#[cfg(not(feature = "sv48"))]
+type Sv = kernel_vm::page_table::Sv39;
+#[cfg(feature = "sv48")]
+type Sv = kernel_vm::page_table::Sv48;
 // 传送门所在虚页。
-const PROTAL_TRANSIT: VPN<Sv39> = VPN::MAX;
+const PROTAL_TRANSIT: VPN<Sv> = VPN::MAX;
 // 内核地址空间。
-static mut KERNEL_SPACE: MaybeUninit<AddressSpace<Sv39, Sv39Manager>> = MaybeUninit::uninit();
+static mut KERNEL_SPACE: MaybeUninit<AddressSpace<Sv, SvManager>> = MaybeUninit::uninit();
 
-extern "C" fn rust_main() -> ! {
+extern "C" fn rust_main(hartid: usize, dtb: usize) -> ! {
+    // 硬件线程号保存在 `tp` 里，内核不使用线程局部存储
+    unsafe { core::arch::asm!("mv tp, {}", in(reg) hartid) };
     let layout = linker::KernelLayout::locate();
     // bss 段清零
     unsafe { layout.zero_bss() };
@@ -54,50 +68,123 @@ extern "C" fn rust_main() -> ! {
     rcore_console::init_console(&Console);
     rcore_console::set_log_level(option_env!("LOG"));
     rcore_console::test_log();
+    // 解析设备树
+    let platform = unsafe { platform::init(dtb) };
+    log::info!("{platform}");
     // 初始化内核堆
+    let (_, heap) = memory(&layout);
     kernel_alloc::init(layout.start() as _);
+    unsafe { kernel_alloc::transfer(core::slice::from_raw_parts_mut(layout.end() as _, heap)) };
+    // 换到堆上的栈，以便回收启动栈
+    const STACK: Layout = unsafe { Layout::from_size_align_unchecked(STACK_SIZE, 4096) };
+    let stack = unsafe { alloc(STACK) };
+    assert!(!stack.is_null(), "failed to allocate the kernel stack");
+    unsafe { linker::switch_stack(stack as usize + STACK_SIZE, kernel_main) }
+}
+
+extern "C" fn kernel_main() -> ! {
+    let layout = linker::KernelLayout::locate();
+    // 回收启动栈
+    let boot = layout.boot();
+    let reclaimed = unsafe {
+        kernel_alloc::add_region(core::slice::from_raw_parts_mut(boot.start as _, boot.len()))
+    };
+    log::info!("reclaimed {reclaimed} bytes from .boot");
+    // 初始化页帧分配器
+    let (memory, heap) = memory(&layout);
     unsafe {
-        kernel_alloc::transfer(core::slice::from_raw_parts_mut(
-            layout.end() as _,
-            MEMORY - layout.len(),
-        ))
+        kernel_frame::init(
+            Sv::PAGE_BITS,
+            core::slice::from_raw_parts_mut(
+                (layout.end() + heap) as _,
+                memory - layout.len() - heap,
+            ),
+        )
     };
     // 建立异界传送门
     let portal_size = MultislotPortal::calculate_size(1);
-    let portal_layout = Layout::from_size_align(portal_size, 1 << Sv39::PAGE_BITS).unwrap();
+    let portal_layout = Layout::from_size_align(portal_size, 1 << Sv::PAGE_BITS).unwrap();
     let portal_ptr = unsafe { alloc(portal_layout) };
-    assert!(portal_layout.size() < 1 << Sv39::PAGE_BITS);
+    assert!(portal_layout.size() < 1 << Sv::PAGE_BITS);
     // 建立内核地址空间
-    kernel_space(layout, MEMORY, portal_ptr as _);
+    kernel_space(layout, memory, portal_ptr as _);
+    // 映射设备寄存器之后改用串口
+    if let Some(uart) = &platform::get().uart {
+        UART.call_once(|| {
+            let uart = unsafe { Ns16550::new(linker::phys_to_virt(uart.range.start)) };
+            uart.init();
+            uart
+        });
+    }
+    interrupt::init();
     // 初始化异界传送门
     let portal = unsafe { MultislotPortal::init_transit(PROTAL_TRANSIT.base().val(), 1) };
     // 初始化 syscall
     syscall::init_io(&SyscallContext);
     syscall::init_process(&SyscallContext);
+    syscall::init_memory(&SyscallContext);
     syscall::init_scheduling(&SyscallContext);
     syscall::init_clock(&SyscallContext);
     syscall::init_signal(&SyscallContext);
     syscall::init_thread(&SyscallContext);
     syscall::init_sync_mutex(&SyscallContext);
     let initproc = read_all(FS.open("initproc", OpenFlags::RDONLY).unwrap());
-    if let Some((process, thread)) = Process::from_elf(ElfFile::new(initproc.as_slice()).unwrap()) {
+    if let Some((process, thread)) = Process::from_elf(
+        ProcId::new().unwrap(),
+        ThreadId::new().unwrap(),
+        ElfFile::new(initproc.as_slice()).unwrap(),
+    ) {
         unsafe {
             PROCESSOR.set_proc_manager(ProcManager::new());
             PROCESSOR.set_manager(ThreadManager::new());
             let (pid, tid) = (process.pid, thread.tid);
-            PROCESSOR.add_proc(pid, process, ProcId::from_usize(usize::MAX));
+            // initproc 没有父进程，负责收养结束的进程留下的子进程
+            PROCESSOR.add_proc(pid, process, ProcId::from_usize(0));
+            PROCESSOR.set_init(pid);
             PROCESSOR.add(tid, thread, pid);
         }
     }
+    // 打开时钟中断，用完时间片的用户程序被抢占
+    timer::init();
     loop {
+        // 不读取终端的前台进程也能被 Ctrl-C、Ctrl-Z 中断
+        tty::poll();
         if let Some(task) = unsafe { PROCESSOR.find_next() } {
-            unsafe { task.context.execute(portal, ()) };
+            // 在用户态运行和处理陷入的时间记到线程上
+            let tid = task.tid;
+            let start = time::read();
+            // 调度器认为时间片还没用完时，时钟中断后继续运行当前任务
+            loop {
+                timer::set_next();
+                unsafe { task.context.execute(portal, ()) };
+                if !timer::expired() || unsafe { PROCESSOR.tick_current() } {
+                    break;
+                }
+            }
+            let trapped = time::read();
+            unsafe { PROCESSOR.charge(tid, trapped - start, 0) };
             match scause::read().cause() {
+                scause::Trap::Interrupt(scause::Interrupt::SupervisorTimer) => {
+                    timer::stop();
+                    // 被抢占时也处理信号，不做系统调用的进程也能被杀死
+                    let ctx = &mut task.context.context;
+                    let current_proc = unsafe { PROCESSOR.get_current_proc().unwrap() };
+                    match current_proc.signal.handle_signals(ctx) {
+                        SignalResult::ProcessKilled(exit_code) => unsafe {
+                            PROCESSOR.make_current_exited(exit_code as _)
+                        },
+                        SignalResult::ProcessSuspended => unsafe {
+                            PROCESSOR.make_current_stopped()
+                        },
+                        _ => unsafe { PROCESSOR.make_current_suspend() },
+                    }
+                }
                 scause::Trap::Exception(scause::Exception::UserEnvCall) => {
                     use syscall::{SyscallId as Id, SyscallResult as Ret};
                     let ctx = &mut task.context.context;
                     ctx.move_next();
                     let id: Id = ctx.a(7).into();
+                    unsafe { PROCESSOR.count_syscall(id.0) };
                     let args = [ctx.a(0), ctx.a(1), ctx.a(2), ctx.a(3), ctx.a(4), ctx.a(5)];
                     let syscall_ret = syscall::handle(Caller { entity: 0, flow: 0 }, id, args);
                     // 目前信号处理位置放在 syscall 执行之后，这只是临时的实现。
@@ -107,12 +194,42 @@ extern "C" fn rust_main() -> ! {
                     //
                     // 最简单粗暴的方法是，在 `scause::Trap` 分类的每一条分支之后都加上信号处理，
                     // 当然这样可能代码上不够优雅。处理信号的具体时机还需要后续再讨论。
+                    let read_would_block = id == Id::READ
+                        && args[0] == syscall::STDIN
+                        && matches!(syscall_ret, Ret::Done(ret) if ret == -syscall::EAGAIN);
+                    let wait_would_block = id == Id::WAIT4
+                        && matches!(syscall_ret, Ret::Done(ret) if ret == -syscall::EAGAIN);
+                    // 可能阻塞在同步原语上的线程等下一次陷入内核时再暂停
+                    let sync_call =
+                        matches!(id, Id::SEMAPHORE_DOWN | Id::MUTEX_LOCK | Id::CONDVAR_WAIT);
                     let current_proc = unsafe { PROCESSOR.get_current_proc().unwrap() };
                     match current_proc.signal.handle_signals(ctx) {
                         // 进程应该结束执行
                         SignalResult::ProcessKilled(exit_code) => unsafe {
                             PROCESSOR.make_current_exited(exit_code as _)
                         },
+                        // 终端还没有输入，回到 `ecall` 阻塞，有输入后重新执行 `read`
+                        SignalResult::NoSignal if read_would_block => {
+                            *ctx.pc_mut() -= 4;
+                            tty::wait(task.tid);
+                            unsafe { PROCESSOR.make_current_blocked() };
+                        }
+                        // 子进程都还在运行，阻塞到有子进程结束或收到信号时重新执行 `wait4`
+                        SignalResult::NoSignal if wait_would_block => {
+                            *ctx.pc_mut() -= 4;
+                            unsafe { PROCESSOR.make_current_waiting() };
+                        }
+                        // 收到暂停信号，带着系统调用的返回值暂停，继续运行时从系统调用返回
+                        SignalResult::ProcessSuspended
+                            if !sync_call
+                                && id != Id::EXIT
+                                && matches!(syscall_ret, Ret::Done(_)) =>
+                        {
+                            if let Ret::Done(ret) = syscall_ret {
+                                *ctx.a_mut(0) = ret as _;
+                            }
+                            unsafe { PROCESSOR.make_current_stopped() };
+                        }
                         _ => match syscall_ret {
                             Ret::Done(ret) => match id {
                                 Id::EXIT => unsafe { PROCESSOR.make_current_exited(ret) },
@@ -131,37 +248,95 @@ extern "C" fn rust_main() -> ! {
                             },
                             Ret::Unsupported(_) => {
                                 log::info!("id = {id:?}");
-                                unsafe { PROCESSOR.make_current_exited(-2) };
+                                // 按被 SIGSYS 杀死记录
+                                let exit_code = -(syscall::SignalNo::SIGSYS as isize);
+                                unsafe { PROCESSOR.make_current_exited(exit_code) };
                             }
                         },
                     }
                 }
+                scause::Trap::Interrupt(scause::Interrupt::SupervisorExternal) => {
+                    interrupt::handle();
+                    unsafe { PROCESSOR.make_current_suspend() };
+                }
+                scause::Trap::Exception(
+                    scause::Exception::LoadPageFault
+                    | scause::Exception::StorePageFault
+                    | scause::Exception::InstructionPageFault,
+                ) if unsafe { PROCESSOR.get_current_proc() }
+                    .unwrap()
+                    .page_fault(stval::read()) =>
+                unsafe { PROCESSOR.make_current_suspend() },
                 e => {
-                    log::error!("unsupported trap: {e:?}");
-                    unsafe { PROCESSOR.make_current_exited(-3) };
+                    let proc = unsafe { PROCESSOR.get_current_proc().unwrap() };
+                    report_user_fault(
+                        format_args!("process {}", proc.pid.get_usize()),
+                        e,
+                        &task.context.context,
+                        &proc.address_space,
+                    );
+                    // 按被 SIGSEGV 杀死记录
+                    let exit_code = -(syscall::SignalNo::SIGSEGV as isize);
+                    unsafe { PROCESSOR.make_current_exited(exit_code) };
                 }
             }
+            // 线程在处理陷入时结束的话，这段时间不再统计
+            unsafe { PROCESSOR.charge(tid, 0, time::read() - trapped) };
+        } else if tty::has_readers() {
+            // 所有线程都在等待终端输入，没有要抢占的任务
+            timer::stop();
+            interrupt::wait();
         } else {
             println!("no task");
             break;
         }
     }
 
+    kernel_alloc::report();
+    log::info!("{}", kernel_frame::stats());
     system_reset(Shutdown, NoReason);
     unreachable!()
 }
 
+/// 打印用户程序的异常现场和调用栈。
+fn report_user_fault(
+    who: impl core::fmt::Display,
+    trap: scause::Trap,
+    ctx: &kernel_context::LocalContext,
+    space: &AddressSpace<Sv, SvManager>,
+) {
+    const READABLE: VmFlags<Sv> = VmFlags::build_from_str("RV");
+    let backtrace = kernel_backtrace::Backtrace::user(ctx.pc(), ctx.x(8), |addr| {
+        space
+            .translate::<usize>(VAddr::new(addr), READABLE)
+            .map(|ptr| unsafe { *ptr.as_ptr() })
+    });
+    log::error!(
+        "{who} was killed by {trap:?}, sepc = {:#x}, stval = {:#x}\n{backtrace}",
+        ctx.pc(),
+        stval::read()
+    );
+}
+
 /// Rust 异常处理函数，以异常方式关机。
 #[panic_handler]
 fn panic(info: &core::panic::PanicInfo) -> ! {
     println!("{info}");
+    println!("{}", kernel_backtrace::Backtrace::capture());
     system_reset(Shutdown, SystemFailure);
     loop {}
 }
 
-pub const MMIO: &[(usize, usize)] = &[
-    (0x1000_1000, 0x00_1000), // Virtio Block in virt machine
-];
+/// 内核之后的物理内存都归内核管理，返回从内核起始处算起的内存总量和内核堆的容量。
+///
+/// 内存不多时内核堆只占内核之后的一半，另一半留给页帧分配器。
+fn memory(layout: &linker::KernelLayout) -> (usize, usize) {
+    let memory = linker::phys_to_virt(platform::get().memory.end) - layout.start();
+    let free = memory
+        .checked_sub(layout.len())
+        .expect("physical memory is smaller than the kernel image");
+    (memory, HEAP.min((free / 2) & !((1 << Sv::PAGE_BITS) - 1)))
+}
 
 fn kernel_space(layout: linker::KernelLayout, memory: usize, portal: usize) {
     let mut space = AddressSpace::new();
@@ -173,68 +348,75 @@ fn kernel_space(layout: linker::KernelLayout, memory: usize, portal: usize) {
             Rodata => "__RV",
             Data | Boot => "_WRV",
         };
-        let s = VAddr::<Sv39>::new(region.range.start);
-        let e = VAddr::<Sv39>::new(region.range.end);
+        let s = VAddr::<Sv>::new(region.range.start);
+        let e = VAddr::<Sv>::new(region.range.end);
         space.map_extern(
             s.floor()..e.ceil(),
-            PPN::new(s.floor().val()),
+            PPN::new(linker::virt_to_phys(region.range.start) >> Sv::PAGE_BITS),
             VmFlags::build_from_str(flags),
         )
     }
-    let s = VAddr::<Sv39>::new(layout.end());
-    let e = VAddr::<Sv39>::new(layout.start() + memory);
+    let s = VAddr::<Sv>::new(layout.end());
+    let e = VAddr::<Sv>::new(layout.start() + memory);
     log::info!("(heap) ---> {:#10x}..{:#10x}", s.val(), e.val());
     space.map_extern(
         s.floor()..e.ceil(),
-        PPN::new(s.floor().val()),
+        PPN::new(linker::virt_to_phys(layout.end()) >> Sv::PAGE_BITS),
         VmFlags::build_from_str("_WRV"),
     );
     space.map_extern(
         PROTAL_TRANSIT..PROTAL_TRANSIT + 1,
-        PPN::new(portal >> Sv39::PAGE_BITS),
+        PPN::new(linker::virt_to_phys(portal) >> Sv::PAGE_BITS),
         VmFlags::build_from_str("__G_XWRV"),
     );
     println!();
 
     // MMIO
-    for (base, len) in MMIO {
-        let s = VAddr::<Sv39>::new(*base);
-        let e = VAddr::<Sv39>::new(*base + *len);
+    for range in platform::get().mmio() {
+        let s = VAddr::<Sv>::new(linker::phys_to_virt(range.start));
+        let e = VAddr::<Sv>::new(linker::phys_to_virt(range.end));
         log::info!("MMIO range -> {:#10x}..{:#10x}", s.val(), e.val());
         space.map_extern(
             s.floor()..e.ceil(),
-            PPN::new(s.floor().val()),
+            PPN::new(range.start >> Sv::PAGE_BITS),
             VmFlags::build_from_str("_WRV"),
         );
     }
 
-    unsafe { satp::set(satp::Mode::Sv39, 0, space.root_ppn().val()) };
+    unsafe { satp::write(VmLayout::<Sv>::satp(space.root_ppn())) };
     unsafe { KERNEL_SPACE = MaybeUninit::new(space) };
 }
 
 /// 映射异界传送门。
-fn map_portal(space: &AddressSpace<Sv39, Sv39Manager>) {
-    let portal_idx = PROTAL_TRANSIT.index_in(Sv39::MAX_LEVEL);
-    space.root()[portal_idx] = unsafe { KERNEL_SPACE.assume_init_ref() }.root()[portal_idx];
+///
+/// 传送门的页表属于内核地址空间，复制的表项不带所有权标记，用户地址空间回收时不会释放它。
+fn map_portal(space: &AddressSpace<Sv, SvManager>) {
+    let portal_idx = PROTAL_TRANSIT.index_in(Sv::MAX_LEVEL);
+    let kernel = unsafe { KERNEL_SPACE.assume_init_ref() }.root()[portal_idx];
+    space.root()[portal_idx] = VmFlags::VALID.build_pte(kernel.ppn());
 }
 
 /// 各种接口库的实现。
 mod impls {
     use crate::{
         fs::{read_all, FS},
-        Thread, PROCESSOR,
+        processor::{send_signal, signal_group},
+        shm::SHM,
+        Sv, Thread, PROCESSOR,
     };
     use alloc::sync::Arc;
-    use alloc::{alloc::alloc_zeroed, string::String, vec::Vec};
-    use core::{alloc::Layout, ptr::NonNull};
+    use alloc::{string::String, vec::Vec};
+    use core::ptr::NonNull;
     use easy_fs::UserBuffer;
     use easy_fs::{FSManager, OpenFlags};
+    use kernel_driver::Ns16550;
+    use kernel_frame::Owner;
     use kernel_vm::{
-        page_table::{MmuMeta, Pte, Sv39, VAddr, VmFlags, VmMeta, PPN, VPN},
-        PageManager,
+        page_table::{MmuMeta, Pte, VAddr, VmFlags, VmMeta, PPN, VPN},
+        AddressSpace, PageManager, VmLayout,
     };
     use rcore_console::log;
-    use rcore_task_manage::{ProcId, ThreadId};
+    use rcore_task_manage::{ProcId, TaskStats, ThreadId, MAX_ID};
     use signal::SignalNo;
     use spin::Mutex;
     use sync::{Condvar, Mutex as MutexTrait, MutexBlocking, Semaphore};
@@ -242,82 +424,146 @@ mod impls {
     use xmas_elf::ElfFile;
 
     #[repr(transparent)]
-    pub struct Sv39Manager(NonNull<Pte<Sv39>>);
+    pub struct SvManager(NonNull<Pte<Sv>>);
 
-    impl Sv39Manager {
-        const OWNED: VmFlags<Sv39> = unsafe { VmFlags::from_raw(1 << 8) };
+    impl SvManager {
+        const OWNED: VmFlags<Sv> = unsafe { VmFlags::from_raw(1 << 8) };
+        const USER: VmFlags<Sv> = VmFlags::build_from_str("U____");
 
+        /// 给 `flags` 加上所有权标记，这样映射的页帧在地址空间回收时释放。
         #[inline]
-        fn page_alloc<T>(count: usize) -> *mut T {
-            unsafe {
-                alloc_zeroed(Layout::from_size_align_unchecked(
-                    count << Sv39::PAGE_BITS,
-                    1 << Sv39::PAGE_BITS,
-                ))
-            }
-            .cast()
+        pub fn owned(mut flags: VmFlags<Sv>) -> VmFlags<Sv> {
+            flags |= Self::OWNED;
+            flags
+        }
+
+        #[inline]
+        fn page_alloc<T>(count: usize, owner: Owner) -> *mut T {
+            kernel_frame::alloc(count, owner).unwrap().as_ptr().cast()
         }
     }
 
-    impl PageManager<Sv39> for Sv39Manager {
+    impl PageManager<Sv> for SvManager {
         #[inline]
         fn new_root() -> Self {
-            Self(NonNull::new(Self::page_alloc(1)).unwrap())
+            Self(NonNull::new(Self::page_alloc(1, Owner::PageTable)).unwrap())
         }
 
         #[inline]
-        fn root_ppn(&self) -> PPN<Sv39> {
-            PPN::new(self.0.as_ptr() as usize >> Sv39::PAGE_BITS)
+        fn root_ppn(&self) -> PPN<Sv> {
+            PPN::new(linker::virt_to_phys(self.0.as_ptr() as usize) >> Sv::PAGE_BITS)
         }
 
         #[inline]
-        fn root_ptr(&self) -> NonNull<Pte<Sv39>> {
+        fn root_ptr(&self) -> NonNull<Pte<Sv>> {
             self.0
         }
 
         #[inline]
-        fn p_to_v<T>(&self, ppn: PPN<Sv39>) -> NonNull<T> {
-            unsafe { NonNull::new_unchecked(VPN::<Sv39>::new(ppn.val()).base().as_mut_ptr()) }
+        fn p_to_v<T>(&self, ppn: PPN<Sv>) -> NonNull<T> {
+            let va = linker::phys_to_virt(ppn.val() << Sv::PAGE_BITS);
+            unsafe { NonNull::new_unchecked(va as *mut T) }
         }
 
         #[inline]
-        fn v_to_p<T>(&self, ptr: NonNull<T>) -> PPN<Sv39> {
-            PPN::new(VAddr::<Sv39>::new(ptr.as_ptr() as _).floor().val())
+        fn v_to_p<T>(&self, ptr: NonNull<T>) -> PPN<Sv> {
+            PPN::new(linker::virt_to_phys(ptr.as_ptr() as usize) >> Sv::PAGE_BITS)
         }
 
         #[inline]
-        fn check_owned(&self, pte: Pte<Sv39>) -> bool {
+        fn check_owned(&self, pte: Pte<Sv>) -> bool {
             pte.flags().contains(Self::OWNED)
         }
 
         #[inline]
-        fn allocate(&mut self, len: usize, flags: &mut VmFlags<Sv39>) -> NonNull<u8> {
+        fn allocate(&mut self, len: usize, flags: &mut VmFlags<Sv>) -> NonNull<u8> {
+            // 页表中间节点只有 V 位，带 U 位的是用户页
+            let owner = if flags.contains(Self::USER) {
+                Owner::User
+            } else {
+                Owner::PageTable
+            };
             *flags |= Self::OWNED;
-            NonNull::new(Self::page_alloc(len)).unwrap()
+            NonNull::new(Self::page_alloc(len, owner)).unwrap()
         }
 
-        fn deallocate(&mut self, _pte: Pte<Sv39>, _len: usize) -> usize {
-            todo!()
+        #[inline]
+        fn deallocate(&mut self, pte: Pte<Sv>, len: usize) -> usize {
+            kernel_frame::release(self.p_to_v(pte.ppn()), len)
         }
 
+        #[inline]
         fn drop_root(&mut self) {
-            todo!()
+            kernel_frame::release(self.0.cast(), 1);
         }
     }
 
     pub struct Console;
 
+    /// 串口，映射设备寄存器之后才可用，此前通过 SBI 输入输出。
+    pub static UART: spin::Once<Ns16550> = spin::Once::new();
+
     impl rcore_console::Console for Console {
         #[inline]
         fn put_char(&self, c: u8) {
+            if let Some(uart) = UART.get() {
+                rcore_console::Console::put_char(uart, c);
+            } else {
+                #[allow(deprecated)]
+                sbi_rt::legacy::console_putchar(c as _);
+            }
+        }
+
+        #[inline]
+        fn put_str(&self, s: &str) {
+            if let Some(uart) = UART.get() {
+                rcore_console::Console::put_str(uart, s);
+            } else {
+                for c in s.bytes() {
+                    #[allow(deprecated)]
+                    sbi_rt::legacy::console_putchar(c as _);
+                }
+            }
+        }
+
+        /// SBI 没有输入时返回 `usize::MAX`。
+        #[inline]
+        fn get_char(&self) -> Option<u8> {
+            if let Some(uart) = UART.get() {
+                return uart.read_byte();
+            }
             #[allow(deprecated)]
-            sbi_rt::legacy::console_putchar(c as _);
+            let c = sbi_rt::legacy::console_getchar();
+            u8::try_from(c).ok()
+        }
+
+        /// 以 `time` 寄存器计时，解析设备树之前不打印时间戳。
+        #[inline]
+        fn timestamp(&self) -> Option<core::time::Duration> {
+            let timebase = platform::try_get()?.timebase_frequency as u64;
+            let time = riscv::register::time::read64();
+            let nanos = (time % timebase) * 1_000_000_000 / timebase;
+            Some(core::time::Duration::new(time / timebase, nanos as _))
+        }
+
+        /// 硬件线程号在启动时保存在 `tp` 里。
+        #[inline]
+        fn hart_id(&self) -> Option<usize> {
+            let hartid: usize;
+            unsafe { core::arch::asm!("mv {}, tp", out(reg) hartid) };
+            Some(hartid)
+        }
+
+        #[inline]
+        fn task_id(&self) -> Option<(usize, usize)> {
+            let (pid, tid) = unsafe { PROCESSOR.current_id() }?;
+            Some((pid.get_usize(), tid.get_usize()))
         }
     }
 
     pub struct SyscallContext;
-    const READABLE: VmFlags<Sv39> = VmFlags::build_from_str("RV");
-    const WRITEABLE: VmFlags<Sv39> = VmFlags::build_from_str("W_V");
+    const READABLE: VmFlags<Sv> = VmFlags::build_from_str("RV");
+    const WRITEABLE: VmFlags<Sv> = VmFlags::build_from_str("W_V");
 
     impl IO for SyscallContext {
         fn write(&self, _caller: Caller, fd: usize, buf: usize, count: usize) -> isize {
@@ -355,15 +601,9 @@ mod impls {
             let current = unsafe { PROCESSOR.get_current_proc().unwrap() };
             if let Some(ptr) = current.address_space.translate(VAddr::new(buf), WRITEABLE) {
                 if fd == STDIN {
-                    let mut ptr = ptr.as_ptr();
-                    for _ in 0..count {
-                        #[allow(deprecated)]
-                        unsafe {
-                            *ptr = sbi_rt::legacy::console_getchar() as u8;
-                            ptr = ptr.add(1);
-                        }
-                    }
-                    count as _
+                    crate::tty::read(unsafe {
+                        core::slice::from_raw_parts_mut(ptr.as_ptr(), count)
+                    })
                 } else if let Some(file) = &current.fd_table[fd] {
                     let mut file = file.lock();
                     if file.readable() {
@@ -425,18 +665,139 @@ mod impls {
             current.fd_table[fd].take();
             0
         }
+
+        fn ioctl(&self, _caller: Caller, fd: usize, request: usize, arg: usize) -> isize {
+            if fd != STDIN {
+                return -ENOTTY;
+            }
+            let current = unsafe { PROCESSOR.get_current_proc().unwrap() };
+            match request {
+                TCGETS => {
+                    if let Some(mut ptr) = current
+                        .address_space
+                        .translate::<Termios>(VAddr::new(arg), WRITEABLE)
+                    {
+                        unsafe { *ptr.as_mut() = crate::tty::termios() };
+                        0
+                    } else {
+                        log::error!("ptr not writeable");
+                        -1
+                    }
+                }
+                TCSETS => {
+                    if let Some(ptr) = current
+                        .address_space
+                        .translate::<Termios>(VAddr::new(arg), READABLE)
+                    {
+                        crate::tty::set_termios(unsafe { ptr.as_ref() });
+                        0
+                    } else {
+                        log::error!("ptr not readable");
+                        -1
+                    }
+                }
+                TIOCGPGRP => {
+                    if let Some(mut ptr) = current
+                        .address_space
+                        .translate::<i32>(VAddr::new(arg), WRITEABLE)
+                    {
+                        let pgid = crate::tty::foreground().map_or(0, |pgid| pgid.get_usize());
+                        unsafe { *ptr.as_mut() = pgid as _ };
+                        0
+                    } else {
+                        log::error!("ptr not writeable");
+                        -1
+                    }
+                }
+                TIOCSPGRP => {
+                    if let Some(ptr) = current
+                        .address_space
+                        .translate::<i32>(VAddr::new(arg), READABLE)
+                    {
+                        let pgid = ProcId::from_usize(unsafe { *ptr.as_ref() } as usize);
+                        // 只能把同一会话中的进程组设为前台进程组
+                        let sid = unsafe { PROCESSOR.sid(current.pid) };
+                        if unsafe { PROCESSOR.group(pgid) }
+                            .into_iter()
+                            .any(|pid| unsafe { PROCESSOR.sid(pid) } == sid)
+                        {
+                            crate::tty::set_foreground(pgid);
+                            0
+                        } else {
+                            -EPERM
+                        }
+                    } else {
+                        log::error!("ptr not readable");
+                        -1
+                    }
+                }
+                _ => -EINVAL,
+            }
+        }
+
+        fn syslog(&self, _caller: Caller, ty: usize, buf: usize, len: usize) -> isize {
+            match ty {
+                SYSLOG_ACTION_READ_ALL => {
+                    const PAGE_SIZE: usize = 1 << Sv::PAGE_BITS;
+                    let current = unsafe { PROCESSOR.get_current_proc().unwrap() };
+                    let mut records = alloc::vec![0u8; len.min(rcore_console::LOG_BUFFER_SIZE)];
+                    let len = rcore_console::read_log(&mut records);
+                    // 用户缓冲区的各页不一定物理连续，逐页复制
+                    let mut copied = 0;
+                    while copied < len {
+                        let addr = buf + copied;
+                        let n = (len - copied).min(PAGE_SIZE - addr % PAGE_SIZE);
+                        let Some(ptr) = current
+                            .address_space
+                            .translate::<u8>(VAddr::new(addr), WRITEABLE)
+                        else {
+                            log::error!("ptr not writeable");
+                            return -1;
+                        };
+                        unsafe {
+                            core::ptr::copy_nonoverlapping(
+                                records[copied..].as_ptr(),
+                                ptr.as_ptr(),
+                                n,
+                            )
+                        };
+                        copied += n;
+                    }
+                    len as _
+                }
+                SYSLOG_ACTION_CLEAR => {
+                    rcore_console::clear_log();
+                    0
+                }
+                SYSLOG_ACTION_SIZE_UNREAD => rcore_console::log_len() as _,
+                SYSLOG_ACTION_SIZE_BUFFER => rcore_console::LOG_BUFFER_SIZE as _,
+                _ => -EINVAL,
+            }
+        }
     }
 
     impl Process for SyscallContext {
+        /// 与 Linux 一样只保留退出码的低 8 位，负数留给被信号杀死的进程。
         #[inline]
         fn exit(&self, _caller: Caller, exit_code: usize) -> isize {
-            exit_code as isize
+            (exit_code & 0xff) as isize
         }
 
         fn fork(&self, _caller: Caller) -> isize {
             let current_proc = unsafe { PROCESSOR.get_current_proc().unwrap() };
-            let (proc, mut thread) = current_proc.fork().unwrap();
-            let pid = proc.pid;
+            // 进程或线程 Id 用完时失败，等有进程被回收后可以重试
+            let Some(pid) = ProcId::new() else {
+                return -EAGAIN;
+            };
+            let Some(tid) = ThreadId::new() else {
+                pid.release();
+                return -EAGAIN;
+            };
+            let Some((proc, mut thread)) = current_proc.fork(pid, tid) else {
+                pid.release();
+                tid.release();
+                return -ENOMEM;
+            };
             *thread.context.context.a_mut(0) = 0 as _;
             unsafe {
                 PROCESSOR.add_proc(pid, proc, current_proc.pid);
@@ -446,7 +807,7 @@ mod impls {
         }
 
         fn exec(&self, _caller: Caller, path: usize, count: usize) -> isize {
-            const READABLE: VmFlags<Sv39> = VmFlags::build_from_str("RV");
+            const READABLE: VmFlags<Sv> = VmFlags::build_from_str("RV");
             let current = unsafe { PROCESSOR.get_current_proc().unwrap() };
             current
                 .address_space
@@ -472,28 +833,315 @@ mod impls {
                 )
         }
 
-        fn wait(&self, _caller: Caller, pid: isize, exit_code_ptr: usize) -> isize {
+        fn wait(&self, _caller: Caller, pid: isize, exit_code_ptr: usize, options: usize) -> isize {
             let current = unsafe { PROCESSOR.get_current_proc().unwrap() };
-            const WRITABLE: VmFlags<Sv39> = VmFlags::build_from_str("W_V");
-            if let Some((dead_pid, exit_code)) =
-                unsafe { PROCESSOR.wait(ProcId::from_usize(pid as usize)) }
+            const WRITABLE: VmFlags<Sv> = VmFlags::build_from_str("W_V");
+            let child = ProcId::from_usize(pid as usize);
+            // 指定 WUNTRACED 时先报告暂停了的子进程
+            let stopped = if options & WUNTRACED != 0 {
+                unsafe { PROCESSOR.wait_stopped(child) }
+            } else {
+                None
+            };
+            let (pid, status) = match stopped {
+                Some(stopped_pid) => (stopped_pid, stop_status(SignalNo::SIGSTOP as _)),
+                None => match unsafe { PROCESSOR.wait(child) } {
+                    // 等待的子进程都还在运行，不阻塞时返回 0，否则由调度循环阻塞当前进程
+                    Some((dead_pid, _)) if dead_pid.get_usize() == -2isize as usize => {
+                        return if options & WNOHANG != 0 { 0 } else { -EAGAIN };
+                    }
+                    Some((dead_pid, exit_code)) => (dead_pid, wait_status(exit_code)),
+                    // 等待的子进程不存在
+                    None => return -ECHILD,
+                },
+            };
+            if let Some(mut ptr) = current
+                .address_space
+                .translate(VAddr::new(exit_code_ptr), WRITABLE)
             {
-                if let Some(mut ptr) = current
-                    .address_space
-                    .translate(VAddr::new(exit_code_ptr), WRITABLE)
-                {
-                    unsafe { *ptr.as_mut() = exit_code };
+                unsafe { *ptr.as_mut() = status };
+            }
+            pid.get_usize() as _
+        }
+
+        fn getpid(&self, _caller: Caller) -> isize {
+            let current = unsafe { PROCESSOR.get_current_proc().unwrap() };
+            current.pid.get_usize() as _
+        }
+
+        /// 没有父进程时返回 0。
+        fn getppid(&self, _caller: Caller) -> isize {
+            let current = unsafe { PROCESSOR.get_current_proc().unwrap() };
+            unsafe { PROCESSOR.parent(current.pid) }.map_or(0, |pid| pid.get_usize() as _)
+        }
+
+        /// 只能修改调用者自己和它的子进程的进程组。
+        fn setpgid(&self, _caller: Caller, pid: isize, pgid: isize) -> isize {
+            if pid < 0 || pgid < 0 {
+                return -EINVAL;
+            }
+            let current = unsafe { PROCESSOR.get_current_proc().unwrap() };
+            let pid = if pid == 0 {
+                current.pid
+            } else {
+                ProcId::from_usize(pid as _)
+            };
+            let pgid = if pgid == 0 {
+                pid
+            } else {
+                ProcId::from_usize(pgid as _)
+            };
+            if pid != current.pid && unsafe { PROCESSOR.parent(pid) } != Some(current.pid) {
+                return -ESRCH;
+            }
+            if unsafe { PROCESSOR.setpgid(pid, pgid) } {
+                0
+            } else {
+                -EPERM
+            }
+        }
+
+        fn getpgid(&self, _caller: Caller, pid: isize) -> isize {
+            let current = unsafe { PROCESSOR.get_current_proc().unwrap() };
+            let pid = if pid == 0 {
+                current.pid
+            } else {
+                ProcId::from_usize(pid as _)
+            };
+            unsafe { PROCESSOR.pgid(pid) }.map_or(-ESRCH, |pgid| pgid.get_usize() as _)
+        }
+
+        fn setsid(&self, _caller: Caller) -> isize {
+            let current = unsafe { PROCESSOR.get_current_proc().unwrap() };
+            if unsafe { PROCESSOR.setsid(current.pid) } {
+                current.pid.get_usize() as _
+            } else {
+                -EPERM
+            }
+        }
+
+        fn getrusage(&self, _caller: Caller, who: isize, usage: usize) -> isize {
+            let current = unsafe { PROCESSOR.get_current_proc().unwrap() };
+            let rusage = match who {
+                RUSAGE_SELF => rusage_of(&unsafe { PROCESSOR.proc_stats(current.pid) }.unwrap()),
+                RUSAGE_CHILDREN => {
+                    rusage_of(unsafe { PROCESSOR.children_stats(current.pid) }.unwrap())
+                }
+                RUSAGE_THREAD => {
+                    let tid = unsafe { PROCESSOR.current().unwrap() }.tid;
+                    rusage_of(unsafe { PROCESSOR.thread_stats(tid) }.unwrap())
+                }
+                _ => return -EINVAL,
+            };
+            if copy_to_user(&current.address_space, usage, &rusage) {
+                0
+            } else {
+                -EFAULT
+            }
+        }
+
+        fn times(&self, _caller: Caller, buf: usize) -> isize {
+            let current = unsafe { PROCESSOR.get_current_proc().unwrap() };
+            let stats = unsafe { PROCESSOR.proc_stats(current.pid) }.unwrap();
+            let children = unsafe { PROCESSOR.children_stats(current.pid) }.unwrap();
+            let tms = Tms {
+                tms_utime: ticks_to(stats.user_time, CLK_TCK) as _,
+                tms_stime: ticks_to(stats.system_time, CLK_TCK) as _,
+                tms_cutime: ticks_to(children.user_time, CLK_TCK) as _,
+                tms_cstime: ticks_to(children.system_time, CLK_TCK) as _,
+            };
+            if copy_to_user(&current.address_space, buf, &tms) {
+                ticks_to(riscv::register::time::read(), CLK_TCK) as _
+            } else {
+                -EFAULT
+            }
+        }
+
+        fn task_info(&self, _caller: Caller, pid: isize, info: usize) -> isize {
+            let current = unsafe { PROCESSOR.get_current_proc().unwrap() };
+            let pid = if pid == 0 {
+                current.pid
+            } else {
+                ProcId::from_usize(pid as _)
+            };
+            // 进程的统计是它所有线程的统计之和
+            let Some(stats) = (unsafe { PROCESSOR.proc_stats(pid) }) else {
+                return -ESRCH;
+            };
+            if copy_to_user(&current.address_space, info, &task_info_of(&stats)) {
+                0
+            } else {
+                -EFAULT
+            }
+        }
+
+        /// 设置进程 Id 的上限，返回原来的上限，`max` 为 0 时只查询。
+        ///
+        /// 上限至少为 2，能容纳 init 进程。
+        fn pid_max(&self, _caller: Caller, max: usize) -> isize {
+            let old = ProcId::max();
+            if max != 0 {
+                if !(2..=MAX_ID).contains(&max) {
+                    return -EINVAL;
+                }
+                ProcId::set_max(max);
+            }
+            old as _
+        }
+    }
+
+    /// 把 `value` 写到用户地址 `addr`，返回是否写入。
+    ///
+    /// 结构体可能跨页，各页不一定物理连续，要逐页翻译、逐页复制；有一页不可写就什么也不写。
+    fn copy_to_user<T>(space: &AddressSpace<Sv, SvManager>, addr: usize, value: &T) -> bool {
+        const WRITABLE: VmFlags<Sv> = VmFlags::build_from_str("W_V");
+        const PAGE_SIZE: usize = 1 << Sv::PAGE_BITS;
+        let len = core::mem::size_of::<T>();
+        let mut offset = 0;
+        while offset < len {
+            if space
+                .translate::<u8>(VAddr::new(addr + offset), WRITABLE)
+                .is_none()
+            {
+                return false;
+            }
+            offset += PAGE_SIZE - (addr + offset) % PAGE_SIZE;
+        }
+        let src = value as *const T as *const u8;
+        let mut copied = 0;
+        while copied < len {
+            let dst = addr + copied;
+            let n = (len - copied).min(PAGE_SIZE - dst % PAGE_SIZE);
+            let ptr = space.translate::<u8>(VAddr::new(dst), WRITABLE).unwrap();
+            unsafe { core::ptr::copy_nonoverlapping(src.add(copied), ptr.as_ptr(), n) };
+            copied += n;
+        }
+        true
+    }
+
+    /// 把时钟周期数换算成以 `per_second` 分之一秒为单位的时间。
+    fn ticks_to(ticks: usize, per_second: usize) -> usize {
+        (ticks as u128 * per_second as u128 / platform::get().timebase_frequency as u128) as _
+    }
+
+    /// 用运行统计填写 `getrusage` 的结果。
+    fn rusage_of(stats: &TaskStats) -> Rusage {
+        Rusage {
+            ru_utime: TimeVal::from_microsecond(ticks_to(stats.user_time, 1_000_000)),
+            ru_stime: TimeVal::from_microsecond(ticks_to(stats.system_time, 1_000_000)),
+            ru_nvcsw: stats.voluntary_switches() as _,
+            ru_nivcsw: stats.preemptions as _,
+            ..Default::default()
+        }
+    }
+
+    /// 用运行统计填写 `task_info` 的结果。
+    fn task_info_of(stats: &TaskStats) -> TaskInfo {
+        let mut info = TaskInfo {
+            utime: ticks_to(stats.user_time, 1_000_000),
+            stime: ticks_to(stats.system_time, 1_000_000),
+            switches: stats.switches,
+            preemptions: stats.preemptions,
+            ..TaskInfo::ZERO
+        };
+        for (id, count) in stats.syscalls() {
+            info.syscalls += count;
+            if let Some(times) = info.syscall_times.get_mut(id) {
+                *times = count as _;
+            }
+        }
+        info
+    }
+
+    impl Memory for SyscallContext {
+        fn brk(&self, _caller: Caller, addr: usize) -> isize {
+            let current = unsafe { PROCESSOR.get_current_proc().unwrap() };
+            current.brk(addr) as _
+        }
+
+        fn mmap(
+            &self,
+            _caller: Caller,
+            addr: usize,
+            length: usize,
+            prot: i32,
+            flags: i32,
+            fd: i32,
+            offset: usize,
+        ) -> isize {
+            let current = unsafe { PROCESSOR.get_current_proc().unwrap() };
+            let file = if flags & MAP_ANONYMOUS != 0 {
+                None
+            } else if let Some(Some(file)) = current.fd_table.get(fd as usize) {
+                let file = file.lock();
+                // 共享的可写映射会写回文件，要求文件可写
+                let writable = flags & MAP_SHARED == 0 || prot & PROT_WRITE == 0 || file.writable();
+                match &file.inode {
+                    Some(inode) if file.readable() && writable => Some((inode.clone(), offset)),
+                    _ => {
+                        log::error!("file not mappable");
+                        return -1;
+                    }
                 }
-                return dead_pid.get_usize() as _;
             } else {
-                // 等待的子进程不存在
+                log::error!("unsupported fd: {fd}");
                 return -1;
+            };
+            current
+                .mmaps
+                .map(&current.address_space, addr, length, prot, flags, file)
+                .map_or_else(|errno| -errno, |addr| addr as isize)
+        }
+
+        fn munmap(&self, _caller: Caller, addr: usize, length: usize) -> isize {
+            let current = unsafe { PROCESSOR.get_current_proc().unwrap() };
+            if current
+                .mmaps
+                .unmap(&mut current.address_space, addr, length)
+            {
+                0
+            } else {
+                -1
             }
         }
 
-        fn getpid(&self, _caller: Caller) -> isize {
+        fn msync(&self, _caller: Caller, addr: usize, length: usize, _flags: i32) -> isize {
             let current = unsafe { PROCESSOR.get_current_proc().unwrap() };
-            current.pid.get_usize() as _
+            if current.mmaps.sync(&current.address_space, addr, length) {
+                0
+            } else {
+                -1
+            }
+        }
+
+        fn shmget(&self, _caller: Caller, key: usize, size: usize, flags: usize) -> isize {
+            SHM.lock()
+                .get(key, size, flags)
+                .map_or(-1, |id| id as isize)
+        }
+
+        fn shmat(&self, _caller: Caller, shmid: usize, addr: usize, _flags: usize) -> isize {
+            let current = unsafe { PROCESSOR.get_current_proc().unwrap() };
+            SHM.lock()
+                .segment(shmid)
+                .and_then(|segment| current.attach(segment, addr))
+                .map_or(-1, |addr| addr as isize)
+        }
+
+        fn shmdt(&self, _caller: Caller, addr: usize) -> isize {
+            let current = unsafe { PROCESSOR.get_current_proc().unwrap() };
+            if current.detach(addr) {
+                0
+            } else {
+                -1
+            }
+        }
+
+        fn shmctl(&self, _caller: Caller, shmid: usize, cmd: usize, _buf: usize) -> isize {
+            match cmd {
+                IPC_RMID if SHM.lock().remove(shmid) => 0,
+                _ => -1,
+            }
         }
     }
 
@@ -502,22 +1150,33 @@ mod impls {
         fn sched_yield(&self, _caller: Caller) -> isize {
             0
         }
+
+        /// 设置当前线程的优先级，调度器不支持优先级或优先级不合法时返回 `-EINVAL`。
+        fn set_priority(&self, _caller: Caller, prio: isize) -> isize {
+            let tid = unsafe { PROCESSOR.current().unwrap().tid };
+            if prio >= 0 && unsafe { PROCESSOR.set_priority(tid, prio as _) } {
+                prio
+            } else {
+                -EINVAL
+            }
+        }
     }
 
     impl Clock for SyscallContext {
         #[inline]
         fn clock_gettime(&self, _caller: Caller, clock_id: ClockId, tp: usize) -> isize {
-            const WRITABLE: VmFlags<Sv39> = VmFlags::build_from_str("W_V");
+            const WRITABLE: VmFlags<Sv> = VmFlags::build_from_str("W_V");
             match clock_id {
                 ClockId::CLOCK_MONOTONIC => {
                     if let Some(mut ptr) = unsafe { PROCESSOR.get_current_proc().unwrap() }
                         .address_space
                         .translate(VAddr::new(tp), WRITABLE)
                     {
-                        let time = riscv::register::time::read() * 10000 / 125;
+                        let timebase = platform::get().timebase_frequency;
+                        let time = riscv::register::time::read();
                         *unsafe { ptr.as_mut() } = TimeSpec {
-                            tv_sec: time / 1_000_000_000,
-                            tv_nsec: time % 1_000_000_000,
+                            tv_sec: time / timebase,
+                            tv_nsec: time % timebase * 1_000_000_000 / timebase,
                         };
                         0
                     } else {
@@ -531,18 +1190,28 @@ mod impls {
     }
 
     impl Signal for SyscallContext {
+        /// `pid` 为 0 时发送给调用者所在的进程组，小于 -1 时发送给进程组 `-pid`。
         fn kill(&self, _caller: Caller, pid: isize, signum: u8) -> isize {
-            if let Some(target_task) =
-                unsafe { PROCESSOR.get_proc(ProcId::from_usize(pid as usize)) }
-            {
-                if let Ok(signal_no) = SignalNo::try_from(signum) {
-                    if signal_no != SignalNo::ERR {
-                        target_task.signal.add_signal(signal_no);
-                        return 0;
-                    }
+            let signal_no = match SignalNo::try_from(signum) {
+                Ok(signal_no) if signal_no != SignalNo::ERR => signal_no,
+                _ => return -1,
+            };
+            let sent = match pid {
+                0 => {
+                    let current = unsafe { PROCESSOR.get_current_proc().unwrap() };
+                    let pgid = unsafe { PROCESSOR.pgid(current.pid) }.unwrap();
+                    signal_group(pgid, signal_no)
                 }
+                // 不支持向所有进程发送信号
+                -1 => false,
+                pid if pid < 0 => signal_group(ProcId::from_usize(-pid as _), signal_no),
+                pid => send_signal(ProcId::from_usize(pid as _), signal_no),
+            };
+            if sent {
+                0
+            } else {
+                -1
             }
-            -1
         }
 
         fn sigaction(
@@ -622,33 +1291,32 @@ mod impls {
         fn thread_create(&self, _caller: Caller, entry: usize, arg: usize) -> isize {
             // 主要的问题是用户栈怎么分配，这里不增加其他的数据结构，直接从规定的栈顶的位置从下搜索是否被映射
             let current_proc = unsafe { PROCESSOR.get_current_proc().unwrap() };
+            // 线程 Id 用完时失败
+            let Some(tid) = ThreadId::new() else {
+                return -EAGAIN;
+            };
             // 第一个线程的用户栈栈底
-            let mut vpn = VPN::<Sv39>::new((1 << 26) - 2);
+            let mut vpn = VmLayout::<Sv>::top_pages(2).start;
             let addrspace = &mut current_proc.address_space;
             loop {
-                let idx = vpn.index_in(Sv39::MAX_LEVEL);
+                let idx = vpn.index_in(Sv::MAX_LEVEL);
                 if !addrspace.root()[idx].is_valid() {
                     break;
                 }
-                vpn = VPN::<Sv39>::new(vpn.val() - 3);
+                vpn = VPN::<Sv>::new(vpn.val() - 3);
             }
-            let stack = unsafe {
-                alloc_zeroed(Layout::from_size_align_unchecked(
-                    2 << Sv39::PAGE_BITS,
-                    1 << Sv39::PAGE_BITS,
-                ))
-            };
+            let stack = kernel_frame::alloc(2, Owner::Stack).unwrap().as_ptr();
             addrspace.map_extern(
                 vpn..vpn + 2,
-                PPN::new(stack as usize >> Sv39::PAGE_BITS),
-                VmFlags::build_from_str("U_WRV"),
+                PPN::new(linker::virt_to_phys(stack as usize) >> Sv::PAGE_BITS),
+                SvManager::owned(VmFlags::build_from_str("U_WRV")),
             );
-            let satp = (8 << 60) | addrspace.root_ppn().val();
+            let satp = VmLayout::<Sv>::satp(addrspace.root_ppn());
             let mut context = kernel_context::LocalContext::user(entry);
             *context.sp_mut() = (vpn + 2).base().val();
             *context.a_mut(0) = arg;
-            let thread = Thread::new(satp, context);
-            let tid = thread.tid;
+            *context.x_mut(4) = tid.get_usize();
+            let thread = Thread::new(tid, satp, context);
             unsafe {
                 PROCESSOR.add(tid, thread, current_proc.pid);
             }
@@ -1,12 +1,18 @@
-use crate::{map_portal, Sv39Manager, PROCESSOR};
+use crate::{
+    map_portal,
+    shm::{ShmAttach, ShmSegment},
+    Sv, SvManager, PROCESSOR,
+};
 use alloc::sync::Arc;
-use alloc::{alloc::alloc_zeroed, boxed::Box, vec::Vec};
-use core::{alloc::Layout, str::FromStr};
+use alloc::{boxed::Box, vec::Vec};
+use core::str::FromStr;
 use easy_fs::FileHandle;
 use kernel_context::{foreign::ForeignContext, LocalContext};
+use kernel_frame::Owner;
+use kernel_mmap::Mappings;
 use kernel_vm::{
-    page_table::{MmuMeta, Sv39, VAddr, VmFlags, PPN, VPN},
-    AddressSpace,
+    page_table::{MmuMeta, VAddr, VmFlags, VmMeta, PPN, VPN},
+    AddressSpace, VmLayout,
 };
 use rcore_task_manage::{ProcId, ThreadId};
 use signal::Signal;
@@ -18,6 +24,14 @@ use xmas_elf::{
     program, ElfFile,
 };
 
+/// 程序断点的上限，堆不能长进 mmap 区域、用户栈和跳板页。
+const HEAP_TOP: usize = VmLayout::<Sv>::USER_TOP >> 2;
+
+/// 共享内存段的映射权限。
+const SHM_FLAGS: VmFlags<Sv> = VmFlags::build_from_str("U_WRV");
+/// 内核自动选择挂载地址时，从用户地址空间的中点开始。
+const SHM_BASE: usize = VmLayout::<Sv>::USER_TOP >> 1;
+
 /// 线程
 pub struct Thread {
     /// 不可变
@@ -27,9 +41,9 @@ pub struct Thread {
 }
 
 impl Thread {
-    pub fn new(satp: usize, context: LocalContext) -> Self {
+    pub fn new(tid: ThreadId, satp: usize, context: LocalContext) -> Self {
         Self {
-            tid: ThreadId::new(),
+            tid,
             context: ForeignContext { context, satp },
         }
     }
@@ -40,7 +54,11 @@ pub struct Process {
     /// 不可变
     pub pid: ProcId,
     /// 可变
-    pub address_space: AddressSpace<Sv39, Sv39Manager>,
+    pub address_space: AddressSpace<Sv, SvManager>,
+    /// 堆底，紧接在 ELF 的最后一个段之后
+    pub heap_bottom: usize,
+    /// 程序断点（program break），即堆顶
+    pub program_brk: usize,
     /// 文件描述符表
     pub fd_table: Vec<Option<Mutex<FileHandle>>>,
     /// 信号模块
@@ -49,30 +67,52 @@ pub struct Process {
     pub semaphore_list: Vec<Option<Arc<Semaphore>>>,
     pub mutex_list: Vec<Option<Arc<dyn MutexTrait>>>,
     pub condvar_list: Vec<Option<Arc<Condvar>>>,
+    /// 挂载的共享内存段
+    pub shm: Vec<ShmAttach>,
+    /// `mmap` 建立的映射
+    pub mmaps: Mappings<Sv>,
 }
 
 impl Process {
     /// 只支持一个线程
     pub fn exec(&mut self, elf: ElfFile) {
-        let (proc, thread) = Process::from_elf(elf).unwrap();
-        self.address_space = proc.address_space;
+        // 沿用原来的进程 Id 和主线程 Id
+        let pthreads = unsafe { PROCESSOR.get_thread(self.pid).unwrap() };
+        let tid = pthreads[0];
+        let (mut proc, thread) = Process::from_elf(self.pid, tid, elf).unwrap();
+        // 换下的地址空间和映射随 `proc` 一起释放，共享映射的脏页在那时写回
+        core::mem::swap(&mut self.address_space, &mut proc.address_space);
+        core::mem::swap(&mut self.mmaps, &mut proc.mmaps);
+        self.heap_bottom = proc.heap_bottom;
+        self.program_brk = proc.program_brk;
+        self.shm.clear();
         unsafe {
-            let pthreads = PROCESSOR.get_thread(self.pid).unwrap();
-            PROCESSOR.get_task(pthreads[0]).unwrap().context = thread.context;
+            PROCESSOR.get_task(tid).unwrap().context = thread.context;
         }
     }
     /// 只支持一个线程
-    pub fn fork(&mut self) -> Option<(Self, Thread)> {
-        // 子进程 pid
-        let pid = ProcId::new();
+    pub fn fork(&mut self, pid: ProcId, tid: ThreadId) -> Option<(Self, Thread)> {
+        // 空闲页帧不足以复制地址空间时失败，而不是复制到一半时耗尽
+        let areas = &self.address_space.areas;
+        let pages: usize = areas.iter().map(|r| r.end.val() - r.start.val()).sum();
+        // 每个页表页有 512 项，区域的首尾可能各多占一个页表页
+        let tables = (pages >> 9) + areas.len() * 2 * (Sv::MAX_LEVEL + 1);
+        if kernel_frame::stats().free() < pages + tables + self.mmaps.fork_cost() {
+            return None;
+        }
         // 复制父进程地址空间
         let parent_addr_space = &self.address_space;
-        let mut address_space: AddressSpace<Sv39, Sv39Manager> = AddressSpace::new();
+        let mut address_space: AddressSpace<Sv, SvManager> = AddressSpace::new();
         parent_addr_space.cloneself(&mut address_space);
+        // 共享内存段映射到相同的物理页
+        for attach in &self.shm {
+            address_space.map_shared(attach.range.clone(), attach.segment.ppn(), SHM_FLAGS);
+        }
+        let mmaps = self.mmaps.fork(&mut self.address_space, &mut address_space);
         map_portal(&address_space);
         // 线程
         let pthreads = unsafe { PROCESSOR.get_thread(self.pid).unwrap() };
-        let context = unsafe {
+        let mut context = unsafe {
             PROCESSOR
                 .get_task(pthreads[0])
                 .unwrap()
@@ -80,8 +120,9 @@ impl Process {
                 .context
                 .clone()
         };
-        let satp = (8 << 60) | address_space.root_ppn().val();
-        let thread = Thread::new(satp, context);
+        *context.x_mut(4) = tid.get_usize();
+        let satp = VmLayout::<Sv>::satp(address_space.root_ppn());
+        let thread = Thread::new(tid, satp, context);
         // 复制父进程文件符描述表
         let mut new_fd_table: Vec<Option<Mutex<FileHandle>>> = Vec::new();
         for fd in self.fd_table.iter_mut() {
@@ -95,17 +136,122 @@ impl Process {
             Self {
                 pid,
                 address_space,
+                heap_bottom: self.heap_bottom,
+                program_brk: self.program_brk,
                 fd_table: new_fd_table,
                 signal: self.signal.from_fork(),
                 semaphore_list: Vec::new(),
                 mutex_list: Vec::new(),
                 condvar_list: Vec::new(),
+                shm: self.shm.clone(),
+                mmaps,
             },
             thread,
         ))
     }
 
-    pub fn from_elf(elf: ElfFile) -> Option<(Self, Thread)> {
+    /// 把共享内存段挂载到 `addr`，`addr` 为 0 时由内核选择地址。
+    ///
+    /// 成功时返回挂载的地址。
+    pub fn attach(&mut self, segment: Arc<ShmSegment>, addr: usize) -> Option<usize> {
+        const PAGE_MASK: usize = (1 << Sv::PAGE_BITS) - 1;
+        let start = if addr == 0 {
+            // 自用户地址空间中点向上，依次排在已挂载的段之后
+            self.shm
+                .iter()
+                .map(|attach| attach.range.end.val())
+                .max()
+                .unwrap_or(SHM_BASE >> Sv::PAGE_BITS)
+        } else if addr & PAGE_MASK == 0 {
+            addr >> Sv::PAGE_BITS
+        } else {
+            None?
+        };
+        let end = start + segment.pages();
+        if !self.mmaps.is_free(&self.address_space, start..end) {
+            None?
+        }
+        let range = VPN::new(start)..VPN::new(end);
+        self.address_space
+            .map_shared(range.clone(), segment.ppn(), SHM_FLAGS);
+        self.shm.push(ShmAttach { range, segment });
+        Some(start << Sv::PAGE_BITS)
+    }
+
+    /// 卸载挂载在 `addr` 的共享内存段。
+    pub fn detach(&mut self, addr: usize) -> bool {
+        let vpn = VAddr::<Sv>::new(addr).floor();
+        match self.shm.iter().position(|attach| attach.range.start == vpn) {
+            Some(i) => {
+                let attach = self.shm.swap_remove(i);
+                self.address_space.unmap(attach.range);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 处理缺页，返回是否已解决。
+    #[inline]
+    pub fn page_fault(&mut self, addr: usize) -> bool {
+        self.mmaps.page_fault(&mut self.address_space, addr)
+    }
+
+    /// 把程序断点调整到 `addr`，返回调整后的程序断点。
+    ///
+    /// `addr` 低于堆底或高于 [`HEAP_TOP`] 时不调整，可以用 `brk(0)` 查询当前的程序断点。
+    /// 堆收缩时已映射的页保留，再次扩张时直接使用。
+    /// 新的堆区域与其他映射重叠或者页帧不足时不调整。
+    pub fn brk(&mut self, addr: usize) -> usize {
+        const VALID: VmFlags<Sv> = VmFlags::build_from_str("V");
+        if addr < self.heap_bottom || addr > HEAP_TOP {
+            return self.program_brk;
+        }
+        let end = VAddr::<Sv>::new(addr).ceil().val();
+        let space = &self.address_space;
+        let mapped = |vpn: usize| {
+            space
+                .translate::<u8>(VPN::<Sv>::new(vpn).base(), VALID)
+                .is_some()
+        };
+        // 堆总是从堆底连续映射，找到第一个未映射的页，从那里到新的堆顶都不能有映射
+        let start = (VAddr::<Sv>::new(self.program_brk).ceil().val()..end)
+            .find(|&vpn| !mapped(vpn))
+            .unwrap_or(end);
+        if (start..end).any(mapped) {
+            return self.program_brk;
+        }
+        // 逐页映射，页帧不足时撤销这次映射的页
+        for vpn in start..end {
+            // 留出映射这一页可能需要的页表页
+            let frame = if kernel_frame::stats().free() > Sv::MAX_LEVEL {
+                kernel_frame::alloc(1, Owner::User)
+            } else {
+                None
+            };
+            let Some(frame) = frame else {
+                for vpn in start..vpn {
+                    let page = VPN::<Sv>::new(vpn);
+                    let ptr = self
+                        .address_space
+                        .translate::<u8>(page.base(), VALID)
+                        .unwrap();
+                    self.address_space.unmap(page..VPN::new(vpn + 1));
+                    kernel_frame::release(ptr, 1);
+                }
+                return self.program_brk;
+            };
+            self.address_space.map_extern(
+                VPN::new(vpn)..VPN::new(vpn + 1),
+                PPN::new(linker::virt_to_phys(frame.as_ptr() as usize) >> Sv::PAGE_BITS),
+                SvManager::owned(VmFlags::build_from_str("U_WRV")),
+            );
+        }
+        self.program_brk = addr;
+        addr
+    }
+
+    pub fn from_elf(pid: ProcId, tid: ThreadId, elf: ElfFile) -> Option<(Self, Thread)> {
         let entry = match elf.header.pt2 {
             HeaderPt2::Header64(pt2)
                 if pt2.type_.as_type() == header::Type::Executable
@@ -116,10 +262,11 @@ impl Process {
             _ => None?,
         };
 
-        const PAGE_SIZE: usize = 1 << Sv39::PAGE_BITS;
+        const PAGE_SIZE: usize = 1 << Sv::PAGE_BITS;
         const PAGE_MASK: usize = PAGE_SIZE - 1;
 
         let mut address_space = AddressSpace::new();
+        let mut heap_bottom = 0;
         for program in elf.program_iter() {
             if !matches!(program.get_type(), Ok(program::Type::Load)) {
                 continue;
@@ -129,6 +276,7 @@ impl Process {
             let len_file = program.file_size() as usize;
             let off_mem = program.virtual_addr() as usize;
             let end_mem = off_mem + program.mem_size() as usize;
+            heap_bottom = heap_bottom.max(end_mem);
             assert_eq!(off_file & PAGE_MASK, off_mem & PAGE_MASK);
 
             let mut flags: [u8; 5] = *b"U___V";
@@ -148,29 +296,30 @@ impl Process {
                 VmFlags::from_str(unsafe { core::str::from_utf8_unchecked(&flags) }).unwrap(),
             );
         }
+        // 堆从 ELF 最后一个段之后的页开始
+        let heap_bottom = (heap_bottom + PAGE_MASK) & !PAGE_MASK;
         // 映射用户栈
-        let stack = unsafe {
-            alloc_zeroed(Layout::from_size_align_unchecked(
-                2 << Sv39::PAGE_BITS,
-                1 << Sv39::PAGE_BITS,
-            ))
-        };
+        let stack = kernel_frame::alloc(2, Owner::Stack).unwrap().as_ptr();
         address_space.map_extern(
-            VPN::new((1 << 26) - 2)..VPN::new(1 << 26),
-            PPN::new(stack as usize >> Sv39::PAGE_BITS),
-            VmFlags::build_from_str("U_WRV"),
+            VmLayout::<Sv>::top_pages(2),
+            PPN::new(linker::virt_to_phys(stack as usize) >> Sv::PAGE_BITS),
+            SvManager::owned(VmFlags::build_from_str("U_WRV")),
         );
         // 映射异界传送门
         map_portal(&address_space);
-        let satp = (8 << 60) | address_space.root_ppn().val();
+        let satp = VmLayout::<Sv>::satp(address_space.root_ppn());
         let mut context = LocalContext::user(entry);
-        *context.sp_mut() = 1 << 38;
-        let thread = Thread::new(satp, context);
+        *context.sp_mut() = VmLayout::<Sv>::USER_TOP;
+        // 用户线程的 `tp` 是线程号，用户库的控制台锁据此区分线程
+        *context.x_mut(4) = tid.get_usize();
+        let thread = Thread::new(tid, satp, context);
 
         Some((
             Self {
-                pid: ProcId::new(),
+                pid,
                 address_space,
+                heap_bottom,
+                program_brk: heap_bottom,
                 fd_table: vec![
                     // Stdin
                     Some(Mutex::new(FileHandle::empty(true, false))),
@@ -181,8 +330,17 @@ impl Process {
                 semaphore_list: Vec::new(),
                 mutex_list: Vec::new(),
                 condvar_list: Vec::new(),
+                shm: Vec::new(),
+                mmaps: Mappings::new(),
             },
             thread,
         ))
     }
 }
+
+impl Drop for Process {
+    fn drop(&mut self) {
+        // 进程无论以何种方式结束，共享映射的脏页都要写回
+        self.mmaps.sync_all(&self.address_space);
+    }
+}
@@ -1,16 +1,54 @@
-use crate::process::{Process, Thread};
-use alloc::collections::{BTreeMap, VecDeque};
-use rcore_task_manage::{Manage, PThreadManager, ProcId, Schedule, ThreadId};
+use crate::{
+    process::{Process, Thread},
+    tty,
+};
+use alloc::collections::BTreeMap;
+use kernel_alloc::{SlabBox, SlabCache};
+use rcore_task_manage::{Manage, PThreadManager, ProcId, Schedule, Stride, ThreadId};
+use signal::SignalNo;
 
 pub static mut PROCESSOR: PThreadManager<Process, Thread, ThreadManager, ProcManager> =
     PThreadManager::new();
 
+/// 进程实体的缓存。
+static PROCESSES: SlabCache<Process> = SlabCache::new("process");
+/// 线程实体的缓存。
+static THREADS: SlabCache<Thread> = SlabCache::new("thread");
+
+/// 向进程发送信号，进程不存在时返回 `false`。
+///
+/// 阻塞在 wait 上和等待终端输入的线程醒来处理信号，`SIGCONT` 和 `SIGKILL` 让暂停的进程继续运行。
+pub fn send_signal(pid: ProcId, signal: SignalNo) -> bool {
+    let Some(target) = (unsafe { PROCESSOR.get_proc(pid) }) else {
+        return false;
+    };
+    target.signal.add_signal(signal);
+    unsafe {
+        PROCESSOR.wake(pid);
+        tty::wake_proc(pid);
+        if matches!(signal, SignalNo::SIGCONT | SignalNo::SIGKILL) {
+            PROCESSOR.resume(pid);
+        }
+    }
+    true
+}
+
+/// 向进程组中的所有进程发送信号，进程组中没有进程时返回 `false`。
+pub fn signal_group(pgid: ProcId, signal: SignalNo) -> bool {
+    let group = unsafe { PROCESSOR.group(pgid) };
+    for &pid in &group {
+        send_signal(pid, signal);
+    }
+    !group.is_empty()
+}
+
 /// 任务管理器
-/// `tasks` 中保存所有的任务实体
-/// `ready_queue` 删除任务的实体
+/// `tasks` 中保存所有的任务实体，实体从 `THREADS` 缓存分配
+/// `scheduler` 调度就绪的任务，使用步长调度，处理器时间与 `set_priority` 设置的优先级成正比；
+/// 换成 `rcore_task_manage` 中的其他调度器即可改变调度算法
 pub struct ThreadManager {
-    tasks: BTreeMap<ThreadId, Thread>,
-    ready_queue: VecDeque<ThreadId>,
+    tasks: BTreeMap<ThreadId, SlabBox<Thread>>,
+    scheduler: Stride<ThreadId>,
 }
 
 impl ThreadManager {
@@ -18,7 +56,7 @@ impl ThreadManager {
     pub fn new() -> Self {
         Self {
             tasks: BTreeMap::new(),
-            ready_queue: VecDeque::new(),
+            scheduler: Stride::new(),
         }
     }
 }
@@ -27,35 +65,56 @@ impl Manage<Thread, ThreadId> for ThreadManager {
     /// 插入一个新任务
     #[inline]
     fn insert(&mut self, id: ThreadId, task: Thread) {
-        self.tasks.insert(id, task);
+        self.tasks.insert(
+            id,
+            THREADS.alloc(task).expect("failed to allocate a thread"),
+        );
     }
     /// 根据 id 获取对应的任务
     #[inline]
     fn get_mut(&mut self, id: ThreadId) -> Option<&mut Thread> {
-        self.tasks.get_mut(&id)
+        self.tasks.get_mut(&id).map(|task| &mut **task)
     }
     /// 删除任务实体
     #[inline]
     fn delete(&mut self, id: ThreadId) {
         self.tasks.remove(&id);
+        self.scheduler.remove(id);
     }
 }
 
 impl Schedule<ThreadId> for ThreadManager {
     /// 添加 id 进入调度队列
+    #[inline]
     fn add(&mut self, id: ThreadId) {
-        self.ready_queue.push_back(id);
+        self.scheduler.add(id);
     }
     /// 从调度队列中取出 id
+    #[inline]
     fn fetch(&mut self) -> Option<ThreadId> {
-        self.ready_queue.pop_front()
+        self.scheduler.fetch()
+    }
+    /// 运行的任务用完一个时钟中断周期
+    #[inline]
+    fn tick(&mut self, id: ThreadId) -> bool {
+        self.scheduler.tick(id)
+    }
+    /// 设置任务的优先级
+    #[inline]
+    fn set_priority(&mut self, id: ThreadId, priority: usize) -> bool {
+        self.scheduler.set_priority(id, priority)
+    }
+    /// 从调度队列中删除任务
+    #[inline]
+    fn remove(&mut self, id: ThreadId) {
+        self.scheduler.remove(id);
     }
 }
 
 /// 进程管理器
-/// `procs` 中保存所有的进程实体
+/// `procs` 中保存所有的进程实体，实体从 `PROCESSES` 缓存分配
 pub struct ProcManager {
-    procs: BTreeMap<ProcId, Process>,
+    procs: BTreeMap<ProcId, SlabBox<Process>>,
 }
 
 impl ProcManager {
@@ -71,12 +130,15 @@ impl Manage<Process, ProcId> for ProcManager {
     /// 插入一个新任务
     #[inline]
     fn insert(&mut self, id: ProcId, item: Process) {
-        self.procs.insert(id, item);
+        self.procs.insert(
+            id,
+            PROCESSES.alloc(item).expect("failed to allocate a process"),
+        );
     }
     /// 根据 id 获取对应的任务
     #[inline]
     fn get_mut(&mut self, id: ProcId) -> Option<&mut Process> {
-        self.procs.get_mut(&id)
+        self.procs.get_mut(&id).map(|proc| &mut **proc)
     }
     /// 删除任务实体
     #[inline]
@@ -0,0 +1,105 @@
+use crate::Sv;
+use alloc::{collections::BTreeMap, sync::Arc};
+use core::{ops::Range, ptr::NonNull};
+use kernel_frame::Owner;
+use kernel_vm::page_table::{MmuMeta, PPN, VPN};
+use spin::Mutex;
+use syscall::{IPC_CREAT, IPC_EXCL, IPC_PRIVATE};
+
+/// 共享内存段表。
+pub static SHM: Mutex<ShmTable> = Mutex::new(ShmTable::new());
+
+/// 共享内存段。
+///
+/// 段的物理页由所有挂载它的进程共同持有，最后一个引用释放时回收。
+pub struct ShmSegment {
+    base: usize,
+    pages: usize,
+}
+
+impl ShmSegment {
+    fn new(pages: usize) -> Self {
+        let base = kernel_frame::alloc(pages, Owner::User).unwrap().as_ptr() as usize;
+        Self { base, pages }
+    }
+
+    /// 段的起始物理页号。
+    #[inline]
+    pub fn ppn(&self) -> PPN<Sv> {
+        PPN::new(linker::virt_to_phys(self.base) >> Sv::PAGE_BITS)
+    }
+
+    /// 段的页数。
+    #[inline]
+    pub fn pages(&self) -> usize {
+        self.pages
+    }
+}
+
+impl Drop for ShmSegment {
+    fn drop(&mut self) {
+        kernel_frame::release(NonNull::new(self.base as _).unwrap(), self.pages);
+    }
+}
+
+/// 进程挂载的一个共享内存段。
+#[derive(Clone)]
+pub struct ShmAttach {
+    pub range: Range<VPN<Sv>>,
+    pub segment: Arc<ShmSegment>,
+}
+
+/// 以键和编号索引共享内存段。
+pub struct ShmTable {
+    keys: BTreeMap<usize, usize>,
+    segments: BTreeMap<usize, Arc<ShmSegment>>,
+    next_id: usize,
+}
+
+impl ShmTable {
+    const fn new() -> Self {
+        Self {
+            keys: BTreeMap::new(),
+            segments: BTreeMap::new(),
+            next_id: 0,
+        }
+    }
+
+    /// 按 `shmget` 的语义查找或创建段，返回段编号。
+    pub fn get(&mut self, key: usize, size: usize, flags: usize) -> Option<usize> {
+        let pages = (size + (1 << Sv::PAGE_BITS) - 1) >> Sv::PAGE_BITS;
+        if key != IPC_PRIVATE {
+            if let Some(&id) = self.keys.get(&key) {
+                if flags & IPC_CREAT != 0 && flags & IPC_EXCL != 0 {
+                    return None;
+                }
+                return (self.segments[&id].pages >= pages).then_some(id);
+            }
+            if flags & IPC_CREAT == 0 {
+                return None;
+            }
+        }
+        if pages == 0 {
+            return None;
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        self.segments.insert(id, Arc::new(ShmSegment::new(pages)));
+        if key != IPC_PRIVATE {
+            self.keys.insert(key, id);
+        }
+        Some(id)
+    }
+
+    /// 取出编号对应的段。
+    #[inline]
+    pub fn segment(&self, id: usize) -> Option<Arc<ShmSegment>> {
+        self.segments.get(&id).cloned()
+    }
+
+    /// 从表中移除段，已挂载的进程仍可继续使用，直到全部卸载。
+    pub fn remove(&mut self, id: usize) -> bool {
+        self.keys.retain(|_, v| *v != id);
+        self.segments.remove(&id).is_some()
+    }
+}
@@ -0,0 +1,134 @@
+//! 控制台终端。
+//!
+//! 前台进程组由 `TIOCSPGRP` 设置，收到 Ctrl-C、Ctrl-Z 时向前台进程组中的所有进程发送 `SIGINT`、`SIGTSTP`。
+//! 还没有设置前台进程组，或者前台进程组中的进程都退出了，Ctrl-C、Ctrl-Z 只丢弃正在编辑的行。
+//!
+//! 还没有输入时读终端的线程阻塞，有输入、收到 Ctrl-C、Ctrl-Z 或者所属进程收到信号时唤醒，重新执行 `read`。
+
+use crate::{processor::signal_group, PROCESSOR};
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use rcore_console::tty::{Tty, TtyMode, TtySignal};
+use rcore_task_manage::{ProcId, ThreadId};
+use signal::SignalNo;
+use spin::Mutex;
+use syscall::{Termios, EAGAIN};
+
+/// 控制台终端。
+static TTY: Mutex<Tty> = Mutex::new(Tty::new());
+
+/// 前台进程组号，`usize::MAX` 表示没有前台进程组。
+static FOREGROUND: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+/// 等待终端输入的线程。
+static READERS: Mutex<Vec<ThreadId>> = Mutex::new(Vec::new());
+
+/// 前台进程组，还没有设置时返回 `None`。
+#[inline]
+pub fn foreground() -> Option<ProcId> {
+    let pgid = FOREGROUND.load(Ordering::Relaxed);
+    (pgid != usize::MAX).then(|| ProcId::from_usize(pgid))
+}
+
+/// 设置前台进程组。
+#[inline]
+pub fn set_foreground(pgid: ProcId) {
+    FOREGROUND.store(pgid.get_usize(), Ordering::Relaxed);
+}
+
+/// 取出控制台上的输入，收到 Ctrl-C、Ctrl-Z 时向前台进程组发送信号。
+///
+/// 有可读的输入或收到信号时唤醒等待输入的线程，被中断的线程回到用户态之前处理信号。
+pub fn poll() {
+    let mut tty = TTY.lock();
+    let signal = tty.poll();
+    let readable = tty.readable();
+    drop(tty);
+    if let Some(signal) = signal {
+        deliver(signal);
+    }
+    if signal.is_some() || readable {
+        for tid in READERS.lock().drain(..) {
+            unsafe { PROCESSOR.re_enque(tid) };
+        }
+    }
+}
+
+/// 从终端读取，还没有输入时返回 `-EAGAIN`。
+pub fn read(buf: &mut [u8]) -> isize {
+    let mut tty = TTY.lock();
+    let signal = tty.poll();
+    let ans = tty.read(buf).map_or(-EAGAIN, |n| n as _);
+    drop(tty);
+    if let Some(signal) = signal {
+        deliver(signal);
+    }
+    ans
+}
+
+/// 线程 `tid` 阻塞等待终端输入。
+#[inline]
+pub fn wait(tid: ThreadId) {
+    READERS.lock().push(tid);
+}
+
+/// 唤醒进程 `pid` 中等待终端输入的线程，被中断的线程回到用户态之前处理信号。
+pub fn wake_proc(pid: ProcId) {
+    let Some(threads) = (unsafe { PROCESSOR.get_thread(pid) }).cloned() else {
+        return;
+    };
+    READERS.lock().retain(|&tid| {
+        if threads.contains(&tid) {
+            unsafe { PROCESSOR.re_enque(tid) };
+            false
+        } else {
+            true
+        }
+    });
+}
+
+/// 是否有线程在等待终端输入。
+#[inline]
+pub fn has_readers() -> bool {
+    !READERS.lock().is_empty()
+}
+
+/// 当前的终端设置。
+pub fn termios() -> Termios {
+    let tty = TTY.lock();
+    let mut lflag = 0;
+    if tty.isig() {
+        lflag |= Termios::ISIG;
+    }
+    if tty.mode() == TtyMode::Canonical {
+        lflag |= Termios::ICANON;
+    }
+    if tty.echo() {
+        lflag |= Termios::ECHO;
+    }
+    Termios { lflag }
+}
+
+/// 修改终端设置。
+pub fn set_termios(termios: &Termios) {
+    let mut tty = TTY.lock();
+    tty.set_isig(termios.lflag & Termios::ISIG != 0);
+    tty.set_mode(if termios.lflag & Termios::ICANON != 0 {
+        TtyMode::Canonical
+    } else {
+        TtyMode::Raw
+    });
+    tty.set_echo(termios.lflag & Termios::ECHO != 0);
+}
+
+fn deliver(signal: TtySignal) {
+    if let Some(pgid) = foreground() {
+        signal_group(
+            pgid,
+            match signal {
+                TtySignal::Interrupt => SignalNo::SIGINT,
+                TtySignal::Suspend => SignalNo::SIGTSTP,
+            },
+        );
+    }
+}
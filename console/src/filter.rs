@@ -0,0 +1,134 @@
+//! 按模块过滤日志。
+//!
+//! 过滤规则是逗号分隔的指令，与 `env_logger` 相同：
+//!
+//! - `warn`：没有匹配模块的日志的级别；
+//! - `easy_fs=trace`：`easy_fs` 及其子模块的日志级别；
+//! - `easy_fs`：等价于 `easy_fs=trace`。
+//!
+//! 多个模块匹配时取最长的。只有模块指令时，其他模块的日志全部关闭。
+
+use core::str::FromStr;
+use log::LevelFilter;
+
+/// 最多的模块指令数。
+const MAX_DIRECTIVES: usize = 16;
+/// 模块名的最大字节数。
+const NAME_MAX: usize = 32;
+
+/// 一条模块指令。
+#[derive(Clone, Copy)]
+struct Directive {
+    name: [u8; NAME_MAX],
+    len: usize,
+    level: LevelFilter,
+}
+
+impl Directive {
+    const EMPTY: Self = Self {
+        name: [0; NAME_MAX],
+        len: 0,
+        level: LevelFilter::Off,
+    };
+
+    #[inline]
+    fn name(&self) -> &[u8] {
+        &self.name[..self.len]
+    }
+
+    /// `target` 是否是这个模块或其子模块。
+    #[inline]
+    fn matches(&self, target: &str) -> bool {
+        let target = target.as_bytes();
+        target.starts_with(self.name())
+            && (target.len() == self.len || target[self.len..].starts_with(b"::"))
+    }
+}
+
+/// 日志过滤规则。
+pub(crate) struct Filter {
+    default: LevelFilter,
+    directives: [Directive; MAX_DIRECTIVES],
+    len: usize,
+}
+
+impl Filter {
+    /// 所有日志都打印。
+    pub const fn new() -> Self {
+        Self {
+            default: LevelFilter::Trace,
+            directives: [Directive::EMPTY; MAX_DIRECTIVES],
+            len: 0,
+        }
+    }
+
+    /// 解析过滤规则，忽略无效的指令。
+    pub fn parse(spec: &str) -> Self {
+        let mut ans = Self::new();
+        let mut default = None;
+        for item in spec.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            match item.split_once('=') {
+                Some((name, level)) => {
+                    if let Ok(level) = LevelFilter::from_str(level.trim()) {
+                        ans.set(name.trim(), level);
+                    }
+                }
+                None => match LevelFilter::from_str(item) {
+                    Ok(level) => default = Some(level),
+                    Err(_) => {
+                        ans.set(item, LevelFilter::Trace);
+                    }
+                },
+            }
+        }
+        ans.default = default.unwrap_or(if ans.len == 0 {
+            LevelFilter::Trace
+        } else {
+            LevelFilter::Off
+        });
+        ans
+    }
+
+    /// 设置模块的日志级别，模块名为空时设置默认级别。
+    ///
+    /// 模块名过长或指令已满时返回 `false`。
+    pub fn set(&mut self, name: &str, level: LevelFilter) -> bool {
+        if name.is_empty() {
+            self.default = level;
+            return true;
+        }
+        if let Some(d) = self.directives[..self.len]
+            .iter_mut()
+            .find(|d| d.name() == name.as_bytes())
+        {
+            d.level = level;
+            return true;
+        }
+        if name.len() > NAME_MAX || self.len == MAX_DIRECTIVES {
+            return false;
+        }
+        let d = &mut self.directives[self.len];
+        d.name[..name.len()].copy_from_slice(name.as_bytes());
+        d.len = name.len();
+        d.level = level;
+        self.len += 1;
+        true
+    }
+
+    /// 来自 `target` 的日志的级别。
+    pub fn level(&self, target: &str) -> LevelFilter {
+        self.directives[..self.len]
+            .iter()
+            .filter(|d| d.matches(target))
+            .max_by_key(|d| d.len)
+            .map_or(self.default, |d| d.level)
+    }
+
+    /// 所有规则中最高的级别，交给 `log` 预先过滤。
+    pub fn max_level(&self) -> LevelFilter {
+        self.directives[..self.len]
+            .iter()
+            .map(|d| d.level)
+            .fold(self.default, Ord::max)
+    }
+}
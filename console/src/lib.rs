@@ -1,13 +1,30 @@
-﻿//! 提供可定制实现的 `print!`、`println!` 和 `log::Log`。
+﻿//! 提供可定制实现的 `print!`、`println!` 和 `log::Log`，以及输入的行规程 [`tty`]。
+//!
+//! 每次 `print!`、每行 `println!` 和每条日志都先格式化到缓冲区，再在控制台锁内整体输出，
+//! 多个硬件线程或线程同时输出时不会交错在一行里。
+//!
+//! [`Console`] 提供时间戳、硬件线程号或进程号、线程号时，日志前加上形如 `[t=1.000000 hart=0 pid=1 tid=0]` 的前缀。
 
 #![no_std]
 #![deny(warnings, missing_docs)]
 
+pub mod tty;
+
+mod filter;
+#[cfg(feature = "log-buffer")]
+mod log_buffer;
+
 use core::{
     fmt::{self, Write},
-    str::FromStr,
+    hint::spin_loop,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
 };
-use spin::Once;
+use filter::Filter;
+use spin::{Mutex, Once};
+
+#[cfg(feature = "log-buffer")]
+pub use log_buffer::{clear_log, log_len, read_log, LOG_BUFFER_SIZE};
 
 /// 向用户提供 `log`。
 pub extern crate log;
@@ -26,6 +43,41 @@ pub trait Console: Sync {
             self.put_char(c);
         }
     }
+
+    /// 从控制台取出一个字符，没有输入时立即返回 `None`。
+    ///
+    /// 不支持输入的控制台不必实现。
+    #[inline]
+    fn get_char(&self) -> Option<u8> {
+        None
+    }
+
+    /// 单调时间戳，用于日志前缀，返回 `None` 时不打印。
+    #[inline]
+    fn timestamp(&self) -> Option<Duration> {
+        None
+    }
+
+    /// 当前硬件线程号，用于日志前缀，返回 `None` 时不打印。
+    #[inline]
+    fn hart_id(&self) -> Option<usize> {
+        None
+    }
+
+    /// 当前的进程号和线程号，用于日志前缀，返回 `None` 时不打印。
+    #[inline]
+    fn task_id(&self) -> Option<(usize, usize)> {
+        None
+    }
+
+    /// 当前控制流的标识，控制台锁以此区分不同控制流的输出和同一控制流的重入输出。
+    ///
+    /// 默认为硬件线程号。用户程序的多个线程共用控制台，应当覆盖为线程的标识。
+    /// 返回 `None` 表示只有一个控制流。
+    #[inline]
+    fn flow_id(&self) -> Option<usize> {
+        self.hart_id()
+    }
 }
 
 /// 库找到输出的方法：保存一个对象引用，这是一种单例。
@@ -37,10 +89,33 @@ pub fn init_console(console: &'static dyn Console) {
     log::set_logger(&Logger).unwrap();
 }
 
-/// 根据环境变量设置日志级别。
+/// 从控制台取出一个字符，没有输入时立即返回 `None`。
+#[inline]
+pub fn getchar() -> Option<u8> {
+    CONSOLE.get()?.get_char()
+}
+
+/// 日志过滤规则。
+static FILTER: Mutex<Filter> = Mutex::new(Filter::new());
+
+/// 根据环境变量设置日志过滤规则，替换之前的所有规则。
+///
+/// 规则形如 `warn,easy_fs=trace,kernel_vm=debug`：不带模块名的级别是默认级别，
+/// 带模块名的级别作用于这个模块及其子模块。没有设置时打印所有日志。
 pub fn set_log_level(env: Option<&str>) {
-    use log::LevelFilter as Lv;
-    log::set_max_level(env.and_then(|s| Lv::from_str(s).ok()).unwrap_or(Lv::Trace));
+    let filter = env.map_or(Filter::new(), Filter::parse);
+    log::set_max_level(filter.max_level());
+    *FILTER.lock() = filter;
+}
+
+/// 设置一个模块及其子模块的日志级别，模块名为空时设置默认级别。
+///
+/// 模块名超过 32 字节或已经设置了 16 个模块时返回 `false`。
+pub fn set_module_log_level(module: &str, level: log::LevelFilter) -> bool {
+    let mut filter = FILTER.lock();
+    let ans = filter.set(module, level);
+    log::set_max_level(filter.max_level());
+    ans
 }
 
 /// 打印一些测试信息。
@@ -68,7 +143,23 @@ pub fn test_log() {
 #[doc(hidden)]
 #[inline]
 pub fn _print(args: fmt::Arguments) {
-    Logger.write_fmt(args).unwrap();
+    let _lock = lock();
+    let mut line = Line::new();
+    line.write_fmt(args).unwrap();
+    line.flush();
+}
+
+/// 打印并换行。
+///
+/// 给宏用的，用户不会直接调它。
+#[doc(hidden)]
+#[inline]
+pub fn _println(args: fmt::Arguments) {
+    let _lock = lock();
+    let mut line = Line::new();
+    line.write_fmt(args).unwrap();
+    line.write_str("\n").unwrap();
+    line.flush();
 }
 
 /// 格式化打印。
@@ -84,32 +175,138 @@ macro_rules! print {
 macro_rules! println {
     () => ($crate::print!("\n"));
     ($($arg:tt)*) => {{
-        $crate::_print(core::format_args!($($arg)*));
-        $crate::println!();
+        $crate::_println(core::format_args!($($arg)*));
     }}
 }
 
-/// 这个 Unit struct 是 `core::fmt` 要求的。
-struct Logger;
+/// 控制台锁的持有者：控制流标识加一，0 表示未锁定。
+static LOCK: AtomicUsize = AtomicUsize::new(0);
+
+/// 控制台锁。
+///
+/// 同一控制流重入时（如输出过程中 panic）不再加锁，以免死锁。
+struct Lock(bool);
+
+#[inline]
+fn lock() -> Lock {
+    let me = CONSOLE.get().and_then(|c| c.flow_id()).unwrap_or(0) + 1;
+    loop {
+        match LOCK.compare_exchange_weak(0, me, Ordering::Acquire, Ordering::Relaxed) {
+            Ok(_) => break Lock(true),
+            Err(holder) if holder == me => break Lock(false),
+            Err(_) => spin_loop(),
+        }
+    }
+}
+
+impl Drop for Lock {
+    #[inline]
+    fn drop(&mut self) {
+        if self.0 {
+            LOCK.store(0, Ordering::Release);
+        }
+    }
+}
+
+/// 行缓冲区，一条记录攒满一行再交给控制台。
+///
+/// 用户程序的控制台每次输出是一次系统调用，整行输出才能保证多个线程的输出不交错。
+struct Line {
+    buf: [u8; Self::CAPACITY],
+    len: usize,
+}
+
+impl Line {
+    const CAPACITY: usize = 256;
 
-/// 实现 [`Write`] trait，格式化的基础。
-impl Write for Logger {
     #[inline]
-    fn write_str(&mut self, s: &str) -> Result<(), fmt::Error> {
-        let _ = CONSOLE.get().unwrap().put_str(s);
+    const fn new() -> Self {
+        Self {
+            buf: [0; Self::CAPACITY],
+            len: 0,
+        }
+    }
+
+    fn flush(&mut self) {
+        if self.len > 0 {
+            // 缓冲区中只有完整的字符串片段
+            let s = unsafe { core::str::from_utf8_unchecked(&self.buf[..self.len]) };
+            CONSOLE.get().unwrap().put_str(s);
+            self.len = 0;
+        }
+    }
+}
+
+/// 实现 [`Write`] trait，格式化的基础。
+impl Write for Line {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.len + s.len() > Self::CAPACITY {
+            self.flush();
+        }
+        if s.len() > Self::CAPACITY {
+            CONSOLE.get().unwrap().put_str(s);
+        } else {
+            self.buf[self.len..][..s.len()].copy_from_slice(s.as_bytes());
+            self.len += s.len();
+        }
         Ok(())
     }
 }
 
+/// 日志前缀，创建时取得时间戳和编号，输出到控制台和日志缓冲区的前缀相同。
+struct Prefix {
+    time: Option<Duration>,
+    hart: Option<usize>,
+    task: Option<(usize, usize)>,
+}
+
+impl Prefix {
+    #[inline]
+    fn new(console: &dyn Console) -> Self {
+        Self {
+            time: console.timestamp(),
+            hart: console.hart_id(),
+            task: console.task_id(),
+        }
+    }
+}
+
+impl fmt::Display for Prefix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.time.is_none() && self.hart.is_none() && self.task.is_none() {
+            return Ok(());
+        }
+        let mut sep = "[";
+        if let Some(time) = self.time {
+            write!(f, "{sep}t={}.{:06}", time.as_secs(), time.subsec_micros())?;
+            sep = " ";
+        }
+        if let Some(hart) = self.hart {
+            write!(f, "{sep}hart={hart}")?;
+            sep = " ";
+        }
+        if let Some((pid, tid)) = self.task {
+            write!(f, "{sep}pid={pid} tid={tid}")?;
+        }
+        write!(f, "] ")
+    }
+}
+
+/// 日志记录器。
+struct Logger;
+
 /// 实现 `log::Log` trait，提供分级日志。
 impl log::Log for Logger {
     #[inline]
-    fn enabled(&self, _metadata: &log::Metadata) -> bool {
-        true
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= FILTER.lock().level(metadata.target())
     }
 
     #[inline]
     fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
         use log::Level::*;
         let color_code: u8 = match record.level() {
             Error => 31,
@@ -118,11 +315,23 @@ impl log::Log for Logger {
             Debug => 32,
             Trace => 90,
         };
-        println!(
-            "\x1b[{color_code}m[{:>5}] {}\x1b[0m",
+        let prefix = Prefix::new(*CONSOLE.get().unwrap());
+        let _lock = lock();
+        let mut line = Line::new();
+        writeln!(
+            line,
+            "\x1b[{color_code}m{prefix}[{:>5}] {}\x1b[0m",
             record.level(),
             record.args(),
-        );
+        )
+        .unwrap();
+        line.flush();
+        #[cfg(feature = "log-buffer")]
+        log_buffer::record(format_args!(
+            "{prefix}[{:>5}] {}\n",
+            record.level(),
+            record.args()
+        ));
     }
 
     fn flush(&self) {}
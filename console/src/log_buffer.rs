@@ -0,0 +1,92 @@
+//! 日志环形缓冲区。
+//!
+//! 保存最近的日志记录，不带颜色，每条记录以换行结尾。缓冲区满时丢弃最早的整条记录。
+
+use core::fmt::{self, Write};
+use spin::Mutex;
+
+/// 日志缓冲区的字节数。
+pub const LOG_BUFFER_SIZE: usize = 16 << 10;
+
+struct LogBuffer {
+    buf: [u8; LOG_BUFFER_SIZE],
+    head: usize,
+    len: usize,
+}
+
+static BUFFER: Mutex<LogBuffer> = Mutex::new(LogBuffer {
+    buf: [0; LOG_BUFFER_SIZE],
+    head: 0,
+    len: 0,
+});
+
+impl LogBuffer {
+    /// 第 `i` 个字节。
+    #[inline]
+    fn at(&self, i: usize) -> u8 {
+        self.buf[(self.head + i) % LOG_BUFFER_SIZE]
+    }
+
+    /// 丢弃最早的一条记录。
+    fn drop_record(&mut self) {
+        while self.len > 0 {
+            let c = self.buf[self.head];
+            self.head = (self.head + 1) % LOG_BUFFER_SIZE;
+            self.len -= 1;
+            if c == b'\n' {
+                break;
+            }
+        }
+    }
+}
+
+impl Write for LogBuffer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for &c in s.as_bytes() {
+            if self.len == LOG_BUFFER_SIZE {
+                self.drop_record();
+            }
+            self.buf[(self.head + self.len) % LOG_BUFFER_SIZE] = c;
+            self.len += 1;
+        }
+        Ok(())
+    }
+}
+
+/// 追加一条记录。
+#[inline]
+pub(crate) fn record(args: fmt::Arguments) {
+    BUFFER.lock().write_fmt(args).unwrap();
+}
+
+/// 把缓冲区中最近的日志复制到 `buf`，返回复制的字节数。
+///
+/// `buf` 放不下所有日志时，从能放下的最早一条完整记录开始复制。不会取走日志。
+pub fn read_log(buf: &mut [u8]) -> usize {
+    let buffer = BUFFER.lock();
+    let mut skip = buffer.len.saturating_sub(buf.len());
+    if skip > 0 {
+        while skip < buffer.len && buffer.at(skip - 1) != b'\n' {
+            skip += 1;
+        }
+    }
+    let len = buffer.len - skip;
+    for (i, c) in buf[..len].iter_mut().enumerate() {
+        *c = buffer.at(skip + i);
+    }
+    len
+}
+
+/// 缓冲区中日志的字节数。
+#[inline]
+pub fn log_len() -> usize {
+    BUFFER.lock().len
+}
+
+/// 清空日志缓冲区。
+#[inline]
+pub fn clear_log() {
+    let mut buffer = BUFFER.lock();
+    buffer.head = 0;
+    buffer.len = 0;
+}
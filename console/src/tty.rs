@@ -0,0 +1,259 @@
+//! 终端行规程。
+//!
+//! 内核用 [`Tty`] 处理控制台输入：规范模式下回显输入、处理退格和删行，按行交给读者；
+//! 原始模式下每个字符直接交给读者。开启信号时收到 Ctrl-C、Ctrl-Z 由内核向前台进程组发送 `SIGINT`、`SIGTSTP`。
+//!
+//! ```ignore
+//! static TTY: Mutex<Tty> = Mutex::new(Tty::new());
+//!
+//! let mut tty = TTY.lock();
+//! match tty.poll() {
+//!     Some(TtySignal::Interrupt) => { /* 向前台进程组发送 SIGINT */ }
+//!     Some(TtySignal::Suspend) => { /* 向前台进程组发送 SIGTSTP */ }
+//!     None => {}
+//! }
+//! match tty.read(buf) {
+//!     Some(n) => n as isize,
+//!     None => -EAGAIN,
+//! }
+//! ```
+
+use crate::{getchar, print};
+
+/// 一行最多的字符数（含换行），超出的输入被丢弃。
+const LINE_MAX: usize = 256;
+/// 等待读取的输入最多的字符数，超出的输入被丢弃。
+const READY_MAX: usize = 1024;
+
+/// Ctrl-C，中断前台进程。
+const INTR: u8 = 0x03;
+/// Ctrl-D，文件结束。
+const EOF: u8 = 0x04;
+/// 退格。
+const BS: u8 = 0x08;
+/// Ctrl-U，删除整行。
+const KILL: u8 = 0x15;
+/// Ctrl-Z，暂停前台进程。
+const SUSP: u8 = 0x1a;
+/// 删除。
+const DEL: u8 = 0x7f;
+
+/// 终端模式。
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TtyMode {
+    /// 规范模式，按行读取，处理编辑字符和 Ctrl-C。
+    Canonical,
+    /// 原始模式，每个字符直接交给读者。
+    Raw,
+}
+
+/// 终端输入产生的信号。
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TtySignal {
+    /// Ctrl-C，中断前台进程组。
+    Interrupt,
+    /// Ctrl-Z，暂停前台进程组。
+    Suspend,
+}
+
+/// 终端。
+pub struct Tty {
+    mode: TtyMode,
+    echo: bool,
+    /// Ctrl-C、Ctrl-Z 是否产生信号，关闭时作为普通字符。
+    isig: bool,
+    /// 正在编辑的行。
+    line: [u8; LINE_MAX],
+    line_len: usize,
+    /// 等待读取的输入，环形队列。
+    ready: [u8; READY_MAX],
+    head: usize,
+    len: usize,
+    /// 收到了空行上的 Ctrl-D，下次读取返回 0。
+    eof: bool,
+}
+
+impl Tty {
+    /// 规范模式、开启回显和信号的终端。
+    pub const fn new() -> Self {
+        Self {
+            mode: TtyMode::Canonical,
+            echo: true,
+            isig: true,
+            line: [0; LINE_MAX],
+            line_len: 0,
+            ready: [0; READY_MAX],
+            head: 0,
+            len: 0,
+            eof: false,
+        }
+    }
+
+    /// 终端模式。
+    #[inline]
+    pub fn mode(&self) -> TtyMode {
+        self.mode
+    }
+
+    /// 设置终端模式，切换到原始模式时正在编辑的行立即交给读者。
+    pub fn set_mode(&mut self, mode: TtyMode) {
+        if mode == TtyMode::Raw {
+            self.submit_line();
+        }
+        self.mode = mode;
+    }
+
+    /// 是否回显。
+    #[inline]
+    pub fn echo(&self) -> bool {
+        self.echo
+    }
+
+    /// 设置是否回显。
+    #[inline]
+    pub fn set_echo(&mut self, echo: bool) {
+        self.echo = echo;
+    }
+
+    /// Ctrl-C、Ctrl-Z 是否产生信号。
+    #[inline]
+    pub fn isig(&self) -> bool {
+        self.isig
+    }
+
+    /// 设置 Ctrl-C、Ctrl-Z 是否产生信号。
+    #[inline]
+    pub fn set_isig(&mut self, isig: bool) {
+        self.isig = isig;
+    }
+
+    /// 取出控制台上所有的输入交给行规程，返回其中最后产生的信号。
+    pub fn poll(&mut self) -> Option<TtySignal> {
+        let mut signal = None;
+        while let Some(c) = getchar() {
+            signal = self.input(c).or(signal);
+        }
+        signal
+    }
+
+    /// 处理一个输入字符，开启信号时收到 Ctrl-C、Ctrl-Z 返回产生的信号。
+    pub fn input(&mut self, c: u8) -> Option<TtySignal> {
+        if self.isig && (c == INTR || c == SUSP) {
+            // 丢弃正在编辑的行
+            self.line_len = 0;
+            return Some(if c == INTR {
+                self.echo_str("^C\n");
+                TtySignal::Interrupt
+            } else {
+                self.echo_str("^Z\n");
+                TtySignal::Suspend
+            });
+        }
+        if self.mode == TtyMode::Raw {
+            if self.echo && c.is_ascii() {
+                print!("{}", c as char);
+            }
+            self.push_ready(c);
+            return None;
+        }
+        match c {
+            EOF => {
+                if self.line_len == 0 {
+                    self.eof = true;
+                } else {
+                    self.submit_line();
+                }
+            }
+            BS | DEL => {
+                if self.line_len > 0 {
+                    self.line_len -= 1;
+                    self.echo_str("\x08 \x08");
+                }
+            }
+            KILL => {
+                for _ in 0..self.line_len {
+                    self.echo_str("\x08 \x08");
+                }
+                self.line_len = 0;
+            }
+            b'\r' | b'\n' => {
+                self.push_line(b'\n');
+                self.echo_str("\n");
+                self.submit_line();
+            }
+            // 留一个位置给换行
+            _ if self.line_len + 1 < LINE_MAX => {
+                self.push_line(c);
+                if self.echo && c.is_ascii() {
+                    print!("{}", c as char);
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// 是否有可读的输入，包括文件结束。
+    #[inline]
+    pub fn readable(&self) -> bool {
+        self.len > 0 || self.eof
+    }
+
+    /// 读取等待的输入，没有可读的输入时返回 `None`。
+    ///
+    /// 规范模式下一次最多读取一行；收到空行上的 Ctrl-D 后读取返回 `Some(0)`，表示文件结束。
+    pub fn read(&mut self, buf: &mut [u8]) -> Option<usize> {
+        if self.len == 0 {
+            return core::mem::take(&mut self.eof).then_some(0);
+        }
+        let mut n = 0;
+        while n < buf.len() && self.len > 0 {
+            let c = self.ready[self.head];
+            self.head = (self.head + 1) % READY_MAX;
+            self.len -= 1;
+            buf[n] = c;
+            n += 1;
+            if self.mode == TtyMode::Canonical && c == b'\n' {
+                break;
+            }
+        }
+        Some(n)
+    }
+
+    #[inline]
+    fn echo_str(&self, s: &str) {
+        if self.echo {
+            print!("{s}");
+        }
+    }
+
+    #[inline]
+    fn push_line(&mut self, c: u8) {
+        if self.line_len < LINE_MAX {
+            self.line[self.line_len] = c;
+            self.line_len += 1;
+        }
+    }
+
+    fn submit_line(&mut self) {
+        for i in 0..self.line_len {
+            self.push_ready(self.line[i]);
+        }
+        self.line_len = 0;
+    }
+
+    #[inline]
+    fn push_ready(&mut self, c: u8) {
+        if self.len < READY_MAX {
+            self.ready[(self.head + self.len) % READY_MAX] = c;
+            self.len += 1;
+        }
+    }
+}
+
+impl Default for Tty {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
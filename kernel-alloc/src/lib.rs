@@ -4,26 +4,34 @@
 // #![deny(warnings)]
 #![deny(missing_docs)]
 
-extern crate alloc;
+mod slab;
+#[cfg(feature = "tracking")]
+mod tracking;
+
+pub use slab::{SlabBox, SlabCache, SlabStats, CACHES};
 
-use alloc::alloc::handle_alloc_error;
 use core::{
     alloc::{GlobalAlloc, Layout},
+    fmt,
+    mem::ManuallyDrop,
+    ops::{Deref, DerefMut},
     ptr::NonNull,
 };
 use customizable_buddy::{BuddyAllocator, LinkedListBuddy, UsizeBuddy};
+use riscv::register::sstatus;
+use spin::{Mutex, MutexGuard};
 
 /// 初始化内存分配。
 ///
 /// 参数 `base_address` 表示动态内存区域的起始位置。
 #[inline]
 pub fn init(base_address: usize) {
-    unsafe {
-        HEAP.init(
-            core::mem::size_of::<usize>().trailing_zeros() as _,
-            NonNull::new(base_address as *mut u8).unwrap(),
-        )
-    };
+    let mut heap = heap();
+    heap.buddy.init(
+        core::mem::size_of::<usize>().trailing_zeros() as _,
+        NonNull::new(base_address as *mut u8).unwrap(),
+    );
+    heap.base = base_address;
 }
 
 /// 将一个内存块托管到内存分配器。
@@ -35,14 +43,275 @@ pub fn init(base_address: usize) {
 #[inline]
 pub unsafe fn transfer(region: &'static mut [u8]) {
     let ptr = NonNull::new(region.as_mut_ptr()).unwrap();
-    HEAP.transfer(ptr, region.len());
+    let mut heap = heap();
+    heap.buddy.transfer(ptr, region.len());
+    heap.stats.capacity += region.len();
+}
+
+/// 将启动后才可用或才发现的内存区域加入内存分配器，返回实际加入的字节数。
+///
+/// 与 [`transfer`] 不同，区域的首尾不必对齐，会向内收缩到分配器的最小粒度；
+/// 位于初始化时传入的起始位置之前或收缩后为空的区域被忽略，返回 0。
+///
+/// # Safety
+///
+/// 与 [`transfer`] 相同，`region` 的所有权将转移到分配器。
+pub unsafe fn add_region(region: &'static mut [u8]) -> usize {
+    const MASK: usize = core::mem::size_of::<usize>() - 1;
+    let start = (region.as_ptr() as usize + MASK) & !MASK;
+    let end = (region.as_ptr() as usize + region.len()) & !MASK;
+    let mut heap = heap();
+    if start < heap.base || start >= end {
+        return 0;
+    }
+    heap.buddy
+        .transfer(NonNull::new(start as *mut u8).unwrap(), end - start);
+    heap.stats.capacity += end - start;
+    end - start
+}
+
+/// 尝试分配内存，内存不足时返回 `None` 而不是终止内核。
+///
+/// 需要在分配失败时向用户返回错误的调用者（如 `fork`、`mmap`）应该使用这个函数。
+#[inline]
+pub fn try_alloc(layout: Layout) -> Option<NonNull<u8>> {
+    heap().alloc(layout)
+}
+
+/// 回收 [`try_alloc`] 分配的内存。
+///
+/// # Safety
+///
+/// `ptr` 必须是以同样的 `layout` 调用 [`try_alloc`] 得到的。
+#[inline]
+pub unsafe fn dealloc(ptr: NonNull<u8>, layout: Layout) {
+    heap().dealloc(ptr, layout)
+}
+
+/// 查询堆的使用情况。
+#[inline]
+pub fn stats() -> HeapStats {
+    heap().stats
+}
+
+/// 查询各个 slab 缓存的使用情况，按对象从小到大排列。
+#[inline]
+pub fn slab_stats() -> [SlabStats; CACHES] {
+    let heap = heap();
+    core::array::from_fn(|i| heap.caches[i].stats())
+}
+
+/// 打印堆和各个 slab 缓存的使用情况，启用 `tracking` 特性时还打印所有未回收的分配。
+///
+/// 通常在关机前调用，用于检查内存泄漏。
+pub fn report() {
+    log::info!("{}", stats());
+    for cache in slab_stats().iter().filter(|cache| cache.slabs > 0) {
+        log::info!("{cache}");
+    }
+    // 日志不从堆上分配，持锁打印不会死锁
+    let heap = heap();
+    let mut typed = heap.typed;
+    while let Some(cache) = unsafe { typed.as_ref() } {
+        log::info!("{}: {}", cache.name, cache.cache.stats());
+        typed = cache.next;
+    }
+    #[cfg(feature = "tracking")]
+    heap.tracker.report();
+}
+
+/// 堆的使用情况。
+#[derive(Clone, Copy, Debug)]
+pub struct HeapStats {
+    /// 托管给分配器的字节数。
+    pub capacity: usize,
+    /// 已分配的字节数，按请求的大小计算。
+    pub in_use: usize,
+    /// 已分配字节数的峰值。
+    pub peak: usize,
+    /// 分配失败的次数。
+    pub failures: usize,
+    /// 各阶未回收的分配数，以阶数为下标，`2^n` 字节的块为 `n` 阶。
+    pub by_order: [usize; usize::BITS as usize],
+}
+
+impl HeapStats {
+    const ZERO: Self = Self {
+        capacity: 0,
+        in_use: 0,
+        peak: 0,
+        failures: 0,
+        by_order: [0; usize::BITS as usize],
+    };
+
+    /// 分配 `layout` 使用的块的阶数。
+    #[inline]
+    fn order(layout: Layout) -> usize {
+        layout
+            .size()
+            .max(layout.align())
+            .next_power_of_two()
+            .trailing_zeros() as _
+    }
+}
+
+impl fmt::Display for HeapStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "heap: {}/{} bytes in use, peak {}, {} failures",
+            self.in_use, self.capacity, self.peak, self.failures
+        )?;
+        for (order, count) in self.by_order.iter().enumerate() {
+            if *count > 0 {
+                write!(f, ", order {order}: {count}")?;
+            }
+        }
+        Ok(())
+    }
 }
 
 /// 堆分配器。
 ///
 /// 最大容量：6 + 21 + 3 = 30 -> 1 GiB。
-/// 不考虑并发使用，因此没有加锁。
-static mut HEAP: BuddyAllocator<21, UsizeBuddy, LinkedListBuddy> = BuddyAllocator::new();
+/// 多个硬件线程通过自旋锁互斥，持锁期间屏蔽本硬件线程的中断，中断处理中分配内存也不会死锁。
+static HEAP: Mutex<Heap> = Mutex::new(Heap {
+    buddy: BuddyAllocator::new(),
+    base: usize::MAX,
+    caches: slab::Cache::ALL,
+    typed: core::ptr::null_mut(),
+    stats: HeapStats::ZERO,
+    #[cfg(feature = "tracking")]
+    tracker: tracking::Tracker::new(),
+});
+
+type Buddy = BuddyAllocator<21, UsizeBuddy, LinkedListBuddy>;
+
+/// 伙伴分配器、小对象缓存及其统计信息。
+///
+/// 不超过 1 KiB 的对象从 slab 缓存分配，更大的对象直接从伙伴分配器分配。
+/// [`SlabCache`] 的对象从各自的缓存分配，同样计入堆的统计。
+struct Heap {
+    buddy: Buddy,
+    /// 初始化时传入的起始位置，托管的内存块都不能在它之前。
+    base: usize,
+    caches: [slab::Cache; CACHES],
+    /// 已经登记的类型缓存的链表。
+    typed: *mut slab::Typed,
+    stats: HeapStats,
+    #[cfg(feature = "tracking")]
+    tracker: tracking::Tracker,
+}
+
+/// 伙伴分配器和 slab 中的链表由裸指针构成，只在持锁时访问。
+unsafe impl Send for Heap {}
+
+impl Heap {
+    fn alloc(&mut self, layout: Layout) -> Option<NonNull<u8>> {
+        let result = match slab::class(layout) {
+            Some(class) => self.caches[class].alloc(&mut self.buddy),
+            None => self.buddy.allocate_layout(layout).ok().map(|(ptr, _)| ptr),
+        };
+        self.account_alloc(result, layout)
+    }
+
+    fn dealloc(&mut self, ptr: NonNull<u8>, layout: Layout) {
+        match slab::class(layout) {
+            Some(class) => self.caches[class].dealloc(ptr, &mut self.buddy),
+            None => self.buddy.deallocate_layout(ptr, layout),
+        }
+        self.account_dealloc(ptr, layout);
+    }
+
+    /// 从类型缓存 `typed` 分配，第一次分配时登记这个缓存。
+    fn alloc_typed(&mut self, typed: *mut slab::Typed, layout: Layout) -> Option<NonNull<u8>> {
+        let typed = unsafe { &mut *typed };
+        typed.register(&mut self.typed);
+        let result = typed.cache.alloc(&mut self.buddy);
+        self.account_alloc(result, layout)
+    }
+
+    /// 回收 [`alloc_typed`](Self::alloc_typed) 分配的对象。
+    fn dealloc_typed(&mut self, typed: *mut slab::Typed, ptr: NonNull<u8>, layout: Layout) {
+        unsafe { &mut *typed }.cache.dealloc(ptr, &mut self.buddy);
+        self.account_dealloc(ptr, layout);
+    }
+
+    /// 记录一次分配的结果。
+    fn account_alloc(
+        &mut self,
+        result: Option<NonNull<u8>>,
+        layout: Layout,
+    ) -> Option<NonNull<u8>> {
+        match result {
+            Some(ptr) => {
+                self.stats.in_use += layout.size();
+                self.stats.peak = self.stats.peak.max(self.stats.in_use);
+                self.stats.by_order[HeapStats::order(layout)] += 1;
+                #[cfg(feature = "tracking")]
+                self.tracker.insert(ptr.as_ptr() as _, layout.size());
+                Some(ptr)
+            }
+            None => {
+                self.stats.failures += 1;
+                None
+            }
+        }
+    }
+
+    /// 记录一次回收。
+    #[cfg_attr(not(feature = "tracking"), allow(unused_variables))]
+    fn account_dealloc(&mut self, ptr: NonNull<u8>, layout: Layout) {
+        self.stats.in_use -= layout.size();
+        self.stats.by_order[HeapStats::order(layout)] -= 1;
+        #[cfg(feature = "tracking")]
+        self.tracker.remove(ptr.as_ptr() as _);
+    }
+}
+
+/// 屏蔽中断并锁定堆分配器。
+#[inline]
+fn heap() -> HeapGuard {
+    let sie = sstatus::read().sie();
+    unsafe { sstatus::clear_sie() };
+    HeapGuard {
+        heap: ManuallyDrop::new(HEAP.lock()),
+        sie,
+    }
+}
+
+/// 堆分配器的锁，释放时恢复加锁前的中断状态。
+struct HeapGuard {
+    heap: ManuallyDrop<MutexGuard<'static, Heap>>,
+    sie: bool,
+}
+
+impl Deref for HeapGuard {
+    type Target = Heap;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.heap
+    }
+}
+
+impl DerefMut for HeapGuard {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.heap
+    }
+}
+
+impl Drop for HeapGuard {
+    #[inline]
+    fn drop(&mut self) {
+        // 先解锁再恢复中断，否则解锁前可能被中断打断
+        unsafe { ManuallyDrop::drop(&mut self.heap) };
+        if self.sie {
+            unsafe { sstatus::set_sie() };
+        }
+    }
+}
 
 struct Global;
 
@@ -50,17 +319,22 @@ struct Global;
 static GLOBAL: Global = Global;
 
 unsafe impl GlobalAlloc for Global {
+    /// 分配失败时返回空指针，由调用者决定是报错还是终止内核。
     #[inline]
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-        if let Ok((ptr, _)) = HEAP.allocate_layout::<u8>(layout) {
-            ptr.as_ptr()
-        } else {
-            handle_alloc_error(layout)
+        let result = heap().alloc(layout);
+        match result {
+            Some(ptr) => ptr.as_ptr(),
+            None => {
+                // 此时已经解锁，打印日志不会死锁
+                log::warn!("failed to allocate {layout:?}, {}", stats());
+                core::ptr::null_mut()
+            }
         }
     }
 
     #[inline]
     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
-        HEAP.deallocate_layout(NonNull::new(ptr).unwrap(), layout)
+        heap().dealloc(NonNull::new(ptr).unwrap(), layout)
     }
 }
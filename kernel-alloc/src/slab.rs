@@ -0,0 +1,342 @@
+//! 小对象的 slab 缓存。
+//!
+//! 从伙伴分配器整块取得 slab，再切分成等大的对象。缓存有两种：
+//!
+//! - 全局分配器按大小分级，每阶对象一个缓存，大小向上取整到同一阶的对象共用一个缓存；
+//! - [`SlabCache`] 为一种类型单独建立缓存，对象按类型的实际大小切分，统计也按类型单独记录。
+//!
+//! 小对象反复创建和销毁只在 slab 之间周转，不会打碎伙伴分配器的大块。
+
+use crate::Buddy;
+use core::{
+    alloc::Layout,
+    cell::UnsafeCell,
+    fmt,
+    marker::PhantomData,
+    mem::{align_of, size_of},
+    ops::{Deref, DerefMut},
+    ptr::{null_mut, NonNull},
+};
+
+/// slab 最少的字节数。
+const MIN_SLAB_SIZE: usize = 4096;
+/// slab 至少切分成这么多份，slab 头最多占去其中一份。
+const MIN_SLOTS: usize = 16;
+/// 最小的对象为 2^3 = 8 字节，空闲对象要存放一个指针。
+const MIN_ORDER: usize = 3;
+/// 最大的对象为 2^10 = 1 KiB，更大的对象直接从伙伴分配器分配。
+const MAX_ORDER: usize = 10;
+/// 缓存的数量。
+pub const CACHES: usize = MAX_ORDER - MIN_ORDER + 1;
+
+/// 选择分配 `layout` 的缓存，对象太大时返回 `None`。
+#[inline]
+pub(crate) fn class(layout: Layout) -> Option<usize> {
+    let order = layout
+        .size()
+        .max(layout.align())
+        .next_power_of_two()
+        .trailing_zeros() as usize;
+    let order = order.max(MIN_ORDER);
+    (order <= MAX_ORDER).then_some(order - MIN_ORDER)
+}
+
+/// 一个缓存的使用情况。
+#[derive(Clone, Copy, Default, Debug)]
+pub struct SlabStats {
+    /// 对象的字节数。
+    pub object_size: usize,
+    /// 持有的 slab 数。
+    pub slabs: usize,
+    /// 已分配的对象数。
+    pub in_use: usize,
+    /// 所有 slab 能容纳的对象数。
+    pub capacity: usize,
+}
+
+impl fmt::Display for SlabStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "slab-{}: {}/{} objects in use, {} slabs",
+            self.object_size, self.in_use, self.capacity, self.slabs
+        )
+    }
+}
+
+/// slab 头，位于 slab 开头。
+struct Slab {
+    /// 空闲对象链表，每个空闲对象开头存放下一个空闲对象的地址。
+    free: *mut usize,
+    /// 已分配的对象数。
+    in_use: usize,
+    /// 下一个有空闲对象的 slab。
+    next: *mut Slab,
+}
+
+/// 一种大小的对象的缓存。
+///
+/// 只把有空闲对象的 slab 串成链表，满的 slab 在释放对象时重新挂回链表。
+pub(crate) struct Cache {
+    /// 对象占用的字节数，是对齐的整数倍。
+    size: usize,
+    /// 对象的对齐。
+    align: usize,
+    partial: *mut Slab,
+    stats: SlabStats,
+}
+
+impl Cache {
+    /// 每阶一个缓存。
+    pub const ALL: [Self; CACHES] = [
+        Self::class(0),
+        Self::class(1),
+        Self::class(2),
+        Self::class(3),
+        Self::class(4),
+        Self::class(5),
+        Self::class(6),
+        Self::class(7),
+    ];
+
+    /// 第 `class` 阶的缓存，对象按自身大小对齐。
+    const fn class(class: usize) -> Self {
+        let size = 1 << (class + MIN_ORDER);
+        Self::new(size, size)
+    }
+
+    /// 存放 `size` 字节、按 `align` 对齐的对象的缓存。
+    ///
+    /// 空闲对象要存放一个指针，对象至少占一个指针的大小并按指针对齐。
+    const fn new(size: usize, align: usize) -> Self {
+        let align = if align > align_of::<usize>() {
+            align
+        } else {
+            align_of::<usize>()
+        };
+        let size = if size > size_of::<usize>() {
+            size
+        } else {
+            size_of::<usize>()
+        };
+        let size = (size + align - 1) & !(align - 1);
+        Self {
+            size,
+            align,
+            partial: null_mut(),
+            stats: SlabStats {
+                object_size: size,
+                slabs: 0,
+                in_use: 0,
+                capacity: 0,
+            },
+        }
+    }
+
+    #[inline]
+    pub fn stats(&self) -> SlabStats {
+        self.stats
+    }
+
+    /// 分配一个对象，缓存和伙伴分配器都耗尽时返回 `None`。
+    pub fn alloc(&mut self, buddy: &mut Buddy) -> Option<NonNull<u8>> {
+        if self.partial.is_null() {
+            self.grow(buddy)?;
+        }
+        let slab = unsafe { &mut *self.partial };
+        let obj = slab.free;
+        slab.free = unsafe { *obj } as _;
+        slab.in_use += 1;
+        // 取走最后一个空闲对象的 slab 离开链表
+        if slab.free.is_null() {
+            self.partial = slab.next;
+            slab.next = null_mut();
+        }
+        self.stats.in_use += 1;
+        NonNull::new(obj.cast())
+    }
+
+    /// 回收一个对象。
+    ///
+    /// 空的 slab 归还伙伴分配器，但保留最后一个，以免对象反复创建销毁时反复申请 slab。
+    pub fn dealloc(&mut self, ptr: NonNull<u8>, buddy: &mut Buddy) {
+        let slab_ptr = (ptr.as_ptr() as usize & !(self.slab_size() - 1)) as *mut Slab;
+        let slab = unsafe { &mut *slab_ptr };
+        // 满的 slab 不在链表上，重新挂回
+        if slab.free.is_null() {
+            slab.next = self.partial;
+            self.partial = slab_ptr;
+        }
+        let obj = ptr.as_ptr().cast::<usize>();
+        unsafe { *obj = slab.free as _ };
+        slab.free = obj;
+        slab.in_use -= 1;
+        self.stats.in_use -= 1;
+        if slab.in_use == 0 && !(self.partial == slab_ptr && slab.next.is_null()) {
+            self.unlink(slab_ptr);
+            buddy.deallocate_layout(NonNull::new(slab_ptr).unwrap(), self.slab_layout());
+            self.stats.slabs -= 1;
+            self.stats.capacity -= self.objects_per_slab();
+        }
+    }
+
+    /// 从伙伴分配器取得一个 slab，切分后挂到链表上。
+    fn grow(&mut self, buddy: &mut Buddy) -> Option<()> {
+        let (ptr, _) = buddy.allocate_layout::<Slab>(self.slab_layout()).ok()?;
+        let base = ptr.as_ptr() as usize;
+        let mut free = null_mut::<usize>();
+        // 对象的大小不一定整除 slab 剩下的空间，末尾放不下一个对象的部分不用
+        for i in (0..self.objects_per_slab()).rev() {
+            let obj = (base + self.first_object() + i * self.size) as *mut usize;
+            unsafe { *obj = free as _ };
+            free = obj;
+        }
+        unsafe {
+            ptr.as_ptr().write(Slab {
+                free,
+                in_use: 0,
+                next: self.partial,
+            })
+        };
+        self.partial = ptr.as_ptr();
+        self.stats.slabs += 1;
+        self.stats.capacity += self.objects_per_slab();
+        Some(())
+    }
+
+    /// 从链表上摘下 `target`。
+    fn unlink(&mut self, target: *mut Slab) {
+        let mut cur = &mut self.partial;
+        while *cur != target {
+            cur = unsafe { &mut (**cur).next };
+        }
+        *cur = unsafe { (*target).next };
+    }
+
+    /// slab 的字节数，也是 slab 的对齐。
+    ///
+    /// 大对象的 slab 相应加大，否则 1 KiB 的对象在 4 KiB 的 slab 里要为 slab 头让出一个位置，只能放下 3 个。
+    #[inline]
+    fn slab_size(&self) -> usize {
+        MIN_SLAB_SIZE.max((MIN_SLOTS * self.size).next_power_of_two())
+    }
+
+    #[inline]
+    fn slab_layout(&self) -> Layout {
+        let size = self.slab_size();
+        unsafe { Layout::from_size_align_unchecked(size, size) }
+    }
+
+    /// 第一个对象在 slab 中的偏移，对象按要求对齐，放在 slab 头之后。
+    #[inline]
+    fn first_object(&self) -> usize {
+        let mask = self.align - 1;
+        (size_of::<Slab>() + mask) & !mask
+    }
+
+    #[inline]
+    fn objects_per_slab(&self) -> usize {
+        (self.slab_size() - self.first_object()) / self.size
+    }
+}
+
+/// 一种类型的对象缓存。
+///
+/// 缓存应放在静态变量中，第一次分配时登记到堆上，[`report`](crate::report) 按名字打印它的统计。
+/// 进程、线程这类反复创建销毁、大小又不是 2 的幂的对象适合单独建立缓存，不必向上取整到下一阶。
+///
+/// ```ignore
+/// static PROCESSES: SlabCache<Process> = SlabCache::new("process");
+///
+/// let process: SlabBox<Process> = PROCESSES.alloc(process).expect("out of memory");
+/// ```
+pub struct SlabCache<T> {
+    typed: UnsafeCell<Typed>,
+    _phantom: PhantomData<T>,
+}
+
+/// 缓存的状态只在持有堆的锁时访问。
+unsafe impl<T> Sync for SlabCache<T> {}
+
+impl<T> SlabCache<T> {
+    /// 名为 `name` 的空缓存。
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            typed: UnsafeCell::new(Typed {
+                name,
+                cache: Cache::new(size_of::<T>(), align_of::<T>()),
+                next: null_mut(),
+                registered: false,
+            }),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// 把 `value` 移进缓存分配的对象，缓存和伙伴分配器都耗尽时返回 `None`。
+    pub fn alloc(&'static self, value: T) -> Option<SlabBox<T>> {
+        // 先解锁再写入，不在持锁时运行任何用户代码
+        let ptr = crate::heap()
+            .alloc_typed(self.typed.get(), Layout::new::<T>())?
+            .cast::<T>();
+        unsafe { ptr.as_ptr().write(value) };
+        Some(SlabBox { ptr, cache: self })
+    }
+
+    /// 查询缓存的使用情况。
+    pub fn stats(&self) -> SlabStats {
+        let _heap = crate::heap();
+        unsafe { (*self.typed.get()).cache.stats() }
+    }
+}
+
+/// 类型缓存中不依赖类型的部分，登记的缓存串成链表。
+pub(crate) struct Typed {
+    pub name: &'static str,
+    pub cache: Cache,
+    pub next: *mut Typed,
+    registered: bool,
+}
+
+impl Typed {
+    /// 第一次调用时把缓存挂到链表 `head` 上。
+    pub fn register(&mut self, head: &mut *mut Typed) {
+        if !self.registered {
+            self.next = *head;
+            *head = self;
+            self.registered = true;
+        }
+    }
+}
+
+/// 从 [`SlabCache`] 分配的对象，释放时归还所属的缓存。
+pub struct SlabBox<T: 'static> {
+    ptr: NonNull<T>,
+    cache: &'static SlabCache<T>,
+}
+
+unsafe impl<T: Send> Send for SlabBox<T> {}
+unsafe impl<T: Sync> Sync for SlabBox<T> {}
+
+impl<T> Deref for SlabBox<T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<T> DerefMut for SlabBox<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { self.ptr.as_mut() }
+    }
+}
+
+impl<T> Drop for SlabBox<T> {
+    fn drop(&mut self) {
+        // 对象的析构可能释放其他内存，要在加锁之前完成
+        unsafe { self.ptr.as_ptr().drop_in_place() };
+        crate::heap().dealloc_typed(self.cache.typed.get(), self.ptr.cast(), Layout::new::<T>());
+    }
+}
@@ -0,0 +1,53 @@
+//! 跟踪未回收的分配。
+
+/// 最多跟踪的分配数，超出的部分只计数。
+const CAPACITY: usize = 1024;
+
+/// 未回收的分配表。
+///
+/// 表本身不能从堆上分配，因此使用定长数组。
+pub(crate) struct Tracker {
+    /// 每项是分配的地址和大小。
+    live: [(usize, usize); CAPACITY],
+    len: usize,
+    /// 表满后未能记录的分配数。
+    untracked: usize,
+}
+
+impl Tracker {
+    pub const fn new() -> Self {
+        Self {
+            live: [(0, 0); CAPACITY],
+            len: 0,
+            untracked: 0,
+        }
+    }
+
+    pub fn insert(&mut self, addr: usize, size: usize) {
+        if self.len < CAPACITY {
+            self.live[self.len] = (addr, size);
+            self.len += 1;
+        } else {
+            self.untracked += 1;
+        }
+    }
+
+    pub fn remove(&mut self, addr: usize) {
+        match self.live[..self.len].iter().position(|(a, _)| *a == addr) {
+            Some(i) => {
+                self.len -= 1;
+                self.live[i] = self.live[self.len];
+            }
+            None => self.untracked = self.untracked.saturating_sub(1),
+        }
+    }
+
+    pub fn report(&self) {
+        for (addr, size) in &self.live[..self.len] {
+            log::warn!("live allocation: {addr:#x}, {size} bytes");
+        }
+        if self.untracked > 0 {
+            log::warn!("{} live allocations untracked", self.untracked);
+        }
+    }
+}
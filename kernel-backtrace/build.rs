@@ -0,0 +1,22 @@
+fn main() {
+    use std::{env, fs, path::PathBuf};
+
+    // xtask 通过环境变量传入符号表，没有时生成空表
+    const EMPTY: &str = "\
+    .section .ksymbols, \"a\"
+    .align 3
+    .global __ksymbols
+__ksymbols:
+    .quad 0
+";
+    let out = PathBuf::from(env::var_os("OUT_DIR").unwrap()).join("symbols.asm");
+    match env::var_os("KERNEL_SYMBOLS") {
+        Some(path) => {
+            println!("cargo:rerun-if-changed={}", PathBuf::from(&path).display());
+            fs::copy(path, out).unwrap();
+        }
+        None => fs::write(out, EMPTY).unwrap(),
+    };
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=KERNEL_SYMBOLS");
+}
@@ -0,0 +1,151 @@
+//! 沿帧指针回溯调用栈，并用链接进内核的符号表解析函数名。
+//!
+//! 在 panic 处理函数中打印内核调用栈：
+//!
+//! ```ignore
+//! println!("{}", kernel_backtrace::Backtrace::capture());
+//! ```
+//!
+//! 用户程序因异常被杀死时，从用户上下文的 `sepc` 和 `s0` 开始，通过用户地址空间读取用户栈：
+//!
+//! ```ignore
+//! let backtrace = Backtrace::user(sepc, fp, |addr| read_user(addr));
+//! ```
+//!
+//! RISC-V 开启帧指针时，`s0` 指向当前栈帧的顶部，返回地址保存在 `s0 - 8`，调用者的 `s0` 保存在 `s0 - 16`。
+
+#![no_std]
+#![deny(warnings, missing_docs)]
+
+use core::fmt;
+
+// 符号表由 build.rs 生成，放在 `.ksymbols` 段
+#[cfg(target_arch = "riscv64")]
+core::arch::global_asm!(include_str!(concat!(env!("OUT_DIR"), "/symbols.asm")));
+
+/// 最多回溯的栈帧数。
+pub const MAX_DEPTH: usize = 32;
+
+/// 一次回溯的结果。
+pub struct Backtrace {
+    frames: [usize; MAX_DEPTH],
+    len: usize,
+    /// 内核调用栈才用内核符号表解析。
+    kernel: bool,
+}
+
+impl Backtrace {
+    /// 回溯当前的内核调用栈，第一帧是调用者。
+    #[cfg(target_arch = "riscv64")]
+    #[inline(never)]
+    pub fn capture() -> Self {
+        let fp: usize;
+        unsafe { core::arch::asm!("mv {}, s0", out(reg) fp) };
+        let mut ans = Self::new(true);
+        // 内核栈总是可以直接访问
+        ans.walk(fp, |addr| Some(unsafe { *(addr as *const usize) }));
+        ans
+    }
+
+    /// 回溯用户调用栈。
+    ///
+    /// `pc` 和 `fp` 是用户上下文中的 `sepc` 和 `s0`，`read` 读取一个用户地址处的 `usize`，地址不可读时返回 `None`。
+    pub fn user(pc: usize, fp: usize, read: impl Fn(usize) -> Option<usize>) -> Self {
+        let mut ans = Self::new(false);
+        ans.push(pc);
+        ans.walk(fp, read);
+        ans
+    }
+
+    /// 回溯得到的返回地址。
+    #[inline]
+    pub fn frames(&self) -> &[usize] {
+        &self.frames[..self.len]
+    }
+
+    #[inline]
+    const fn new(kernel: bool) -> Self {
+        Self {
+            frames: [0; MAX_DEPTH],
+            len: 0,
+            kernel,
+        }
+    }
+
+    #[inline]
+    fn push(&mut self, pc: usize) -> bool {
+        if self.len < MAX_DEPTH {
+            self.frames[self.len] = pc;
+            self.len += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// 沿帧指针链回溯，帧指针为 0、未对齐、不再向栈底增长或无法读取时停止。
+    fn walk(&mut self, mut fp: usize, read: impl Fn(usize) -> Option<usize>) {
+        const ALIGN: usize = core::mem::size_of::<usize>() - 1;
+        while fp != 0 && fp & ALIGN == 0 {
+            let Some(ra) = read(fp - 8) else { break };
+            let Some(next) = read(fp - 16) else { break };
+            if ra == 0 || !self.push(ra) || next <= fp {
+                break;
+            }
+            fp = next;
+        }
+    }
+}
+
+impl fmt::Display for Backtrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "backtrace:")?;
+        for (i, pc) in self.frames().iter().enumerate() {
+            write!(f, "\n  #{i:<2} {pc:#018x}")?;
+            if self.kernel {
+                if let Some((name, offset)) = symbolize(*pc) {
+                    write!(f, " {name}+{offset:#x}")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 找到包含 `pc` 的内核函数，返回函数名和 `pc` 在函数中的偏移。
+///
+/// 内核没有链接符号表时总是返回 `None`。
+pub fn symbolize(pc: usize) -> Option<(&'static str, usize)> {
+    let symbols = symbols();
+    // 符号按地址升序排列，找最后一个不超过 `pc` 的
+    let i = symbols.partition_point(|s| s.addr as usize <= pc);
+    let symbol = symbols.get(i.checked_sub(1)?)?;
+    let name = unsafe { core::ffi::CStr::from_ptr(symbol.name as _) };
+    Some((name.to_str().ok()?, pc - symbol.addr as usize))
+}
+
+/// 符号表项。
+#[repr(C)]
+struct Symbol {
+    addr: u64,
+    /// 以 0 结尾的函数名。
+    name: u64,
+}
+
+/// 符号表，格式为表项数和按地址升序排列的表项。
+#[cfg(target_arch = "riscv64")]
+fn symbols() -> &'static [Symbol] {
+    extern "C" {
+        static __ksymbols: u64;
+    }
+    unsafe {
+        let count = __ksymbols as usize;
+        let first = (&__ksymbols as *const u64).add(1).cast::<Symbol>();
+        core::slice::from_raw_parts(first, count)
+    }
+}
+
+#[cfg(not(target_arch = "riscv64"))]
+fn symbols() -> &'static [Symbol] {
+    &[]
+}
@@ -0,0 +1,13 @@
+//! QEMU virt 平台的设备驱动。
+//!
+//! - [`Ns16550`]：16550 兼容串口，实现了 [`rcore_console::Console`]；
+//! - [`Plic`]：平台级中断控制器。
+
+#![no_std]
+#![deny(warnings, missing_docs)]
+
+mod ns16550;
+mod plic;
+
+pub use ns16550::Ns16550;
+pub use plic::Plic;
@@ -0,0 +1,131 @@
+use core::ptr::{read_volatile, write_volatile};
+
+/// 接收缓冲寄存器（读）和发送保持寄存器（写）。
+const RBR_THR: usize = 0;
+/// 中断使能寄存器。
+const IER: usize = 1;
+/// FIFO 控制寄存器（写）。
+const FCR: usize = 2;
+/// 线路控制寄存器。
+const LCR: usize = 3;
+/// 调制解调器控制寄存器。
+const MCR: usize = 4;
+/// 线路状态寄存器。
+const LSR: usize = 5;
+
+/// 接收数据可用中断。
+const IER_RX_AVAILABLE: u8 = 1 << 0;
+/// 开启 FIFO 并清空收发 FIFO。
+const FCR_ENABLE_CLEAR: u8 = 0b111;
+/// 访问波特率除数寄存器。
+const LCR_DLAB: u8 = 1 << 7;
+/// 8 位数据、无校验、1 位停止位。
+const LCR_8N1: u8 = 0b11;
+/// 数据终端就绪、请求发送，以及打开中断输出的 OUT2。
+const MCR_DTR_RTS_OUT2: u8 = 0b1011;
+/// 接收 FIFO 中有数据。
+const LSR_DATA_READY: u8 = 1 << 0;
+/// 发送 FIFO 已空。
+const LSR_THR_EMPTY: u8 = 1 << 5;
+
+/// 发送 FIFO 的深度。
+const FIFO_DEPTH: usize = 16;
+
+/// 16550 兼容串口。
+pub struct Ns16550 {
+    base: usize,
+}
+
+impl Ns16550 {
+    /// 寄存器位于 `base` 的串口。
+    ///
+    /// # Safety
+    ///
+    /// `base` 必须是已映射的 16550 寄存器的虚地址，且同一个串口只有一个对象。
+    #[inline]
+    pub const unsafe fn new(base: usize) -> Self {
+        Self { base }
+    }
+
+    /// 初始化串口：8N1，开启并清空收发 FIFO，关闭所有中断。
+    pub fn init(&self) {
+        self.write_reg(IER, 0);
+        // 除数 3 对应 1.8432 MHz 时钟下的 38400 波特率，QEMU 不关心
+        self.write_reg(LCR, LCR_DLAB);
+        self.write_reg(0, 3);
+        self.write_reg(1, 0);
+        self.write_reg(LCR, LCR_8N1);
+        self.write_reg(FCR, FCR_ENABLE_CLEAR);
+        self.write_reg(MCR, MCR_DTR_RTS_OUT2);
+    }
+
+    /// 打开或关闭接收中断。
+    #[inline]
+    pub fn set_rx_interrupt(&self, enable: bool) {
+        self.write_reg(IER, if enable { IER_RX_AVAILABLE } else { 0 });
+    }
+
+    /// 发送一个字节，发送 FIFO 满时等待。
+    #[inline]
+    pub fn write_byte(&self, c: u8) {
+        self.write_bytes(&[c]);
+    }
+
+    /// 发送一串字节，每次等待发送 FIFO 清空后写入一批。
+    pub fn write_bytes(&self, bytes: &[u8]) {
+        for chunk in bytes.chunks(FIFO_DEPTH) {
+            while self.read_reg(LSR) & LSR_THR_EMPTY == 0 {
+                core::hint::spin_loop();
+            }
+            for &c in chunk {
+                self.write_reg(RBR_THR, c);
+            }
+        }
+    }
+
+    /// 从接收 FIFO 取出一个字节，没有数据时返回 `None`。
+    #[inline]
+    pub fn read_byte(&self) -> Option<u8> {
+        if self.read_reg(LSR) & LSR_DATA_READY != 0 {
+            Some(self.read_reg(RBR_THR))
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    fn read_reg(&self, reg: usize) -> u8 {
+        unsafe { read_volatile((self.base + reg) as *const u8) }
+    }
+
+    #[inline]
+    fn write_reg(&self, reg: usize, val: u8) {
+        unsafe { write_volatile((self.base + reg) as *mut u8, val) }
+    }
+}
+
+/// 串口不转换换行，输出 `\n` 时补上 `\r`。
+impl rcore_console::Console for Ns16550 {
+    #[inline]
+    fn put_char(&self, c: u8) {
+        if c == b'\n' {
+            self.write_bytes(b"\r\n");
+        } else {
+            self.write_byte(c);
+        }
+    }
+
+    fn put_str(&self, s: &str) {
+        for (i, line) in s.split('\n').enumerate() {
+            if i > 0 {
+                self.write_bytes(b"\r\n");
+            }
+            self.write_bytes(line.as_bytes());
+        }
+    }
+
+    #[inline]
+    fn get_char(&self) -> Option<u8> {
+        self.read_byte()
+    }
+}
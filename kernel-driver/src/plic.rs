@@ -0,0 +1,89 @@
+use core::ptr::{read_volatile, write_volatile};
+
+/// 中断源优先级寄存器，每个中断源 4 字节。
+const PRIORITY: usize = 0;
+/// 中断使能位，每个上下文 0x80 字节。
+const ENABLE: usize = 0x2000;
+const ENABLE_STRIDE: usize = 0x80;
+/// 优先级阈值和领取/完成寄存器，每个上下文 0x1000 字节。
+const CONTEXT: usize = 0x20_0000;
+const CONTEXT_STRIDE: usize = 0x1000;
+const THRESHOLD: usize = 0;
+const CLAIM_COMPLETE: usize = 4;
+
+/// 平台级中断控制器。
+///
+/// 中断源优先级大于上下文的阈值才会送到上下文，优先级 0 表示不中断。
+pub struct Plic {
+    base: usize,
+}
+
+impl Plic {
+    /// 寄存器位于 `base` 的中断控制器。
+    ///
+    /// # Safety
+    ///
+    /// `base` 必须是已映射的 PLIC 寄存器的虚地址。
+    #[inline]
+    pub const unsafe fn new(base: usize) -> Self {
+        Self { base }
+    }
+
+    /// QEMU virt 上硬件线程 `hart` 的 S 态上下文号。
+    #[inline]
+    pub const fn supervisor_context(hart: usize) -> usize {
+        2 * hart + 1
+    }
+
+    /// 设置中断源的优先级。
+    #[inline]
+    pub fn set_priority(&self, irq: usize, priority: u32) {
+        self.write(PRIORITY + irq * 4, priority);
+    }
+
+    /// 设置上下文的优先级阈值。
+    #[inline]
+    pub fn set_threshold(&self, context: usize, threshold: u32) {
+        self.write(CONTEXT + context * CONTEXT_STRIDE + THRESHOLD, threshold);
+    }
+
+    /// 允许中断源向上下文发出中断。
+    pub fn enable(&self, context: usize, irq: usize) {
+        let offset = ENABLE + context * ENABLE_STRIDE + irq / 32 * 4;
+        self.write(offset, self.read(offset) | 1 << (irq % 32));
+    }
+
+    /// 禁止中断源向上下文发出中断。
+    pub fn disable(&self, context: usize, irq: usize) {
+        let offset = ENABLE + context * ENABLE_STRIDE + irq / 32 * 4;
+        self.write(offset, self.read(offset) & !(1 << (irq % 32)));
+    }
+
+    /// 领取上下文优先级最高的待处理中断，没有时返回 `None`。
+    #[inline]
+    pub fn claim(&self, context: usize) -> Option<usize> {
+        match self.read(CONTEXT + context * CONTEXT_STRIDE + CLAIM_COMPLETE) {
+            0 => None,
+            irq => Some(irq as _),
+        }
+    }
+
+    /// 通知中断源已经处理完领取的中断。
+    #[inline]
+    pub fn complete(&self, context: usize, irq: usize) {
+        self.write(
+            CONTEXT + context * CONTEXT_STRIDE + CLAIM_COMPLETE,
+            irq as _,
+        );
+    }
+
+    #[inline]
+    fn read(&self, offset: usize) -> u32 {
+        unsafe { read_volatile((self.base + offset) as *const u32) }
+    }
+
+    #[inline]
+    fn write(&self, offset: usize, val: u32) {
+        unsafe { write_volatile((self.base + offset) as *mut u32, val) }
+    }
+}
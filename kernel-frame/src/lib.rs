@@ -0,0 +1,247 @@
+//! 物理页帧管理。
+
+#![no_std]
+// #![deny(warnings)]
+#![deny(missing_docs)]
+
+use core::{alloc::Layout, fmt, ptr::NonNull};
+use customizable_buddy::{BuddyAllocator, LinkedListBuddy, UsizeBuddy};
+use spin::Mutex;
+
+/// 页帧的用途。
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Owner {
+    /// 页表。
+    PageTable,
+    /// 用户程序的代码和数据。
+    User,
+    /// 栈。
+    Stack,
+    /// 设备 DMA 缓冲区。
+    Dma,
+}
+
+impl Owner {
+    /// 所有的用途，顺序与 [`FrameStats::by_owner`] 的下标一致。
+    pub const ALL: [Self; 4] = [Self::PageTable, Self::User, Self::Stack, Self::Dma];
+}
+
+/// 页帧分配统计。
+#[derive(Clone, Copy, Default, Debug)]
+pub struct FrameStats {
+    /// 可分配的页帧总数。
+    pub total: usize,
+    /// 已分配的页帧数。
+    pub allocated: usize,
+    /// 已分配页帧数的峰值。
+    pub peak: usize,
+    /// 各种用途的页帧数，以 [`Owner`] 为下标。
+    pub by_owner: [usize; Owner::ALL.len()],
+}
+
+impl FrameStats {
+    /// 空闲的页帧数。
+    #[inline]
+    pub const fn free(&self) -> usize {
+        self.total - self.allocated
+    }
+}
+
+impl fmt::Display for FrameStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "frames: {}/{} allocated, peak {}",
+            self.allocated, self.total, self.peak
+        )?;
+        for (owner, count) in Owner::ALL.iter().zip(self.by_owner) {
+            write!(f, ", {owner:?} {count}")?;
+        }
+        Ok(())
+    }
+}
+
+/// 初始化页帧分配器，托管 `region` 中的物理页帧。
+///
+/// `region` 开头的若干页用于存放每个页帧的元数据。
+///
+/// # Safety
+///
+/// `region` 内存块的所有权将转移到页帧分配器，因此需要调用者确保这个内存块未被其他对象引用，且内核可以直接访问。
+pub unsafe fn init(page_bits: usize, region: &'static mut [u8]) {
+    FRAMES.lock().init(page_bits, region);
+}
+
+/// 分配 `count` 个连续的页帧并清零，每个页帧的引用计数为 1。
+///
+/// 页帧不足时返回 `None`。
+#[inline]
+pub fn alloc(count: usize, owner: Owner) -> Option<NonNull<u8>> {
+    FRAMES.lock().alloc(count, owner)
+}
+
+/// 增加从 `ptr` 开始的 `count` 个页帧的引用计数，用于共享页帧。
+#[inline]
+pub fn share(ptr: NonNull<u8>, count: usize) {
+    FRAMES.lock().share(ptr, count)
+}
+
+/// 减少从 `ptr` 开始的 `count` 个页帧的引用计数，回收引用计数减到 0 的页帧。
+///
+/// 返回回收的页帧数。
+#[inline]
+pub fn release(ptr: NonNull<u8>, count: usize) -> usize {
+    FRAMES.lock().release(ptr, count)
+}
+
+/// 查询页帧的引用计数，空闲的页帧为 0。
+#[inline]
+pub fn refcount(ptr: NonNull<u8>) -> usize {
+    FRAMES.lock().meta_mut(ptr.as_ptr() as _).refcount
+}
+
+/// 查询页帧的用途，空闲的页帧为 `None`。
+#[inline]
+pub fn owner(ptr: NonNull<u8>) -> Option<Owner> {
+    FRAMES.lock().meta_mut(ptr.as_ptr() as _).owner
+}
+
+/// 查询分配统计。
+#[inline]
+pub fn stats() -> FrameStats {
+    FRAMES.lock().stats
+}
+
+/// 页帧元数据。
+#[derive(Clone, Copy)]
+struct FrameMeta {
+    refcount: usize,
+    owner: Option<Owner>,
+}
+
+impl FrameMeta {
+    const FREE: Self = Self {
+        refcount: 0,
+        owner: None,
+    };
+}
+
+/// 页帧分配器。
+///
+/// 最大容量：6 + 16 + 12 = 34 -> 16 GiB。
+struct Frames {
+    page_bits: usize,
+    buddy: BuddyAllocator<16, UsizeBuddy, LinkedListBuddy>,
+    /// 第一个页帧的地址。
+    base: usize,
+    /// 元数据表，每个页帧一项。
+    meta: *mut FrameMeta,
+    len: usize,
+    stats: FrameStats,
+}
+
+/// 元数据表只在持有锁时访问。
+unsafe impl Send for Frames {}
+
+static FRAMES: Mutex<Frames> = Mutex::new(Frames {
+    page_bits: 0,
+    buddy: BuddyAllocator::new(),
+    base: 0,
+    meta: core::ptr::null_mut(),
+    len: 0,
+    stats: FrameStats {
+        total: 0,
+        allocated: 0,
+        peak: 0,
+        by_owner: [0; Owner::ALL.len()],
+    },
+});
+
+impl Frames {
+    unsafe fn init(&mut self, page_bits: usize, region: &'static mut [u8]) {
+        let mask = (1 << page_bits) - 1;
+        let start = (region.as_ptr() as usize + mask) & !mask;
+        let end = (region.as_ptr() as usize + region.len()) & !mask;
+        let len = (end - start) >> page_bits;
+        // 元数据表放在开头，占据的页帧不再托管
+        let meta = start as *mut FrameMeta;
+        for i in 0..len {
+            meta.add(i).write(FrameMeta::FREE);
+        }
+        let managed = (start + len * core::mem::size_of::<FrameMeta>() + mask) & !mask;
+
+        self.page_bits = page_bits;
+        self.base = start;
+        self.meta = meta;
+        self.len = len;
+        self.stats.total = (end - managed) >> page_bits;
+        let ptr = NonNull::new(managed as *mut u8).unwrap();
+        self.buddy.init(page_bits as _, ptr);
+        self.buddy.transfer(ptr, end - managed);
+    }
+
+    fn alloc(&mut self, count: usize, owner: Owner) -> Option<NonNull<u8>> {
+        if count == 0 {
+            None?
+        }
+        let page = self.page_layout();
+        let layout = Layout::from_size_align(count << self.page_bits, page.align()).ok()?;
+        let (ptr, size) = self.buddy.allocate_layout::<u8>(layout).ok()?;
+        // 伙伴分配器按 2 的幂分配，多出的页帧立即归还
+        for i in count..size >> self.page_bits {
+            let tail = unsafe { NonNull::new_unchecked(ptr.as_ptr().add(i << self.page_bits)) };
+            self.buddy.deallocate_layout(tail, page);
+        }
+        unsafe { ptr.as_ptr().write_bytes(0, layout.size()) };
+        for i in 0..count {
+            let meta = self.meta_mut(ptr.as_ptr() as usize + (i << self.page_bits));
+            meta.refcount = 1;
+            meta.owner = Some(owner);
+        }
+        self.stats.allocated += count;
+        self.stats.peak = self.stats.peak.max(self.stats.allocated);
+        self.stats.by_owner[owner as usize] += count;
+        Some(ptr)
+    }
+
+    fn share(&mut self, ptr: NonNull<u8>, count: usize) {
+        for i in 0..count {
+            let addr = ptr.as_ptr() as usize + (i << self.page_bits);
+            let meta = self.meta_mut(addr);
+            assert!(meta.refcount > 0, "sharing a free frame at {addr:#x}");
+            meta.refcount += 1;
+        }
+    }
+
+    fn release(&mut self, ptr: NonNull<u8>, count: usize) -> usize {
+        let page = self.page_layout();
+        let mut freed = 0;
+        for i in 0..count {
+            let addr = ptr.as_ptr() as usize + (i << self.page_bits);
+            let meta = self.meta_mut(addr);
+            assert!(meta.refcount > 0, "releasing a free frame at {addr:#x}");
+            meta.refcount -= 1;
+            if meta.refcount == 0 {
+                let owner = meta.owner.take().unwrap();
+                self.stats.by_owner[owner as usize] -= 1;
+                self.buddy
+                    .deallocate_layout(unsafe { NonNull::new_unchecked(addr as _) }, page);
+                freed += 1;
+            }
+        }
+        self.stats.allocated -= freed;
+        freed
+    }
+
+    #[inline]
+    fn page_layout(&self) -> Layout {
+        unsafe { Layout::from_size_align_unchecked(1 << self.page_bits, 1 << self.page_bits) }
+    }
+
+    #[inline]
+    fn meta_mut(&mut self, addr: usize) -> &mut FrameMeta {
+        let index = addr.wrapping_sub(self.base) >> self.page_bits;
+        assert!(index < self.len, "{addr:#x} is not a managed frame");
+        unsafe { &mut *self.meta.add(index) }
+    }
+}
@@ -0,0 +1,392 @@
+//! 进程的 `mmap` 映射。
+//!
+//! 映射建立时不分配物理页，访问触发缺页时才装入；共享的文件映射在解除映射和同步时把脏页写回文件。
+//! 映射的页不记录在地址空间的 `areas` 中，由 [`Mappings`] 持有页帧的引用。
+
+#![no_std]
+#![deny(warnings, missing_docs)]
+
+extern crate alloc;
+
+use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
+use core::{ops::Range, ptr::NonNull, str::FromStr};
+use easy_fs::Inode;
+use kernel_frame::Owner;
+use kernel_vm::{
+    page_table::{VAddr, VmFlags, VmMeta, PPN, VPN},
+    AddressSpace, PageManager, VmLayout,
+};
+use syscall::{
+    EINVAL, ENOMEM, MAP_FIXED, MAP_PRIVATE, MAP_SHARED, PROT_EXEC, PROT_READ, PROT_WRITE,
+};
+
+/// 页的字节数。
+#[inline]
+const fn page_size<Meta: VmMeta>() -> usize {
+    1 << Meta::PAGE_BITS
+}
+
+/// 页内偏移的掩码。
+#[inline]
+const fn page_mask<Meta: VmMeta>() -> usize {
+    page_size::<Meta>() - 1
+}
+
+/// 一个物理页的引用，复制时增加页帧的引用计数，最后一个引用释放时回收。
+struct Frame(NonNull<u8>);
+
+impl Frame {
+    /// 分配一个清零的页，页帧耗尽时返回 `None`。
+    fn new() -> Option<Self> {
+        kernel_frame::alloc(1, Owner::User).map(Self)
+    }
+
+    #[inline]
+    fn ppn<Meta: VmMeta>(&self) -> PPN<Meta> {
+        PPN::new(linker::virt_to_phys(self.0.as_ptr() as usize) >> Meta::PAGE_BITS)
+    }
+
+    #[inline]
+    fn as_mut_ptr(&self) -> *mut u8 {
+        self.0.as_ptr()
+    }
+}
+
+impl Clone for Frame {
+    fn clone(&self) -> Self {
+        kernel_frame::share(self.0, 1);
+        Self(self.0)
+    }
+}
+
+impl Drop for Frame {
+    fn drop(&mut self) {
+        kernel_frame::release(self.0, 1);
+    }
+}
+
+/// 一段 `mmap` 映射。
+///
+/// 映射建立时不分配物理页，访问触发缺页时才从文件装入。
+struct Mapping<Meta: VmMeta> {
+    range: Range<VPN<Meta>>,
+    flags: VmFlags<Meta>,
+    /// 映射的文件及 `range.start` 对应的文件偏移，匿名映射为 `None`。
+    file: Option<(Arc<Inode>, usize)>,
+    /// 修改对其他映射者可见，并写回文件。
+    shared: bool,
+    /// 已装入的页，以虚页号索引。
+    frames: BTreeMap<usize, Frame>,
+}
+
+impl<Meta: VmMeta> Mapping<Meta> {
+    /// 映射是否可以访问；`PROT_NONE` 的映射只占据地址范围，从不装入物理页。
+    #[inline]
+    fn accessible(&self) -> bool {
+        self.flags.contains(VmFlags::build_from_str("RV"))
+            || self.flags.contains(VmFlags::build_from_str("X__V"))
+    }
+
+    #[inline]
+    fn contains(&self, vpn: usize) -> bool {
+        (self.range.start.val()..self.range.end.val()).contains(&vpn)
+    }
+
+    /// `vpn` 对应的文件偏移。
+    #[inline]
+    fn file_offset(&self, vpn: usize) -> usize {
+        self.file.as_ref().unwrap().1 + ((vpn - self.range.start.val()) << Meta::PAGE_BITS)
+    }
+
+    /// 装入 `vpn` 所在页并映射到地址空间，页帧耗尽时返回 `false`。
+    fn load<M: PageManager<Meta>>(
+        &mut self,
+        space: &mut AddressSpace<Meta, M>,
+        vpn: usize,
+    ) -> bool {
+        let Some(frame) = Frame::new() else {
+            return false;
+        };
+        if let Some((inode, _)) = &self.file {
+            let page =
+                unsafe { core::slice::from_raw_parts_mut(frame.as_mut_ptr(), page_size::<Meta>()) };
+            inode.read_at(self.file_offset(vpn), page);
+        }
+        let vpn_ = VPN::new(vpn);
+        space.map_shared(vpn_..vpn_ + 1, frame.ppn(), self.flags);
+        self.frames.insert(vpn, frame);
+        true
+    }
+
+    /// 把脏页写回文件，只对共享的文件映射有效。
+    ///
+    /// 写回不超过文件原有的长度。
+    fn sync<M: PageManager<Meta>>(&self, space: &AddressSpace<Meta, M>) {
+        let dirty = VmFlags::build_from_str("D______V");
+        let inode = match &self.file {
+            Some((inode, _)) if self.shared => inode,
+            _ => return,
+        };
+        let size = inode.size();
+        for (&vpn, frame) in &self.frames {
+            let offset = self.file_offset(vpn);
+            if offset >= size
+                || space
+                    .translate::<u8>(VPN::<Meta>::new(vpn).base(), dirty)
+                    .is_none()
+            {
+                continue;
+            }
+            let len = (size - offset).min(page_size::<Meta>());
+            inode.write_at(offset, unsafe {
+                core::slice::from_raw_parts(frame.as_mut_ptr(), len)
+            });
+        }
+    }
+
+    /// 复制映射需要的页帧数：共享映射要装入所有未装入的页，私有映射要复制所有已装入的页。
+    #[inline]
+    fn fork_cost(&self) -> usize {
+        if self.shared && self.accessible() {
+            self.range.end.val() - self.range.start.val() - self.frames.len()
+        } else {
+            self.frames.len()
+        }
+    }
+
+    /// 在子进程的地址空间中重建映射：共享映射共用物理页，私有映射复制一份。
+    ///
+    /// 共享映射未装入的页先在父进程中装入，否则父子进程将各自装入不同的页。
+    /// 需要的页帧数由 [`Mapping::fork_cost`] 给出，调用者应事先确认页帧充足。
+    fn fork<M: PageManager<Meta>>(
+        &mut self,
+        parent: &mut AddressSpace<Meta, M>,
+        child: &mut AddressSpace<Meta, M>,
+    ) -> Self {
+        if self.shared && self.accessible() {
+            for vpn in self.range.start.val()..self.range.end.val() {
+                if !self.frames.contains_key(&vpn) {
+                    assert!(self.load(parent, vpn), "out of frames");
+                }
+            }
+        }
+        let frames = self
+            .frames
+            .iter()
+            .map(|(&vpn, frame)| {
+                let frame = if self.shared {
+                    frame.clone()
+                } else {
+                    let copy = Frame::new().expect("out of frames");
+                    unsafe {
+                        copy.as_mut_ptr()
+                            .copy_from_nonoverlapping(frame.as_mut_ptr(), page_size::<Meta>())
+                    };
+                    copy
+                };
+                let vpn_ = VPN::new(vpn);
+                child.map_shared(vpn_..vpn_ + 1, frame.ppn(), self.flags);
+                (vpn, frame)
+            })
+            .collect();
+        Self {
+            range: self.range.clone(),
+            flags: self.flags,
+            file: self.file.clone(),
+            shared: self.shared,
+            frames,
+        }
+    }
+}
+
+/// 进程的 `mmap` 映射集合。
+pub struct Mappings<Meta: VmMeta>(Vec<Mapping<Meta>>);
+
+impl<Meta: VmMeta> Mappings<Meta> {
+    /// 空的映射集合。
+    #[inline]
+    pub const fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    /// 建立映射，成功时返回映射的起始地址，失败时返回错误码。
+    ///
+    /// `addr` 为 0 时由内核选择地址；否则 `addr` 空闲时使用它，
+    /// 不空闲时若指定了 [`MAP_FIXED`] 则失败，否则由内核另选地址。
+    pub fn map<M: PageManager<Meta>>(
+        &mut self,
+        space: &AddressSpace<Meta, M>,
+        addr: usize,
+        len: usize,
+        prot: i32,
+        flags: i32,
+        file: Option<(Arc<Inode>, usize)>,
+    ) -> Result<usize, isize> {
+        let mask = page_mask::<Meta>();
+        if len == 0 || addr & mask != 0 || matches!(file, Some((_, off)) if off & mask != 0) {
+            Err(EINVAL)?
+        }
+        let shared = match flags & (MAP_SHARED | MAP_PRIVATE) {
+            MAP_SHARED => true,
+            MAP_PRIVATE => false,
+            _ => Err(EINVAL)?,
+        };
+        let fixed = flags & MAP_FIXED != 0;
+        let mut vm_flags: [u8; 5] = *b"U___V";
+        if prot & PROT_EXEC != 0 {
+            vm_flags[1] = b'X';
+        }
+        // RISC-V 保留了只写不读的页表项，可写的映射同时可读
+        if prot & PROT_WRITE != 0 {
+            vm_flags[2] = b'W';
+            vm_flags[3] = b'R';
+        }
+        if prot & PROT_READ != 0 {
+            vm_flags[3] = b'R';
+        }
+        let flags =
+            VmFlags::from_str(unsafe { core::str::from_utf8_unchecked(&vm_flags) }).unwrap();
+        let pages = (len + mask) >> Meta::PAGE_BITS;
+        let hint = addr >> Meta::PAGE_BITS;
+        let start = if addr != 0 && self.is_free(space, hint..hint + pages) {
+            hint
+        } else if fixed {
+            Err(EINVAL)?
+        } else {
+            let start = self
+                .0
+                .iter()
+                .map(|m| m.range.end.val())
+                .max()
+                // 内核自动选择映射地址时，从用户地址空间的 1/4 处开始
+                .unwrap_or(VmLayout::<Meta>::USER_TOP >> 2 >> Meta::PAGE_BITS);
+            if !self.is_free(space, start..start + pages) {
+                Err(ENOMEM)?
+            }
+            start
+        };
+        self.0.try_reserve(1).map_err(|_| ENOMEM)?;
+        self.0.push(Mapping {
+            range: VPN::new(start)..VPN::new(start + pages),
+            flags,
+            file,
+            shared,
+            frames: BTreeMap::new(),
+        });
+        Ok(start << Meta::PAGE_BITS)
+    }
+
+    /// 解除 `addr..addr + len` 覆盖的映射，共享映射的脏页先写回文件。
+    ///
+    /// 不支持拆分映射，范围与某个映射部分重叠时失败。
+    pub fn unmap<M: PageManager<Meta>>(
+        &mut self,
+        space: &mut AddressSpace<Meta, M>,
+        addr: usize,
+        len: usize,
+    ) -> bool {
+        let Some(range) = Self::pages(addr, len) else {
+            return false;
+        };
+        if self.0.iter().any(|m| Self::partial(m, &range)) {
+            return false;
+        }
+        self.0.retain(|m| {
+            if range.contains(&m.range.start.val()) {
+                m.sync(space);
+                space.unmap(m.range.clone());
+                false
+            } else {
+                true
+            }
+        });
+        true
+    }
+
+    /// 把 `addr..addr + len` 覆盖的共享映射的脏页写回文件。
+    pub fn sync<M: PageManager<Meta>>(
+        &self,
+        space: &AddressSpace<Meta, M>,
+        addr: usize,
+        len: usize,
+    ) -> bool {
+        let Some(range) = Self::pages(addr, len) else {
+            return false;
+        };
+        self.0
+            .iter()
+            .filter(|m| range.start < m.range.end.val() && m.range.start.val() < range.end)
+            .for_each(|m| m.sync(space));
+        true
+    }
+
+    /// 把所有共享映射的脏页写回文件。
+    pub fn sync_all<M: PageManager<Meta>>(&self, space: &AddressSpace<Meta, M>) {
+        self.0.iter().for_each(|m| m.sync(space));
+    }
+
+    /// 为子进程复制映射需要的页帧数。
+    #[inline]
+    pub fn fork_cost(&self) -> usize {
+        self.0.iter().map(Mapping::fork_cost).sum()
+    }
+
+    /// 为子进程复制映射。
+    pub fn fork<M: PageManager<Meta>>(
+        &mut self,
+        parent: &mut AddressSpace<Meta, M>,
+        child: &mut AddressSpace<Meta, M>,
+    ) -> Self {
+        Self(self.0.iter_mut().map(|m| m.fork(parent, child)).collect())
+    }
+
+    /// 处理缺页，返回是否已装入。
+    ///
+    /// 访问不属于任何映射、已装入或 `PROT_NONE` 的页，说明是真正的访问错误；页帧耗尽时同样无法装入。
+    pub fn page_fault<M: PageManager<Meta>>(
+        &mut self,
+        space: &mut AddressSpace<Meta, M>,
+        addr: usize,
+    ) -> bool {
+        let vpn = VAddr::<Meta>::new(addr).floor().val();
+        match self.0.iter_mut().find(|m| m.contains(vpn)) {
+            Some(m) if m.accessible() && !m.frames.contains_key(&vpn) => m.load(space, vpn),
+            _ => false,
+        }
+    }
+
+    /// 判断一段虚页是否既不属于任何映射，也未在地址空间中映射。
+    pub fn is_free<M: PageManager<Meta>>(
+        &self,
+        space: &AddressSpace<Meta, M>,
+        range: Range<usize>,
+    ) -> bool {
+        let valid = VmFlags::build_from_str("V");
+        range.end <= VmLayout::<Meta>::USER_TOP >> Meta::PAGE_BITS
+            && !self
+                .0
+                .iter()
+                .any(|m| range.start < m.range.end.val() && m.range.start.val() < range.end)
+            && !range.clone().any(|vpn| {
+                space
+                    .translate::<u8>(VPN::<Meta>::new(vpn).base(), valid)
+                    .is_some()
+            })
+    }
+
+    /// `addr..addr + len` 覆盖的虚页号范围。
+    fn pages(addr: usize, len: usize) -> Option<Range<usize>> {
+        let mask = page_mask::<Meta>();
+        if addr & mask != 0 {
+            None?
+        }
+        let start = addr >> Meta::PAGE_BITS;
+        Some(start..start + ((len + mask) >> Meta::PAGE_BITS))
+    }
+
+    #[inline]
+    fn partial(m: &Mapping<Meta>, range: &Range<usize>) -> bool {
+        let (start, end) = (m.range.start.val(), m.range.end.val());
+        start < range.end && range.start < end && (start < range.start || range.end < end)
+    }
+}
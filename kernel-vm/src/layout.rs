@@ -0,0 +1,42 @@
+use core::{marker::PhantomData, ops::Range};
+use page_table::{VmMeta, PPN, VPN};
+
+/// 由页表格式导出的虚存布局。
+///
+/// 内核不再直接写出 `1 << 38`、`8 << 60` 这样只对 Sv39 成立的数字，
+/// 而是从 [`VmMeta`] 计算，换用其他页表格式时布局随之改变。
+pub struct VmLayout<Meta: VmMeta>(PhantomData<Meta>);
+
+impl<Meta: VmMeta> VmLayout<Meta> {
+    /// 虚地址有效位数。
+    pub const V_ADDR_BITS: usize = {
+        let mut bits = Meta::PAGE_BITS;
+        let mut i = 0;
+        while i < Meta::LEVEL_BITS.len() {
+            bits += Meta::LEVEL_BITS[i];
+            i += 1;
+        }
+        bits
+    };
+
+    /// 用户地址空间的上界（不含）。
+    ///
+    /// 用户使用地址空间的低半部分，其上界也是用户栈的栈顶。
+    pub const USER_TOP: usize = 1 << (Self::V_ADDR_BITS - 1);
+
+    /// `satp` 寄存器的 MODE 域：Sv39 为 8，Sv48 为 9，Sv57 为 10。
+    pub const SATP_MODE: usize = Meta::MAX_LEVEL + 6;
+
+    /// 紧贴 [`USER_TOP`](Self::USER_TOP) 之下的 `count` 个虚页，用于放置栈。
+    #[inline]
+    pub fn top_pages(count: usize) -> Range<VPN<Meta>> {
+        let end = Self::USER_TOP >> Meta::PAGE_BITS;
+        VPN::new(end - count)..VPN::new(end)
+    }
+
+    /// 以 `root` 为根页表构造 `satp` 的值。
+    #[inline]
+    pub fn satp(root: PPN<Meta>) -> usize {
+        (Self::SATP_MODE << 60) | root.val()
+    }
+}
@@ -3,9 +3,11 @@
 #![no_std]
 #![deny(warnings, missing_docs)]
 
+mod layout;
 mod space;
 
 pub extern crate page_table;
+pub use layout::VmLayout;
 pub use space::AddressSpace;
 
 use core::ptr::NonNull;
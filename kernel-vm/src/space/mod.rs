@@ -1,4 +1,5 @@
 mod mapper;
+mod unmapper;
 mod visitor;
 
 extern crate alloc;
@@ -7,10 +8,13 @@ use crate::PageManager;
 use alloc::vec::Vec;
 use core::{fmt, ops::Range, ptr::NonNull};
 use mapper::Mapper;
-use page_table::{PageTable, PageTableFormatter, Pos, VAddr, VmFlags, VmMeta, PPN, VPN};
+use page_table::{PageTable, PageTableFormatter, Pos, Pte, VAddr, VmFlags, VmMeta, PPN, VPN};
+use unmapper::Unmapper;
 use visitor::Visitor;
 
 /// 地址空间。
+///
+/// 地址空间释放时回收页管理器标记为自己所有的物理页和页表页，其他页表项只是丢弃。
 pub struct AddressSpace<Meta: VmMeta, M: PageManager<Meta>> {
     /// 虚拟地址块
     pub areas: Vec<Range<VPN<Meta>>>,
@@ -42,6 +46,35 @@ impl<Meta: VmMeta, M: PageManager<Meta>> AddressSpace<Meta, M> {
     /// 向地址空间增加映射关系。
     pub fn map_extern(&mut self, range: Range<VPN<Meta>>, pbase: PPN<Meta>, flags: VmFlags<Meta>) {
         self.areas.push(range.start..range.end);
+        self.map_pages(range, pbase, flags)
+    }
+
+    /// 向地址空间增加共享物理页的映射关系。
+    ///
+    /// 共享的区域不记录在 `areas` 中，[`cloneself`](Self::cloneself) 不会复制它们，
+    /// 需要共享的一方自行在新的地址空间中再次映射。
+    #[inline]
+    pub fn map_shared(&mut self, range: Range<VPN<Meta>>, pbase: PPN<Meta>, flags: VmFlags<Meta>) {
+        self.map_pages(range, pbase, flags)
+    }
+
+    /// 取消 `range` 范围内的映射关系。
+    ///
+    /// 只清除页表项，不释放物理页，物理页由映射时的所有者回收。
+    pub fn unmap(&mut self, range: Range<VPN<Meta>>) {
+        self.areas.retain(|area| *area != range);
+        let root = self.root();
+        for vpn in range.start.val()..range.end.val() {
+            let vpn = VPN::new(vpn);
+            let mut unmapper = Unmapper::new(self, vpn);
+            root.walk(Pos::new(vpn, 0), &mut unmapper);
+            if let Some(mut pte) = unmapper.ans() {
+                unsafe { *pte.as_mut() = VmFlags::from_raw(0).build_pte(PPN::new(0)) };
+            }
+        }
+    }
+
+    fn map_pages(&mut self, range: Range<VPN<Meta>>, pbase: PPN<Meta>, flags: VmFlags<Meta>) {
         let count = range.end.val() - range.start.val();
         let mut root = self.root();
         let mut mapper = Mapper::new(self, pbase..pbase + count, flags);
@@ -130,6 +163,33 @@ impl<Meta: VmMeta, M: PageManager<Meta>> AddressSpace<Meta, M> {
             new_addrspace.map_extern(vpn_range, ppn, flags);
         }
     }
+
+    /// 回收 `level` 级页表 `table` 中拥有所有权的页，页表项指向下一级页表时先回收下一级页表中的页。
+    ///
+    /// 没有所有权的页表项不向下遍历，其中的页表和物理页属于别的地址空间或别的所有者。
+    fn drop_table(&mut self, table: NonNull<Pte<Meta>>, level: usize) {
+        for i in 0..1 << Meta::LEVEL_BITS[level] {
+            let pte = unsafe { *table.as_ptr().add(i) };
+            if !pte.is_valid() || !self.page_manager.check_owned(pte) {
+                continue;
+            }
+            if Meta::is_leaf(pte.flags().val()) {
+                // 高级页表中的叶页表项是大页
+                let bits: usize = Meta::LEVEL_BITS[..level].iter().sum();
+                self.page_manager.deallocate(pte, 1 << bits);
+            } else {
+                self.drop_table(self.page_manager.p_to_v(pte.ppn()), level - 1);
+                self.page_manager.deallocate(pte, 1);
+            }
+        }
+    }
+}
+
+impl<Meta: VmMeta, M: PageManager<Meta>> Drop for AddressSpace<Meta, M> {
+    fn drop(&mut self) {
+        self.drop_table(self.page_manager.root_ptr(), Meta::MAX_LEVEL);
+        self.page_manager.drop_root();
+    }
 }
 
 impl<Meta: VmMeta, P: PageManager<Meta>> fmt::Debug for AddressSpace<Meta, P> {
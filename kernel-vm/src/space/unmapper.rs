@@ -0,0 +1,62 @@
+use crate::{AddressSpace, PageManager};
+use core::ptr::NonNull;
+use page_table::{Pos, Pte, VmMeta, VPN};
+
+/// 查找叶页表项所在的位置，以便原地修改。
+pub(super) struct Unmapper<'a, Meta: VmMeta, M: PageManager<Meta>> {
+    space: &'a AddressSpace<Meta, M>,
+    vpn: VPN<Meta>,
+    table: NonNull<Pte<Meta>>,
+    // `table` 所在的页表级别，每进入一级页表减一
+    level: usize,
+    ans: Option<NonNull<Pte<Meta>>>,
+}
+
+impl<'a, Meta: VmMeta, M: PageManager<Meta>> Unmapper<'a, Meta, M> {
+    #[inline]
+    pub fn new(space: &'a AddressSpace<Meta, M>, vpn: VPN<Meta>) -> Self {
+        Self {
+            space,
+            vpn,
+            table: space.page_manager.root_ptr(),
+            level: Meta::MAX_LEVEL,
+            ans: None,
+        }
+    }
+
+    #[inline]
+    pub const fn ans(self) -> Option<NonNull<Pte<Meta>>> {
+        self.ans
+    }
+}
+
+impl<'a, Meta: VmMeta, M: PageManager<Meta>> page_table::Visitor<Meta> for Unmapper<'a, Meta, M> {
+    #[inline]
+    fn arrive(&mut self, pte: Pte<Meta>, _target_hint: Pos<Meta>) -> Pos<Meta> {
+        if pte.is_valid() {
+            // 大页的叶页表项映射了整个大页，不能按页清除
+            assert_eq!(self.level, 0, "cannot unmap a single page inside a huge page");
+            self.ans = Some(unsafe {
+                NonNull::new_unchecked(self.table.as_ptr().add(self.vpn.index_in(0)))
+            });
+        }
+        Pos::stop()
+    }
+
+    #[inline]
+    fn meet(
+        &mut self,
+        _level: usize,
+        pte: Pte<Meta>,
+        _target_hint: Pos<Meta>,
+    ) -> Option<NonNull<Pte<Meta>>> {
+        self.table = self.space.page_manager.p_to_v(pte.ppn());
+        self.level -= 1;
+        Some(self.table)
+    }
+
+    #[inline]
+    fn block(&mut self, _level: usize, _pte: Pte<Meta>, _target: Pos<Meta>) -> Pos<Meta> {
+        Pos::stop()
+    }
+}
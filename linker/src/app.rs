@@ -1,10 +1,30 @@
-﻿/// 应用程序元数据。
+/// 应用程序元数据。
+///
+/// 由 xtask 生成的 `app.asm` 定义，头部之后是每个应用程序一项的 [`AppEntry`] 表：
+///
+/// ```asm
+/// apps:
+///     .quad base
+///     .quad step
+///     .quad count
+///     .quad app_0_start, app_0_end, app_0_name
+///     ...
+/// ```
 #[repr(C)]
 pub struct AppMeta {
     base: u64,
     step: u64,
     count: u64,
-    first: u64,
+    first: AppEntry,
+}
+
+/// 应用程序表项。
+#[repr(C)]
+struct AppEntry {
+    start: u64,
+    end: u64,
+    /// 以 0 结尾的应用程序名字。
+    name: u64,
 }
 
 impl AppMeta {
@@ -17,6 +37,46 @@ impl AppMeta {
         unsafe { &apps }
     }
 
+    /// 链接进来的应用程序数量。
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.count as _
+    }
+
+    /// 是否没有链接应用程序。
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// 取得第 `i` 个应用程序。
+    pub fn get(&'static self, i: usize) -> Option<App> {
+        if i >= self.len() {
+            return None;
+        }
+        let entry = unsafe { &*(&self.first as *const AppEntry).add(i) };
+        let name = unsafe { core::ffi::CStr::from_ptr(entry.name as _) };
+        let base = if self.base != 0 {
+            self.base as usize + i * self.step as usize
+        } else {
+            0
+        };
+        Some(App {
+            index: i,
+            name: name.to_str().unwrap(),
+            data: unsafe {
+                core::slice::from_raw_parts(entry.start as _, (entry.end - entry.start) as _)
+            },
+            base,
+        })
+    }
+
+    /// 按名字查找应用程序。
+    #[inline]
+    pub fn find(&'static self, name: &str) -> Option<App> {
+        self.iter().find(|app| app.name == name)
+    }
+
     /// 遍历链接进来的应用程序。
     #[inline]
     pub fn iter(&'static self) -> AppIterator {
@@ -24,37 +84,85 @@ impl AppMeta {
     }
 }
 
+/// 一个链接进来的应用程序。
+#[derive(Clone, Copy)]
+pub struct App {
+    index: usize,
+    name: &'static str,
+    data: &'static [u8],
+    /// 装载地址，ELF 格式的应用程序为 0。
+    base: usize,
+}
+
+impl App {
+    /// 应用程序在表中的序号。
+    #[inline]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// 应用程序的名字。
+    #[inline]
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// 应用程序映像的字节数。
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.data.len()
+    }
+
+    /// 应用程序的入口地址。
+    ///
+    /// 二进制格式的应用程序从装载地址开始执行，ELF 格式的应用程序取 ELF 头中的入口。
+    pub fn entry(&self) -> usize {
+        if self.base != 0 {
+            self.base
+        } else if self.data.starts_with(b"\x7fELF") && self.data.len() >= 32 {
+            // ELF64 头中 e_entry 位于偏移 24
+            let mut entry = [0u8; 8];
+            entry.copy_from_slice(&self.data[24..32]);
+            u64::from_le_bytes(entry) as _
+        } else {
+            0
+        }
+    }
+
+    /// 链接在内核中的应用程序映像。
+    #[inline]
+    pub fn data(&self) -> &'static [u8] {
+        self.data
+    }
+
+    /// 装载应用程序。
+    ///
+    /// 二进制格式的应用程序被复制到装载地址，其后 2 MiB 以内的空间清零；ELF 格式的应用程序直接返回映像。
+    pub fn load(&self) -> &'static [u8] {
+        if self.base == 0 {
+            return self.data;
+        }
+        let size = self.data.len();
+        unsafe {
+            core::ptr::copy_nonoverlapping::<u8>(self.data.as_ptr(), self.base as _, size);
+            core::slice::from_raw_parts_mut(self.base as *mut u8, 0x20_0000)[size..].fill(0);
+            core::slice::from_raw_parts(self.base as _, size)
+        }
+    }
+}
+
 /// 应用程序迭代器。
 pub struct AppIterator {
     meta: &'static AppMeta,
-    i: u64,
+    i: usize,
 }
 
 impl Iterator for AppIterator {
-    type Item = &'static [u8];
+    type Item = App;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.i >= self.meta.count {
-            None
-        } else {
-            let i = self.i as usize;
-            self.i += 1;
-            unsafe {
-                let slice = core::slice::from_raw_parts(
-                    &self.meta.first as *const _ as *const usize,
-                    (self.meta.count + 1) as _,
-                );
-                let pos = slice[i];
-                let size = slice[i + 1] - pos;
-                let base = self.meta.base as usize + i * self.meta.step as usize;
-                if base != 0 {
-                    core::ptr::copy_nonoverlapping::<u8>(pos as _, base as _, size);
-                    core::slice::from_raw_parts_mut(base as *mut u8, 0x20_0000)[size..].fill(0);
-                    Some(core::slice::from_raw_parts(base as _, size))
-                } else {
-                    Some(core::slice::from_raw_parts(pos as _, size))
-                }
-            }
-        }
+        let app = self.meta.get(self.i)?;
+        self.i += 1;
+        Some(app)
     }
 }
@@ -21,22 +21,33 @@
 //! 内核所在内核区域定义成 4 个部分（[`KernelRegionTitle`]）:
 //!
 //! 1. 代码段
-//! 2. 只读数据段
+//! 2. 只读数据段，末尾是 xtask 生成的内核符号表（`.ksymbols`）
 //! 3. 数据段
 //! 4. 启动数据段
 //!
-//! 启动数据段放在最后，以便启动完成后换栈。届时可放弃启动数据段，将其加入动态内存区。
+//! 启动数据段放在最后，以便启动完成后换栈。届时可放弃启动数据段，将其加入动态内存区：
+//!
+//! ```ignore
+//! // 在堆上分配新的栈，换栈后继续执行 `kernel_main`
+//! unsafe { linker::switch_stack(stack_top, kernel_main) }
+//! ```
+//!
+//! 换栈后 [`KernelLayout::boot`] 给出的区域不再使用。
 //!
 //! 用 [`KernelLayout`] 结构体定位、保存和访问内核内存布局。
+//!
+//! 启用 `higher-half` 特性时，内核链接到 [`KERNEL_OFFSET`] 之上的高半部分，[`boot0`] 用启动页表开启分页后再进入高级语言入口。
+//! 内核中凡是要把内核虚地址写入页表或交给设备的地方，都要用 [`virt_to_phys`] 和 [`phys_to_virt`] 转换。
 
 #![no_std]
 #![deny(warnings, missing_docs)]
 
 mod app;
 
-pub use app::{AppIterator, AppMeta};
+pub use app::{App, AppIterator, AppMeta};
 
 /// 链接脚本。
+#[cfg(not(feature = "higher-half"))]
 pub const SCRIPT: &[u8] = b"\
 OUTPUT_ARCH(riscv)
 SECTIONS {
@@ -49,6 +60,43 @@ SECTIONS {
         __rodata = .;
         *(.rodata .rodata.*)
         *(.srodata .srodata.*)
+        KEEP(*(.ksymbols))
+    }
+    .data : ALIGN(4K) {
+        __data = .;
+        *(.data .data.*)
+        *(.sdata .sdata.*)
+    }
+    .bss : ALIGN(8) {
+        __sbss = .;
+        *(.bss .bss.*)
+        *(.sbss .sbss.*)
+        __ebss = .;
+    }
+    .boot : ALIGN(4K) {
+        __boot = .;
+        KEEP(*(.boot.stack))
+    }
+    __end = .;
+}";
+
+/// 链接脚本。
+///
+/// 启用 `higher-half` 特性时，内核链接到 [`KERNEL_OFFSET`] 之上的高半部分，但仍装载到原来的物理地址。
+#[cfg(feature = "higher-half")]
+pub const SCRIPT: &[u8] = b"\
+OUTPUT_ARCH(riscv)
+SECTIONS {
+    .text 0xffffffc080200000 : AT(0x80200000) {
+        __start = .;
+        *(.text.entry)
+        *(.text .text.*)
+    }
+    .rodata : ALIGN(4K) {
+        __rodata = .;
+        *(.rodata .rodata.*)
+        *(.srodata .srodata.*)
+        KEEP(*(.ksymbols))
     }
     .data : ALIGN(4K) {
         __data = .;
@@ -68,9 +116,40 @@ SECTIONS {
     __end = .;
 }";
 
+/// 内核虚地址与物理地址之差。
+///
+/// 默认内核的虚地址等于物理地址；启用 `higher-half` 特性时，内核位于 Sv39 和 Sv48 共有的高半部分，
+/// 低半部分全部留给用户地址空间。
+#[cfg(not(feature = "higher-half"))]
+pub const KERNEL_OFFSET: usize = 0;
+/// 内核虚地址与物理地址之差。
+///
+/// 默认内核的虚地址等于物理地址；启用 `higher-half` 特性时，内核位于 Sv39 和 Sv48 共有的高半部分，
+/// 低半部分全部留给用户地址空间。
+#[cfg(feature = "higher-half")]
+pub const KERNEL_OFFSET: usize = 0xffff_ffc0_0000_0000;
+
+/// 内核虚地址转换为物理地址。
+#[inline]
+pub const fn virt_to_phys(va: usize) -> usize {
+    va.wrapping_sub(KERNEL_OFFSET)
+}
+
+/// 物理地址转换为内核虚地址。
+#[inline]
+pub const fn phys_to_virt(pa: usize) -> usize {
+    pa.wrapping_add(KERNEL_OFFSET)
+}
+
 /// 定义内核入口。
 ///
 /// 将设置一个启动栈，并在启动栈上调用高级语言入口。
+/// 入口的参数是 SBI 传入的 `a0`、`a1`，即硬件线程号和设备树地址：
+///
+/// ```ignore
+/// extern "C" fn rust_main(hartid: usize, dtb: usize) -> ! { ... }
+/// ```
+#[cfg(not(feature = "higher-half"))]
 #[macro_export]
 macro_rules! boot0 {
     ($entry:ident; stack = $stack:expr) => {
@@ -83,6 +162,8 @@ macro_rules! boot0 {
 
             core::arch::naked_asm!(
                 "la sp, __end",
+                // 清空帧指针，回溯调用栈到此为止
+                "li s0, 0",
                 "j  {main}",
                 main = sym rust_main,
             )
@@ -90,7 +171,93 @@ macro_rules! boot0 {
     };
 }
 
+/// 定义内核入口。
+///
+/// 将设置一个启动栈，用启动页表开启分页，然后在高半部分的启动栈上调用高级语言入口。
+/// 入口的参数是 SBI 传入的 `a0`、`a1`，即硬件线程号和设备树地址：
+///
+/// ```ignore
+/// extern "C" fn rust_main(hartid: usize, dtb: usize) -> ! { ... }
+/// ```
+#[cfg(feature = "higher-half")]
+#[macro_export]
+macro_rules! boot0 {
+    ($entry:ident; stack = $stack:expr) => {
+        #[unsafe(naked)]
+        #[no_mangle]
+        #[link_section = ".text.entry"]
+        unsafe extern "C" fn _start() -> ! {
+            #[link_section = ".boot.stack"]
+            static mut STACK: [u8; $stack] = [0u8; $stack];
+
+            core::arch::naked_asm!(
+                // 此时在物理地址上执行，`la` 得到的都是物理地址
+                "la   t0, {page_table}",
+                "srli t0, t0, 12",
+                "li   t1, 8 << 60",
+                "or   t0, t0, t1",
+                "csrw satp, t0",
+                "sfence.vma",
+                // 换到高半部分的栈和入口
+                "li   t1, {offset}",
+                "la   sp, __end",
+                "add  sp, sp, t1",
+                "li   s0, 0",
+                "la   t0, {main}",
+                "add  t0, t0, t1",
+                "jr   t0",
+                page_table = sym $crate::BOOT_PAGE_TABLE,
+                offset = const $crate::KERNEL_OFFSET,
+                main = sym rust_main,
+            )
+        }
+    };
+}
+
+/// 启动页表。
+#[cfg(feature = "higher-half")]
+#[doc(hidden)]
+#[repr(C, align(4096))]
+pub struct BootPageTable([usize; 512]);
+
+/// Sv39 启动页表，把 `0x8000_0000` 起的 1 GiB 同时映射到原地和高半部分。
+///
+/// 原地映射使开启分页后的几条指令能继续执行，内核建立自己的地址空间后不再使用。
+#[cfg(feature = "higher-half")]
+#[doc(hidden)]
+pub static BOOT_PAGE_TABLE: BootPageTable = {
+    const BASE: usize = 0x8000_0000;
+    // 可读写执行的大页，预置 A、D 位
+    const PTE: usize = (BASE >> 12) << 10 | 0xcf;
+    let mut table = [0; 512];
+    table[(BASE >> 30) & 0x1ff] = PTE;
+    table[((BASE + KERNEL_OFFSET) >> 30) & 0x1ff] = PTE;
+    BootPageTable(table)
+};
+
+/// 换到 `stack_top` 指向的栈上执行 `entry`，不再返回。
+///
+/// 原来栈上的所有数据都被放弃，需要的信息应该在 `entry` 中重新获取。
+///
+/// # Safety
+///
+/// `stack_top` 必须是一个 16 字节对齐的栈顶，这个栈足够 `entry` 使用且始终有效。
+#[cfg(target_arch = "riscv64")]
+#[inline]
+pub unsafe fn switch_stack(stack_top: usize, entry: extern "C" fn() -> !) -> ! {
+    core::arch::asm!(
+        "mv sp, {stack_top}",
+        "li s0, 0",
+        "jr {entry}",
+        stack_top = in(reg) stack_top,
+        entry = in(reg) entry,
+        options(noreturn),
+    )
+}
+
 /// 内核地址信息。
+///
+/// 所有地址都是内核虚地址，用 [`virt_to_phys`] 转换为物理地址。
 #[derive(Debug)]
 pub struct KernelLayout {
     text: usize,
@@ -150,6 +317,14 @@ impl KernelLayout {
         self.end
     }
 
+    /// 启动数据段的地址范围，其中只有启动栈。
+    ///
+    /// 通过 [`switch_stack`] 换栈之后，这个区域可以加入动态内存区。
+    #[inline]
+    pub const fn boot(&self) -> Range<usize> {
+        self.boot..self.end
+    }
+
     /// 内核静态二进制长度。
     #[inline]
     pub const fn len(&self) -> usize {
@@ -0,0 +1,166 @@
+//! 从设备树发现平台信息。
+//!
+//! 内核启动时调用 [`init`] 解析 SBI 传入的设备树，之后用 [`get`] 访问解析结果。
+
+#![no_std]
+#![deny(warnings, missing_docs)]
+
+use core::{fmt, ops::Range};
+use fdt::{node::FdtNode, Fdt};
+use spin::Once;
+
+/// 最多记录的 virtio-mmio 设备数，QEMU virt 有 8 个。
+pub const MAX_VIRTIO: usize = 8;
+
+/// 一个内存映射的设备。
+#[derive(Clone, Debug)]
+pub struct Device {
+    /// 寄存器的地址范围。
+    pub range: Range<usize>,
+    /// 中断号。
+    pub irq: Option<usize>,
+}
+
+/// 平台信息。
+pub struct Platform {
+    /// 物理内存。
+    pub memory: Range<usize>,
+    /// 硬件线程数。
+    pub harts: usize,
+    /// `time` 寄存器的频率。
+    pub timebase_frequency: usize,
+    /// 串口。
+    pub uart: Option<Device>,
+    /// 平台级中断控制器。
+    pub plic: Option<Device>,
+    virtio: [Option<Device>; MAX_VIRTIO],
+}
+
+static PLATFORM: Once<Platform> = Once::new();
+
+/// 解析位于 `dtb` 的设备树。
+///
+/// # Safety
+///
+/// `dtb` 必须是 SBI 传入的设备树地址，且解析期间未被修改。
+///
+/// # Panics
+///
+/// 设备树无效或缺少物理内存、CPU 信息时 panic。
+pub unsafe fn init(dtb: usize) -> &'static Platform {
+    PLATFORM.call_once(|| {
+        let fdt = Fdt::from_ptr(dtb as *const u8).expect("invalid device tree");
+        Platform::parse(&fdt)
+    })
+}
+
+/// 取得平台信息。
+///
+/// # Panics
+///
+/// 调用 [`init`] 之前调用时 panic。
+#[inline]
+pub fn get() -> &'static Platform {
+    PLATFORM.get().expect("platform not initialized")
+}
+
+/// 取得平台信息，调用 [`init`] 之前返回 `None`。
+#[inline]
+pub fn try_get() -> Option<&'static Platform> {
+    PLATFORM.get()
+}
+
+impl Platform {
+    fn parse(fdt: &Fdt) -> Self {
+        let memory = fdt
+            .memory()
+            .regions()
+            .next()
+            .map(|r| {
+                let start = r.starting_address as usize;
+                start..start + r.size.unwrap_or(0)
+            })
+            .expect("no memory in device tree");
+        let harts = fdt.cpus().count();
+        let timebase_frequency = fdt
+            .cpus()
+            .next()
+            .expect("no cpu in device tree")
+            .timebase_frequency();
+        let mut platform = Self {
+            memory,
+            harts,
+            timebase_frequency,
+            uart: None,
+            plic: None,
+            virtio: Default::default(),
+        };
+        let mut virtio = 0;
+        for node in fdt.all_nodes() {
+            let Some(compatible) = node.compatible() else {
+                continue;
+            };
+            let Some(device) = device(&node) else {
+                continue;
+            };
+            for name in compatible.all() {
+                match name {
+                    "ns16550a" if platform.uart.is_none() => platform.uart = Some(device),
+                    "riscv,plic0" | "sifive,plic-1.0.0" if platform.plic.is_none() => {
+                        platform.plic = Some(device)
+                    }
+                    "virtio,mmio" if virtio < MAX_VIRTIO => {
+                        platform.virtio[virtio] = Some(device);
+                        virtio += 1;
+                    }
+                    _ => continue,
+                }
+                break;
+            }
+        }
+        platform
+    }
+
+    /// 所有 virtio-mmio 设备，设备树中声明但未插入设备的槽位也在其中。
+    #[inline]
+    pub fn virtio(&self) -> impl Iterator<Item = &Device> {
+        self.virtio.iter().flatten()
+    }
+
+    /// 内核需要映射的所有设备寄存器区域。
+    #[inline]
+    pub fn mmio(&self) -> impl Iterator<Item = Range<usize>> + '_ {
+        self.uart
+            .iter()
+            .chain(self.plic.iter())
+            .chain(self.virtio())
+            .map(|device| device.range.clone())
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "memory {:#x}..{:#x}, {} harts, timebase {} Hz",
+            self.memory.start, self.memory.end, self.harts, self.timebase_frequency
+        )?;
+        if let Some(uart) = &self.uart {
+            write!(f, ", uart {:#x}", uart.range.start)?;
+        }
+        if let Some(plic) = &self.plic {
+            write!(f, ", plic {:#x}", plic.range.start)?;
+        }
+        write!(f, ", {} virtio-mmio", self.virtio().count())
+    }
+}
+
+/// 取出节点的第一个寄存器区域和第一个中断号。
+fn device(node: &FdtNode) -> Option<Device> {
+    let reg = node.reg()?.next()?;
+    let start = reg.starting_address as usize;
+    Some(Device {
+        range: start..start + reg.size.unwrap_or(0),
+        irq: node.interrupts().and_then(|mut irqs| irqs.next()),
+    })
+}
@@ -5,12 +5,14 @@ use super::{SignalNo, SignalResult};
 pub enum DefaultAction {
     Terminate(i32), // 结束进程。其实更标准的实现应该细分为 terminate / terminate(core dump) / stop
     Ignore,         // 忽略信号
+    Stop,           // 暂停进程，直到收到 SIGCONT
 }
 
 impl From<SignalNo> for DefaultAction {
     fn from(signal_no: SignalNo) -> Self {
         match signal_no {
-            SignalNo::SIGCHLD | SignalNo::SIGURG => Self::Ignore,
+            SignalNo::SIGCHLD | SignalNo::SIGURG | SignalNo::SIGCONT => Self::Ignore,
+            SignalNo::SIGTSTP | SignalNo::SIGTTIN | SignalNo::SIGTTOU => Self::Stop,
             _ => Self::Terminate(-(signal_no as i32)),
         }
     }
@@ -21,6 +23,7 @@ impl Into<SignalResult> for DefaultAction {
         match self {
             Self::Terminate(exit_code) => SignalResult::ProcessKilled(exit_code),
             Self::Ignore => SignalResult::Ignored,
+            Self::Stop => SignalResult::ProcessSuspended,
         }
     }
 }
@@ -123,8 +123,11 @@ impl Signal for SignalImpl {
             match self.handling.as_ref().unwrap() {
                 // 如果当前正在暂停状态
                 HandlingSignal::Frozen => {
-                    // 则检查是否收到 SIGCONT，如果收到则当前任务需要从暂停状态中恢复
-                    if self.fetch_and_remove(SignalNo::SIGCONT) {
+                    // 则检查是否收到 SIGKILL 或 SIGCONT：暂停的进程也能被杀死，收到 SIGCONT 则当前任务需要从暂停状态中恢复
+                    if self.fetch_and_remove(SignalNo::SIGKILL) {
+                        self.handling.take();
+                        SignalResult::ProcessKilled(-(SignalNo::SIGKILL as i32))
+                    } else if self.fetch_and_remove(SignalNo::SIGCONT) {
                         self.handling.take();
                         SignalResult::Handled
                     } else {
@@ -155,7 +158,11 @@ impl Signal for SignalImpl {
                     } else {
                         // 否则，使用自定义的 DefaultAction 类来处理
                         // 然后再转换成 SignalResult
-                        DefaultAction::from(signal).into()
+                        let action = DefaultAction::from(signal);
+                        if let DefaultAction::Stop = action {
+                            self.handling = Some(HandlingSignal::Frozen);
+                        }
+                        action.into()
                     }
                 }
             }
@@ -0,0 +1,20 @@
+//! see <https://github.com/torvalds/linux/blob/master/include/uapi/asm-generic/errno-base.h>.
+//!
+//! 系统调用失败时返回错误码的相反数。
+
+/// 没有权限。
+pub const EPERM: isize = 1;
+/// 进程不存在。
+pub const ESRCH: isize = 3;
+/// 没有可以等待的子进程。
+pub const ECHILD: isize = 10;
+/// 资源暂时不可用，稍后重试。
+pub const EAGAIN: isize = 11;
+/// 内存不足。
+pub const ENOMEM: isize = 12;
+/// 地址无效。
+pub const EFAULT: isize = 14;
+/// 参数无效。
+pub const EINVAL: isize = 22;
+/// 不是终端。
+pub const ENOTTY: isize = 25;
@@ -1,3 +1,39 @@
 ﻿pub const STDIN: usize = 0;
 pub const STDOUT: usize = 1;
 pub const STDDEBUG: usize = 2;
+
+/// 读取终端设置，参数指向 [`Termios`]。
+pub const TCGETS: usize = 0x5401;
+/// 修改终端设置，参数指向 [`Termios`]。
+pub const TCSETS: usize = 0x5402;
+/// 读取终端的前台进程组，参数指向 `i32`。
+pub const TIOCGPGRP: usize = 0x540f;
+/// 设置终端的前台进程组，参数指向 `i32`，进程组必须与调用者在同一会话中。
+pub const TIOCSPGRP: usize = 0x5410;
+
+/// 读取内核日志缓冲区中最近的日志，不取走。
+pub const SYSLOG_ACTION_READ_ALL: usize = 3;
+/// 清空内核日志缓冲区。
+pub const SYSLOG_ACTION_CLEAR: usize = 5;
+/// 内核日志缓冲区中日志的字节数。
+pub const SYSLOG_ACTION_SIZE_UNREAD: usize = 9;
+/// 内核日志缓冲区的容量。
+pub const SYSLOG_ACTION_SIZE_BUFFER: usize = 10;
+
+/// 终端设置。
+///
+/// 只支持本地模式 `c_lflag` 中的 [`Termios::ISIG`]、[`Termios::ICANON`] 和 [`Termios::ECHO`]。
+#[repr(C)]
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Termios {
+    pub lflag: u32,
+}
+
+impl Termios {
+    /// 收到 Ctrl-C、Ctrl-Z 时向前台进程组发送 `SIGINT`、`SIGTSTP`。
+    pub const ISIG: u32 = 0o1;
+    /// 规范模式，按行读取。
+    pub const ICANON: u32 = 0o2;
+    /// 回显输入。
+    pub const ECHO: u32 = 0o10;
+}
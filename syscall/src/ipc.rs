@@ -0,0 +1,10 @@
+//! see <https://github.com/torvalds/linux/blob/master/include/uapi/linux/ipc.h>.
+
+/// 私有的共享内存段，总是创建新段。
+pub const IPC_PRIVATE: usize = 0;
+/// 键不存在时创建。
+pub const IPC_CREAT: usize = 0o1000;
+/// 与 [`IPC_CREAT`] 一起使用，键已存在时失败。
+pub const IPC_EXCL: usize = 0o2000;
+/// 移除共享内存段。
+pub const IPC_RMID: usize = 0;
@@ -24,12 +24,36 @@ pub trait Process: Sync {
     fn exec(&self, caller: Caller, path: usize, count: usize) -> isize {
         unimplemented!()
     }
-    fn wait(&self, caller: Caller, pid: isize, exit_code_ptr: usize) -> isize {
+    fn wait(&self, caller: Caller, pid: isize, exit_code_ptr: usize, options: usize) -> isize {
         unimplemented!()
     }
     fn getpid(&self, caller: Caller) -> isize {
         unimplemented!()
     }
+    fn getppid(&self, caller: Caller) -> isize {
+        unimplemented!()
+    }
+    fn setpgid(&self, caller: Caller, pid: isize, pgid: isize) -> isize {
+        unimplemented!()
+    }
+    fn getpgid(&self, caller: Caller, pid: isize) -> isize {
+        unimplemented!()
+    }
+    fn setsid(&self, caller: Caller) -> isize {
+        unimplemented!()
+    }
+    fn getrusage(&self, caller: Caller, who: isize, usage: usize) -> isize {
+        unimplemented!()
+    }
+    fn times(&self, caller: Caller, buf: usize) -> isize {
+        unimplemented!()
+    }
+    fn task_info(&self, caller: Caller, pid: isize, info: usize) -> isize {
+        unimplemented!()
+    }
+    fn pid_max(&self, caller: Caller, max: usize) -> isize {
+        unimplemented!()
+    }
 }
 
 pub trait IO: Sync {
@@ -45,9 +69,19 @@ pub trait IO: Sync {
     fn close(&self, caller: Caller, fd: usize) -> isize {
         unimplemented!()
     }
+    fn ioctl(&self, caller: Caller, fd: usize, request: usize, arg: usize) -> isize {
+        unimplemented!()
+    }
+    fn syslog(&self, caller: Caller, ty: usize, buf: usize, len: usize) -> isize {
+        unimplemented!()
+    }
 }
 
 pub trait Memory: Sync {
+    fn brk(&self, caller: Caller, addr: usize) -> isize {
+        unimplemented!()
+    }
+
     fn mmap(
         &self,
         caller: Caller,
@@ -64,12 +98,35 @@ pub trait Memory: Sync {
     fn munmap(&self, caller: Caller, addr: usize, length: usize) -> isize {
         unimplemented!()
     }
+
+    fn msync(&self, caller: Caller, addr: usize, length: usize, flags: i32) -> isize {
+        unimplemented!()
+    }
+
+    fn shmget(&self, caller: Caller, key: usize, size: usize, flags: usize) -> isize {
+        unimplemented!()
+    }
+
+    fn shmat(&self, caller: Caller, shmid: usize, addr: usize, flags: usize) -> isize {
+        unimplemented!()
+    }
+
+    fn shmdt(&self, caller: Caller, addr: usize) -> isize {
+        unimplemented!()
+    }
+
+    fn shmctl(&self, caller: Caller, shmid: usize, cmd: usize, buf: usize) -> isize {
+        unimplemented!()
+    }
 }
 
 pub trait Scheduling: Sync {
     fn sched_yield(&self, caller: Caller) -> isize {
         unimplemented!()
     }
+    fn set_priority(&self, caller: Caller, prio: isize) -> isize {
+        unimplemented!()
+    }
 }
 
 pub trait Clock: Sync {
@@ -199,20 +256,43 @@ pub fn handle(caller: Caller, id: SyscallId, args: [usize; 6]) -> SyscallResult
         Id::READ => IO.call(id, |io| io.read(caller, args[0], args[1], args[2])),
         Id::OPENAT => IO.call(id, |io| io.open(caller, args[0], args[1])),
         Id::CLOSE => IO.call(id, |io| io.close(caller, args[0])),
+        Id::IOCTL => IO.call(id, |io| io.ioctl(caller, args[0], args[1], args[2])),
+        Id::SYSLOG => IO.call(id, |io| io.syslog(caller, args[0], args[1], args[2])),
         Id::EXIT => PROCESS.call(id, |proc| proc.exit(caller, args[0])),
         Id::CLONE => PROCESS.call(id, |proc| proc.fork(caller)),
         Id::EXECVE => PROCESS.call(id, |proc| proc.exec(caller, args[0], args[1])),
-        Id::WAIT4 => PROCESS.call(id, |proc| proc.wait(caller, args[0] as _, args[1])),
+        Id::WAIT4 => PROCESS.call(id, |proc| proc.wait(caller, args[0] as _, args[1], args[2])),
         Id::GETPID => PROCESS.call(id, |proc| proc.getpid(caller)),
+        Id::GETPPID => PROCESS.call(id, |proc| proc.getppid(caller)),
+        Id::SETPGID => PROCESS.call(id, |proc| proc.setpgid(caller, args[0] as _, args[1] as _)),
+        Id::GETPGID => PROCESS.call(id, |proc| proc.getpgid(caller, args[0] as _)),
+        Id::SETSID => PROCESS.call(id, |proc| proc.setsid(caller)),
+        Id::GETRUSAGE => PROCESS.call(id, |proc| proc.getrusage(caller, args[0] as _, args[1])),
+        Id::TIMES => PROCESS.call(id, |proc| proc.times(caller, args[0])),
+        Id::TASK_INFO => PROCESS.call(id, |proc| proc.task_info(caller, args[0] as _, args[1])),
+        Id::PID_MAX => PROCESS.call(id, |proc| proc.pid_max(caller, args[0])),
         Id::CLOCK_GETTIME => CLOCK.call(id, |clock| {
             clock.clock_gettime(caller, ClockId(args[0]), args[1])
         }),
         Id::SCHED_YIELD => SCHEDULING.call(id, |sched| sched.sched_yield(caller)),
+        Id::SETPRIORITY => SCHEDULING.call(id, |sched| sched.set_priority(caller, args[0] as _)),
+        Id::BRK => MEMORY.call(id, |memory| memory.brk(caller, args[0])),
         Id::MUNMAP => MEMORY.call(id, |memory| memory.munmap(caller, args[0], args[1])),
         Id::MMAP => MEMORY.call(id, |memory| {
             let [addr, length, prot, flags, fd, offset] = args;
             memory.mmap(caller, addr, length, prot as _, flags as _, fd as _, offset)
         }),
+        Id::MSYNC => MEMORY.call(id, |memory| {
+            memory.msync(caller, args[0], args[1], args[2] as _)
+        }),
+        Id::SHMGET => MEMORY.call(id, |memory| {
+            memory.shmget(caller, args[0], args[1], args[2])
+        }),
+        Id::SHMAT => MEMORY.call(id, |memory| memory.shmat(caller, args[0], args[1], args[2])),
+        Id::SHMDT => MEMORY.call(id, |memory| memory.shmdt(caller, args[0])),
+        Id::SHMCTL => MEMORY.call(id, |memory| {
+            memory.shmctl(caller, args[0], args[1], args[2])
+        }),
         Id::KILL => SIGNAL.call(id, |signal| signal.kill(caller, args[0] as _, args[1] as _)),
         Id::RT_SIGACTION => SIGNAL.call(id, |signal| {
             signal.sigaction(caller, args[0] as _, args[1], args[2])
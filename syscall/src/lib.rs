@@ -4,13 +4,23 @@
 #[cfg(all(feature = "kernel", feature = "user"))]
 compile_error!("You can only use one of `supervisor` or `user` features at a time");
 
+mod errno;
 mod io;
+mod ipc;
+mod mman;
+mod resource;
 mod syscalls;
 mod time;
+mod wait;
 
+pub use errno::*;
 pub use io::*;
+pub use ipc::*;
+pub use mman::*;
+pub use resource::*;
 pub use signal_defs::{SignalAction, SignalNo, MAX_SIG};
 pub use time::*;
+pub use wait::*;
 
 #[cfg(feature = "user")]
 mod user;
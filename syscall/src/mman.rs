@@ -0,0 +1,26 @@
+//! see <https://github.com/torvalds/linux/blob/master/include/uapi/asm-generic/mman-common.h>.
+
+/// 页不可访问。
+pub const PROT_NONE: i32 = 0;
+/// 页可读。
+pub const PROT_READ: i32 = 0x1;
+/// 页可写。
+pub const PROT_WRITE: i32 = 0x2;
+/// 页可执行。
+pub const PROT_EXEC: i32 = 0x4;
+
+/// 修改对其他映射者可见，并写回文件。
+pub const MAP_SHARED: i32 = 0x01;
+/// 写时私有，修改不写回文件。
+pub const MAP_PRIVATE: i32 = 0x02;
+/// 严格使用给定的地址。
+pub const MAP_FIXED: i32 = 0x10;
+/// 不对应文件，内容初始化为 0。
+pub const MAP_ANONYMOUS: i32 = 0x20;
+
+/// 异步写回。
+pub const MS_ASYNC: i32 = 1;
+/// 使其他映射失效。
+pub const MS_INVALIDATE: i32 = 2;
+/// 同步写回。
+pub const MS_SYNC: i32 = 4;
@@ -0,0 +1,109 @@
+//! see <https://github.com/torvalds/linux/blob/master/include/uapi/linux/resource.h>.
+//!
+//! 内核只统计运行时间、调度次数和系统调用次数，[`Rusage`] 的其他字段总是 0。
+
+/// 统计调用者进程。
+pub const RUSAGE_SELF: isize = 0;
+/// 统计调用者已经结束的子进程。
+pub const RUSAGE_CHILDREN: isize = -1;
+/// 统计调用者线程。
+pub const RUSAGE_THREAD: isize = 1;
+
+/// `times` 的时间单位，每秒的时钟滴答数。
+pub const CLK_TCK: usize = 100;
+
+/// [`TaskInfo`] 按系统调用号统计的系统调用的数量，系统调用号不小于它的系统调用只计入总数。
+pub const MAX_SYSCALL_NUM: usize = 500;
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[repr(C)]
+pub struct TimeVal {
+    // seconds
+    pub tv_sec: usize,
+    // microseconds
+    pub tv_usec: usize,
+}
+
+impl TimeVal {
+    pub const fn from_microsecond(microsecond: usize) -> Self {
+        Self {
+            tv_sec: microsecond / 1_000_000,
+            tv_usec: microsecond % 1_000_000,
+        }
+    }
+
+    pub const fn as_microsecond(&self) -> usize {
+        self.tv_sec * 1_000_000 + self.tv_usec
+    }
+}
+
+/// see <https://man7.org/linux/man-pages/man2/getrusage.2.html>.
+#[derive(Clone, Copy, Default, Debug)]
+#[repr(C)]
+pub struct Rusage {
+    /// 在用户态运行的时间
+    pub ru_utime: TimeVal,
+    /// 内核为任务处理陷入的时间
+    pub ru_stime: TimeVal,
+    pub ru_maxrss: isize,
+    pub ru_ixrss: isize,
+    pub ru_idrss: isize,
+    pub ru_isrss: isize,
+    pub ru_minflt: isize,
+    pub ru_majflt: isize,
+    pub ru_nswap: isize,
+    pub ru_inblock: isize,
+    pub ru_oublock: isize,
+    pub ru_msgsnd: isize,
+    pub ru_msgrcv: isize,
+    pub ru_nsignals: isize,
+    /// 主动让出处理器的次数
+    pub ru_nvcsw: isize,
+    /// 用完时间片被抢占的次数
+    pub ru_nivcsw: isize,
+}
+
+/// see <https://man7.org/linux/man-pages/man2/times.2.html>.
+///
+/// 时间以 [`CLK_TCK`] 分之一秒为单位。
+#[derive(Clone, Copy, Default, Debug)]
+#[repr(C)]
+pub struct Tms {
+    /// 调用者进程在用户态运行的时间
+    pub tms_utime: isize,
+    /// 内核为调用者进程处理陷入的时间
+    pub tms_stime: isize,
+    /// 已经结束的子进程在用户态运行的时间
+    pub tms_cutime: isize,
+    /// 内核为已经结束的子进程处理陷入的时间
+    pub tms_cstime: isize,
+}
+
+/// 进程的运行统计，类似 rCore 实验中的 `sys_task_info`。
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct TaskInfo {
+    /// 在用户态运行的时间，以微秒为单位
+    pub utime: usize,
+    /// 内核为进程处理陷入的时间，以微秒为单位
+    pub stime: usize,
+    /// 被调度运行的次数
+    pub switches: usize,
+    /// 用完时间片被抢占的次数
+    pub preemptions: usize,
+    /// 系统调用的总数
+    pub syscalls: usize,
+    /// 按系统调用号统计的系统调用次数
+    pub syscall_times: [u32; MAX_SYSCALL_NUM],
+}
+
+impl TaskInfo {
+    pub const ZERO: Self = Self {
+        utime: 0,
+        stime: 0,
+        switches: 0,
+        preemptions: 0,
+        syscalls: 0,
+        syscall_times: [0; MAX_SYSCALL_NUM],
+    };
+}
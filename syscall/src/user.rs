@@ -1,4 +1,4 @@
-use crate::{ClockId, SignalAction, SignalNo, SyscallId, TimeSpec};
+use crate::{ClockId, Rusage, SignalAction, SignalNo, SyscallId, TaskInfo, TimeSpec, Tms, EAGAIN};
 use bitflags::*;
 use native::*;
 
@@ -13,6 +13,18 @@ pub fn read(fd: usize, buffer: &[u8]) -> isize {
     unsafe { syscall3(SyscallId::READ, fd, buffer.as_ptr() as _, buffer.len()) }
 }
 
+/// see <https://man7.org/linux/man-pages/man2/ioctl.2.html>.
+#[inline]
+pub fn ioctl(fd: usize, request: usize, arg: usize) -> isize {
+    unsafe { syscall3(SyscallId::IOCTL, fd, request, arg) }
+}
+
+/// see <https://man7.org/linux/man-pages/man2/syslog.2.html>.
+#[inline]
+pub fn syslog(ty: usize, buf: &mut [u8]) -> isize {
+    unsafe { syscall3(SyscallId::SYSLOG, ty, buf.as_mut_ptr() as _, buf.len()) }
+}
+
 bitflags! {
     pub struct OpenFlags: u32 {
         const RDONLY = 0;
@@ -51,6 +63,14 @@ pub fn sched_yield() -> isize {
     unsafe { syscall0(SyscallId::SCHED_YIELD) }
 }
 
+/// 设置调用者的调度优先级，成功时返回 `prio`。
+///
+/// 与 rCore 实验的 `sys_set_priority` 一致，借用 Linux `setpriority` 的系统调用号，但只有一个参数。
+#[inline]
+pub fn set_priority(prio: isize) -> isize {
+    unsafe { syscall1(SyscallId::SETPRIORITY, prio as _) }
+}
+
 /// see <https://man7.org/linux/man-pages/man2/clock_gettime.2.html>.
 #[inline]
 pub fn clock_gettime(clockid: ClockId, tp: *mut TimeSpec) -> isize {
@@ -65,32 +85,150 @@ pub fn exec(path: &str) -> isize {
     unsafe { syscall2(SyscallId::EXECVE, path.as_ptr() as usize, path.len()) }
 }
 
-pub fn wait(exit_code_ptr: *mut i32) -> isize {
+/// see <https://man7.org/linux/man-pages/man2/wait4.2.html>.
+///
+/// `pid` 为 -1 时等待任意子进程，`status` 得到 Linux 格式的等待状态，见 [`WIFEXITED`] 等。
+#[inline]
+pub fn wait4(pid: isize, status: *mut i32, options: usize) -> isize {
+    unsafe { syscall3(SyscallId::WAIT4, pid as usize, status as usize, options) }
+}
+
+/// 等待任意一个子进程结束。
+#[inline]
+pub fn wait(status: *mut i32) -> isize {
+    waitpid(-1, status)
+}
+
+/// 等待子进程结束，被信号打断时重新等待。
+pub fn waitpid(pid: isize, status: *mut i32) -> isize {
     loop {
-        match unsafe { syscall2(SyscallId::WAIT4, usize::MAX, exit_code_ptr as usize) } {
-            -2 => {
-                sched_yield();
-            }
-            exit_pid => return exit_pid,
+        match wait4(pid, status, 0) {
+            ret if ret == -EAGAIN => continue,
+            ret => return ret,
         }
     }
 }
 
-pub fn waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
-    loop {
-        match unsafe { syscall2(SyscallId::WAIT4, pid as usize, exit_code_ptr as usize) } {
-            -2 => {
-                sched_yield();
-            }
-            exit_pid => return exit_pid,
-        }
+/// see <https://man7.org/linux/man-pages/man2/brk.2.html>.
+///
+/// 与系统调用一致，返回调整后的程序断点，失败时返回原来的程序断点。
+#[inline]
+pub fn brk(addr: usize) -> isize {
+    unsafe { syscall1(SyscallId::BRK, addr) }
+}
+
+/// see <https://man7.org/linux/man-pages/man2/mmap.2.html>.
+#[inline]
+pub fn mmap(addr: usize, length: usize, prot: i32, flags: i32, fd: i32, offset: usize) -> isize {
+    unsafe {
+        syscall6(
+            SyscallId::MMAP,
+            addr,
+            length,
+            prot as _,
+            flags as _,
+            fd as _,
+            offset,
+        )
     }
 }
 
+/// see <https://man7.org/linux/man-pages/man2/munmap.2.html>.
+#[inline]
+pub fn munmap(addr: usize, length: usize) -> isize {
+    unsafe { syscall2(SyscallId::MUNMAP, addr, length) }
+}
+
+/// see <https://man7.org/linux/man-pages/man2/msync.2.html>.
+#[inline]
+pub fn msync(addr: usize, length: usize, flags: i32) -> isize {
+    unsafe { syscall3(SyscallId::MSYNC, addr, length, flags as _) }
+}
+
+/// see <https://man7.org/linux/man-pages/man2/shmget.2.html>.
+#[inline]
+pub fn shmget(key: usize, size: usize, flags: usize) -> isize {
+    unsafe { syscall3(SyscallId::SHMGET, key, size, flags) }
+}
+
+/// see <https://man7.org/linux/man-pages/man2/shmat.2.html>.
+#[inline]
+pub fn shmat(shmid: usize, addr: usize, flags: usize) -> isize {
+    unsafe { syscall3(SyscallId::SHMAT, shmid, addr, flags) }
+}
+
+/// see <https://man7.org/linux/man-pages/man2/shmdt.2.html>.
+#[inline]
+pub fn shmdt(addr: usize) -> isize {
+    unsafe { syscall1(SyscallId::SHMDT, addr) }
+}
+
+/// see <https://man7.org/linux/man-pages/man2/shmctl.2.html>.
+#[inline]
+pub fn shmctl(shmid: usize, cmd: usize) -> isize {
+    unsafe { syscall3(SyscallId::SHMCTL, shmid, cmd, 0) }
+}
+
 pub fn getpid() -> isize {
     unsafe { syscall0(SyscallId::GETPID) }
 }
 
+/// see <https://man7.org/linux/man-pages/man2/getppid.2.html>.
+///
+/// 没有父进程时返回 0。
+#[inline]
+pub fn getppid() -> isize {
+    unsafe { syscall0(SyscallId::GETPPID) }
+}
+
+/// see <https://man7.org/linux/man-pages/man2/setpgid.2.html>.
+///
+/// `pid` 为 0 时指调用者，`pgid` 为 0 时新建以 `pid` 为组长的进程组。
+#[inline]
+pub fn setpgid(pid: isize, pgid: isize) -> isize {
+    unsafe { syscall2(SyscallId::SETPGID, pid as _, pgid as _) }
+}
+
+/// see <https://man7.org/linux/man-pages/man2/getpgid.2.html>.
+#[inline]
+pub fn getpgid(pid: isize) -> isize {
+    unsafe { syscall1(SyscallId::GETPGID, pid as _) }
+}
+
+/// see <https://man7.org/linux/man-pages/man2/setsid.2.html>.
+#[inline]
+pub fn setsid() -> isize {
+    unsafe { syscall0(SyscallId::SETSID) }
+}
+
+/// see <https://man7.org/linux/man-pages/man2/getrusage.2.html>.
+#[inline]
+pub fn getrusage(who: isize, usage: &mut Rusage) -> isize {
+    unsafe { syscall2(SyscallId::GETRUSAGE, who as _, usage as *mut _ as _) }
+}
+
+/// see <https://man7.org/linux/man-pages/man2/times.2.html>.
+///
+/// 返回开机以来的时钟滴答数。
+#[inline]
+pub fn times(buf: &mut Tms) -> isize {
+    unsafe { syscall1(SyscallId::TIMES, buf as *mut _ as _) }
+}
+
+/// 读取进程 `pid` 的运行统计，`pid` 为 0 时读取调用者进程。
+#[inline]
+pub fn task_info(pid: isize, info: &mut TaskInfo) -> isize {
+    unsafe { syscall2(SyscallId::TASK_INFO, pid as _, info as *mut _ as _) }
+}
+
+/// 设置进程 Id 的上限（不含），返回原来的上限，`max` 为 0 时只查询。
+///
+/// 类似 Linux 的 `/proc/sys/kernel/pid_max`，已经分配的 Id 不受影响。
+#[inline]
+pub fn pid_max(max: usize) -> isize {
+    unsafe { syscall1(SyscallId::PID_MAX, max) }
+}
+
 #[inline]
 pub fn kill(pid: isize, signum: SignalNo) -> isize {
     unsafe { syscall2(SyscallId::KILL, pid as _, signum as _) }
@@ -0,0 +1,65 @@
+//! see <https://man7.org/linux/man-pages/man2/wait4.2.html>.
+//!
+//! 内核用非负数记录正常退出的进程的退出码，用信号的相反数记录被信号杀死的进程，
+//! `wait4` 时编码成 Linux 格式的等待状态。
+//!
+//! 内核不记录让进程暂停的信号，暂停的子进程总是报告为被 `SIGSTOP` 暂停。
+#![allow(non_snake_case)]
+
+/// 没有已经结束的子进程时立即返回 0，不阻塞。
+pub const WNOHANG: usize = 1;
+/// 也报告暂停了的子进程。
+pub const WUNTRACED: usize = 2;
+
+/// 把内核记录的退出码编码成等待状态。
+#[inline]
+pub const fn wait_status(exit_code: isize) -> i32 {
+    if exit_code < 0 {
+        (-exit_code) as i32 & 0x7f
+    } else {
+        (exit_code as i32 & 0xff) << 8
+    }
+}
+
+/// 被信号 `signum` 暂停的子进程的等待状态。
+#[inline]
+pub const fn stop_status(signum: i32) -> i32 {
+    0x7f | (signum & 0xff) << 8
+}
+
+/// 子进程正常退出。
+#[inline]
+pub const fn WIFEXITED(status: i32) -> bool {
+    status & 0x7f == 0
+}
+
+/// 正常退出的子进程的退出码。
+#[inline]
+pub const fn WEXITSTATUS(status: i32) -> i32 {
+    (status >> 8) & 0xff
+}
+
+/// 子进程被信号杀死。
+#[inline]
+pub const fn WIFSIGNALED(status: i32) -> bool {
+    let sig = status & 0x7f;
+    sig != 0 && sig != 0x7f
+}
+
+/// 杀死子进程的信号。
+#[inline]
+pub const fn WTERMSIG(status: i32) -> i32 {
+    status & 0x7f
+}
+
+/// 子进程被暂停。
+#[inline]
+pub const fn WIFSTOPPED(status: i32) -> bool {
+    status & 0xff == 0x7f
+}
+
+/// 让子进程暂停的信号。
+#[inline]
+pub const fn WSTOPSIG(status: i32) -> i32 {
+    WEXITSTATUS(status)
+}
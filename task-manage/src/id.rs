@@ -1,16 +1,93 @@
 use core::sync::atomic::{AtomicUsize, Ordering};
 
+/// 每种 Id 的最大上限，与 Linux 默认的 `pid_max` 相同
+pub const MAX_ID: usize = 32768;
+
+const BITS: usize = usize::BITS as usize;
+
+/// 用位图分配 Id，释放的 Id 可以重新分配
+///
+/// 从上一次分配的 Id 之后循环查找空闲的 Id，刚释放的 Id 要等分配绕回一圈才会再用到，
+/// 用户程序持有的过期 Id 在此之前不会指向新的任务。
+pub struct IdAllocator {
+    bits: [AtomicUsize; MAX_ID / BITS],
+    // 下一次从这里开始查找
+    next: AtomicUsize,
+    // 最小的 Id
+    min: usize,
+    // Id 的上限，不含
+    max: AtomicUsize,
+}
+
+impl IdAllocator {
+    /// 新建分配器，分配不小于 `min` 的 Id
+    pub const fn new(min: usize) -> Self {
+        Self {
+            bits: [const { AtomicUsize::new(0) }; MAX_ID / BITS],
+            next: AtomicUsize::new(min),
+            min,
+            max: AtomicUsize::new(MAX_ID),
+        }
+    }
+    /// 分配一个空闲的 Id，Id 用完时返回 None
+    pub fn alloc(&self) -> Option<usize> {
+        let max = self.max.load(Ordering::Relaxed);
+        let len = max.checked_sub(self.min)?;
+        let start = self.next.load(Ordering::Relaxed).max(self.min) - self.min;
+        for i in 0..len {
+            let id = self.min + (start + i) % len;
+            let bit = 1 << (id % BITS);
+            let word = &self.bits[id / BITS];
+            if word.load(Ordering::Relaxed) & bit == 0
+                && word.fetch_or(bit, Ordering::AcqRel) & bit == 0
+            {
+                self.next.store(id + 1, Ordering::Relaxed);
+                return Some(id);
+            }
+        }
+        None
+    }
+    /// 释放 Id，之后可以重新分配
+    pub fn dealloc(&self, id: usize) {
+        if id < MAX_ID {
+            self.bits[id / BITS].fetch_and(!(1 << (id % BITS)), Ordering::AcqRel);
+        }
+    }
+    /// 设置 Id 的上限（不含），不超过 [`MAX_ID`]；已经分配的 Id 不受影响
+    pub fn set_max(&self, max: usize) {
+        self.max.store(max.min(MAX_ID), Ordering::Relaxed);
+    }
+    /// Id 的上限，不含
+    pub fn max(&self) -> usize {
+        self.max.load(Ordering::Relaxed)
+    }
+}
+
+// 与 Linux 一样从 1 开始，0 表示没有父进程
+static PIDS: IdAllocator = IdAllocator::new(1);
+static TIDS: IdAllocator = IdAllocator::new(0);
+static CIDS: IdAllocator = IdAllocator::new(0);
+
 /// 进程 Id
 #[derive(Eq, PartialEq, Debug, Clone, Copy, Hash, Ord, PartialOrd)]
 pub struct ProcId(usize);
 
 impl ProcId {
-    ///
-    pub fn new() -> Self {
-        // 任务编号计数器，任务编号自增
-        static PID_COUNTER: AtomicUsize = AtomicUsize::new(0);
-        let id = PID_COUNTER.fetch_add(1, Ordering::Relaxed);
-        Self(id)
+    /// 分配一个空闲的进程 Id，用完时返回 None
+    pub fn new() -> Option<Self> {
+        PIDS.alloc().map(Self)
+    }
+    /// 释放进程 Id，进程被回收之后才能释放
+    pub fn release(self) {
+        PIDS.dealloc(self.0);
+    }
+    /// 设置进程 Id 的上限（不含）
+    pub fn set_max(max: usize) {
+        PIDS.set_max(max);
+    }
+    /// 进程 Id 的上限，不含
+    pub fn max() -> usize {
+        PIDS.max()
     }
     ///
     pub fn from_usize(v: usize) -> Self {
@@ -27,12 +104,17 @@ impl ProcId {
 pub struct ThreadId(usize);
 
 impl ThreadId {
-    ///
-    pub fn new() -> Self {
-        // 任务编号计数器，任务编号自增
-        static TID_COUNTER: AtomicUsize = AtomicUsize::new(0);
-        let id = TID_COUNTER.fetch_add(1, Ordering::Relaxed);
-        Self(id)
+    /// 分配一个空闲的线程 Id，用完时返回 None
+    pub fn new() -> Option<Self> {
+        TIDS.alloc().map(Self)
+    }
+    /// 释放线程 Id，线程被回收之后才能释放
+    pub fn release(self) {
+        TIDS.dealloc(self.0);
+    }
+    /// 设置线程 Id 的上限（不含）
+    pub fn set_max(max: usize) {
+        TIDS.set_max(max);
     }
     ///
     pub fn from_usize(v: usize) -> Self {
@@ -49,12 +131,17 @@ impl ThreadId {
 pub struct CoroId(usize);
 
 impl CoroId {
-    ///
-    pub fn new() -> Self {
-        // 任务编号计数器，任务编号自增
-        static CID_COUNTER: AtomicUsize = AtomicUsize::new(0);
-        let id = CID_COUNTER.fetch_add(1, Ordering::Relaxed);
-        Self(id)
+    /// 分配一个空闲的协程 Id，用完时返回 None
+    pub fn new() -> Option<Self> {
+        CIDS.alloc().map(Self)
+    }
+    /// 释放协程 Id
+    pub fn release(self) {
+        CIDS.dealloc(self.0);
+    }
+    /// 设置协程 Id 的上限（不含）
+    pub fn set_max(max: usize) {
+        CIDS.set_max(max);
     }
     ///
     pub fn from_usize(v: usize) -> Self {
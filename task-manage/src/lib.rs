@@ -8,10 +8,12 @@ extern crate alloc;
 mod id;
 mod manager;
 mod scheduler;
+mod stats;
 
 pub use id::*;
 pub use manager::Manage;
-pub use scheduler::Schedule;
+pub use scheduler::{Cfs, Mlfq, RoundRobin, Schedule, Stride, DEFAULT_PRIORITY};
+pub use stats::TaskStats;
 
 #[cfg(feature = "proc")]
 mod proc_manage;
@@ -1,9 +1,11 @@
 use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
 
 use super::id::ProcId;
 use super::manager::Manage;
 use super::scheduler::Schedule;
 use super::ProcRel;
+use super::TaskStats;
 use core::marker::PhantomData;
 
 /// ProcManager 数据结构，只管理进程以及进程之间的父子关系
@@ -16,6 +18,10 @@ pub struct PManager<P, MP: Manage<P, ProcId> + Schedule<ProcId>> {
     manager: Option<MP>,
     // 当前正在运行的进程 ID
     current: Option<ProcId>,
+    // init 进程，收养结束的进程留下的子进程
+    init: Option<ProcId>,
+    // 已经回收但还被用作进程组或会话 Id 的进程 Id
+    reaped: Vec<ProcId>,
     phantom_data: PhantomData<P>,
 }
 
@@ -26,13 +32,50 @@ impl<P, MP: Manage<P, ProcId> + Schedule<ProcId>> PManager<P, MP> {
             rel_map: BTreeMap::new(),
             manager: None,
             current: None,
+            init: None,
+            reaped: Vec::new(),
             phantom_data: PhantomData::<P>,
         }
     }
+    /// 设置 init 进程，结束的进程留下的子进程都交给它
+    #[inline]
+    pub fn set_init(&mut self, id: ProcId) {
+        self.init = Some(id);
+    }
+    /// 进程的父进程，父进程不存在时返回 None
+    pub fn parent(&self, id: ProcId) -> Option<ProcId> {
+        let parent = self.rel_map.get(&id)?.parent;
+        self.rel_map.contains_key(&parent).then_some(parent)
+    }
+    /// init 进程收养子进程；init 进程自己结束时，子进程不再有父进程
+    fn adopt_orphans(&mut self, children: Vec<ProcId>) {
+        let init = self.init.filter(|init| self.rel_map.contains_key(init));
+        for child in children {
+            self.rel_map.get_mut(&child).unwrap().parent = init.unwrap_or(ProcId::from_usize(0));
+            if let Some(init) = init {
+                self.rel_map.get_mut(&init).unwrap().add_child(child);
+            }
+        }
+    }
+    /// 释放已经回收的进程的 Id；还有进程组或会话以它为 Id 时，推迟到不再使用时释放
+    fn release_pids(&mut self, ids: impl IntoIterator<Item = ProcId>) {
+        self.reaped.extend(ids);
+        let rel_map = &self.rel_map;
+        self.reaped.retain(|&id| {
+            let in_use = rel_map.values().any(|rel| rel.pgid == id || rel.sid == id);
+            if !in_use {
+                id.release();
+            }
+            in_use
+        });
+    }
     /// 找到下一个进程
     pub fn find_next(&mut self) -> Option<&mut P> {
         if let Some(id) = self.manager.as_mut().unwrap().fetch() {
             if let Some(task) = self.manager.as_mut().unwrap().get_mut(id) {
+                if let Some(rel) = self.rel_map.get_mut(&id) {
+                    rel.stats.switches += 1;
+                }
                 self.current = Some(id);
                 Some(task)
             } else {
@@ -42,6 +85,45 @@ impl<P, MP: Manage<P, ProcId> + Schedule<ProcId>> PManager<P, MP> {
             None
         }
     }
+    /// 当前任务用完了一个时钟中断周期，返回是否应该让出处理器
+    pub fn tick_current(&mut self) -> bool {
+        let Some(id) = self.current else {
+            return false;
+        };
+        let expired = self.manager.as_mut().unwrap().tick(id);
+        if expired {
+            self.rel_map.get_mut(&id).unwrap().stats.preemptions += 1;
+        }
+        expired
+    }
+    /// 当前进程发起了系统调用 `syscall_id`
+    pub fn count_syscall(&mut self, syscall_id: usize) {
+        if let Some(rel) = self.current.and_then(|id| self.rel_map.get_mut(&id)) {
+            rel.stats.count_syscall(syscall_id);
+        }
+    }
+    /// 把一段在用户态和内核态运行的时间记到进程上，进程已经结束时什么也不做
+    pub fn charge(&mut self, id: ProcId, user_time: usize, system_time: usize) {
+        if let Some(rel) = self.rel_map.get_mut(&id) {
+            rel.stats.user_time += user_time;
+            rel.stats.system_time += system_time;
+        }
+    }
+    /// 进程的运行统计，进程不存在时返回 None
+    #[inline]
+    pub fn stats(&self, id: ProcId) -> Option<&TaskStats> {
+        self.rel_map.get(&id).map(|rel| &rel.stats)
+    }
+    /// 进程已经结束的子进程的运行统计之和，包括子进程结束时累计的它们的子进程，进程不存在时返回 None
+    #[inline]
+    pub fn children_stats(&self, id: ProcId) -> Option<&TaskStats> {
+        self.rel_map.get(&id).map(|rel| &rel.children_stats)
+    }
+    /// 设置任务的调度优先级，调度器不支持时返回 false
+    #[inline]
+    pub fn set_priority(&mut self, id: ProcId, priority: usize) -> bool {
+        self.manager.as_mut().unwrap().set_priority(id, priority)
+    }
     /// 设置 manager
     pub fn set_manager(&mut self, manager: MP) {
         self.manager = Some(manager);
@@ -52,6 +134,97 @@ impl<P, MP: Manage<P, ProcId> + Schedule<ProcId>> PManager<P, MP> {
         self.manager.as_mut().unwrap().add(id);
         self.current = None;
     }
+    /// 阻塞当前进程，直到有子进程结束或者被 [`PManager::wake`] 唤醒
+    pub fn make_current_waiting(&mut self) {
+        let id = self.current.unwrap();
+        self.rel_map.get_mut(&id).unwrap().waiting = true;
+        self.current = None;
+    }
+    /// 唤醒阻塞在 wait 上的进程，进程没有在等待时什么也不做
+    pub fn wake(&mut self, id: ProcId) {
+        if let Some(rel) = self.rel_map.get_mut(&id) {
+            if core::mem::take(&mut rel.waiting) {
+                self.manager.as_mut().unwrap().add(id);
+            }
+        }
+    }
+    /// 暂停当前进程，直到被 [`PManager::resume`] 继续运行，并通知父进程
+    pub fn make_current_stopped(&mut self) {
+        let id = self.current.take().unwrap();
+        let current_rel = self.rel_map.get_mut(&id).unwrap();
+        current_rel.stopped = true;
+        let parent_pid = current_rel.parent;
+        if let Some(parent_rel) = self.rel_map.get_mut(&parent_pid) {
+            if !parent_rel.stopped_children.contains(&id) {
+                parent_rel.stopped_children.push(id);
+            }
+            self.wake(parent_pid);
+        }
+    }
+    /// 让暂停的进程继续运行，进程没有暂停时什么也不做
+    pub fn resume(&mut self, id: ProcId) {
+        if let Some(rel) = self.rel_map.get_mut(&id) {
+            if core::mem::take(&mut rel.stopped) {
+                let parent_pid = rel.parent;
+                self.manager.as_mut().unwrap().add(id);
+                if let Some(parent_rel) = self.rel_map.get_mut(&parent_pid) {
+                    parent_rel.stopped_children.retain(|&child| child != id);
+                }
+            }
+        }
+    }
+    /// 进程所在的进程组，进程不存在时返回 None
+    #[inline]
+    pub fn pgid(&self, id: ProcId) -> Option<ProcId> {
+        self.rel_map.get(&id).map(|rel| rel.pgid)
+    }
+    /// 进程所在的会话，进程不存在时返回 None
+    #[inline]
+    pub fn sid(&self, id: ProcId) -> Option<ProcId> {
+        self.rel_map.get(&id).map(|rel| rel.sid)
+    }
+    /// 把进程移到同一会话中的进程组 `pgid`，`pgid` 等于 `id` 时以进程为组长新建进程组；会话首进程不能改变进程组
+    pub fn setpgid(&mut self, id: ProcId, pgid: ProcId) -> bool {
+        let Some(sid) = self.sid(id) else {
+            return false;
+        };
+        if sid == id
+            || (pgid != id
+                && !self
+                    .rel_map
+                    .values()
+                    .any(|rel| rel.pgid == pgid && rel.sid == sid))
+        {
+            return false;
+        }
+        self.rel_map.get_mut(&id).unwrap().pgid = pgid;
+        // 原来的进程组可能因此不再使用
+        self.release_pids([]);
+        true
+    }
+    /// 以进程为首新建会话和进程组，进程已经是进程组组长时失败
+    pub fn setsid(&mut self, id: ProcId) -> bool {
+        if self.rel_map.values().any(|rel| rel.pgid == id) {
+            return false;
+        }
+        match self.rel_map.get_mut(&id) {
+            Some(rel) => {
+                rel.pgid = id;
+                rel.sid = id;
+                self.release_pids([]);
+                true
+            }
+            None => false,
+        }
+    }
+    /// 进程组中的所有进程
+    pub fn group(&self, pgid: ProcId) -> Vec<ProcId> {
+        self.rel_map
+            .iter()
+            .filter(|(_, rel)| rel.pgid == pgid)
+            .map(|(&id, _)| id)
+            .collect()
+    }
     /// 结束当前进程，只会删除进程的内容，以及与当前进程相关的关系
     pub fn make_current_exited(&mut self, exit_code: isize) {
         let id = self.current.unwrap();
@@ -59,28 +232,45 @@ impl<P, MP: Manage<P, ProcId> + Schedule<ProcId>> PManager<P, MP> {
         let current_rel = self.rel_map.remove(&id).unwrap();
         let parent_pid = current_rel.parent;
         let children = current_rel.children;
-        // 从父进程中删除当前进程
+        // 已经结束但没有被等待的子进程随当前进程一起回收
+        let mut reaped: Vec<ProcId> = current_rel
+            .dead_children
+            .iter()
+            .map(|&(id, _)| id)
+            .collect();
+        // 从父进程中删除当前进程，唤醒等待子进程结束的父进程；没有父进程时当前进程直接回收
         if let Some(parent_rel) = self.rel_map.get_mut(&parent_pid) {
             parent_rel.del_child(id, exit_code);
+            // 进程和它的子进程的运行统计计入父进程
+            parent_rel.children_stats.add(&current_rel.stats);
+            parent_rel.children_stats.add(&current_rel.children_stats);
+            self.wake(parent_pid);
+        } else {
+            reaped.push(id);
         }
-        // 把当前进程的所有子进程转移到 0 号进程
-        for i in children {
-            self.rel_map.get_mut(&i).unwrap().parent = ProcId::from_usize(0);
-            self.rel_map
-                .get_mut(&ProcId::from_usize(0))
-                .unwrap()
-                .add_child(i);
-        }
+        // 还在运行的子进程交给 init 进程
+        self.adopt_orphans(children);
+        self.release_pids(reaped);
         self.current = None;
     }
     /// 添加进程，需要指明创建的进程的父进程 Id
     pub fn add(&mut self, id: ProcId, task: P, parent: ProcId) {
         self.manager.as_mut().unwrap().insert(id, task);
         self.manager.as_mut().unwrap().add(id);
-        if let Some(parent_relation) = self.rel_map.get_mut(&parent) {
-            parent_relation.add_child(id);
-        }
-        self.rel_map.insert(id, ProcRel::new(parent));
+        let (pgid, sid) = match self.rel_map.get_mut(&parent) {
+            Some(parent_relation) => {
+                parent_relation.add_child(id);
+                (parent_relation.pgid, parent_relation.sid)
+            }
+            // 没有父进程的进程自成一个会话
+            None => (id, id),
+        };
+        self.rel_map.insert(id, ProcRel::new(parent, pgid, sid));
+    }
+    /// 当前进程的 Id，不在运行任何进程时返回 `None`
+    #[inline]
+    pub fn current_id(&self) -> Option<ProcId> {
+        self.current
     }
     /// 当前进程
     pub fn current(&mut self) -> Option<&mut P> {
@@ -93,13 +283,28 @@ impl<P, MP: Manage<P, ProcId> + Schedule<ProcId>> PManager<P, MP> {
         self.manager.as_mut().unwrap().get_mut(id)
     }
     /// wait 系统调用，返回结束的子进程 id 和 exit_code，正在运行的子进程不返回 None，返回 (-2, -1)
+    ///
+    /// 返回的子进程已经回收，它的 Id 可能被分配给新的进程
     pub fn wait(&mut self, child_pid: ProcId) -> Option<(ProcId, isize)> {
         let id = self.current.unwrap();
         let current_rel = self.rel_map.get_mut(&id).unwrap();
-        if child_pid.get_usize() == usize::MAX {
+        let ans = if child_pid.get_usize() == usize::MAX {
             current_rel.wait_any_child()
         } else {
             current_rel.wait_child(child_pid)
+        };
+        if let Some((dead_pid, _)) = ans.filter(|(id, _)| id.get_usize() != -2isize as usize) {
+            self.release_pids([dead_pid]);
         }
+        ans
+    }
+    /// 取出当前进程一个暂停了但还没有报告的子进程，`child_pid` 为 usize::MAX 时取任意一个
+    #[inline]
+    pub fn wait_stopped(&mut self, child_pid: ProcId) -> Option<ProcId> {
+        let id = self.current.unwrap();
+        self.rel_map
+            .get_mut(&id)
+            .unwrap()
+            .take_stopped_child(child_pid)
     }
 }
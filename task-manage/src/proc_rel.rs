@@ -1,4 +1,5 @@
 use super::id::ProcId;
+use super::TaskStats;
 use alloc::vec::Vec;
 
 /// 进程之间的关系，通过进程的 Id 来查询这个关系
@@ -10,15 +11,36 @@ pub struct ProcRel {
     pub children: Vec<ProcId>,
     /// 已经结束的进程
     pub dead_children: Vec<(ProcId, isize)>,
+    /// 进程阻塞在 wait 上，有子进程结束时唤醒
+    pub waiting: bool,
+    /// 进程组 Id
+    pub pgid: ProcId,
+    /// 会话 Id
+    pub sid: ProcId,
+    /// 进程被暂停，等待 SIGCONT
+    pub stopped: bool,
+    /// 暂停了但还没有被 wait 报告的子进程
+    pub stopped_children: Vec<ProcId>,
+    /// 进程的运行统计
+    pub stats: TaskStats,
+    /// 已经结束的子进程的运行统计之和
+    pub children_stats: TaskStats,
 }
 
 impl ProcRel {
-    /// new/fork 创建进程时使用
-    pub fn new(parent_pid: ProcId) -> Self {
+    /// new/fork 创建进程时使用，进程组和会话与父进程相同
+    pub fn new(parent_pid: ProcId, pgid: ProcId, sid: ProcId) -> Self {
         Self {
             parent: parent_pid,
             children: Vec::new(),
             dead_children: Vec::new(),
+            waiting: false,
+            pgid,
+            sid,
+            stopped: false,
+            stopped_children: Vec::new(),
+            stats: TaskStats::new(),
+            children_stats: TaskStats::new(),
         }
     }
     /// 添加子进程 Id
@@ -36,6 +58,18 @@ impl ProcRel {
             let dead_child = self.children.remove(idx);
             self.dead_children.push((dead_child, exit_code));
         }
+        self.stopped_children.retain(|&id| id != child_pid);
+    }
+    /// 取出一个暂停了但还没有报告的子进程，`child_pid` 为 usize::MAX 时取任意一个
+    pub fn take_stopped_child(&mut self, child_pid: ProcId) -> Option<ProcId> {
+        let idx = if child_pid.get_usize() == usize::MAX {
+            self.stopped_children.len().checked_sub(1)?
+        } else {
+            self.stopped_children
+                .iter()
+                .position(|&id| id == child_pid)?
+        };
+        Some(self.stopped_children.remove(idx))
     }
     /// 等待任意一个结束的子进程，直接弹出 dead_children 队首，如果队列为空，则返回 -2
     pub fn wait_any_child(&mut self) -> Option<(ProcId, isize)> {
@@ -1,6 +1,7 @@
 use alloc::vec::Vec;
 
 use super::id::{ProcId, ThreadId};
+use super::TaskStats;
 
 /// 线程、进程之间的关系，通过进程的 Id 来查询这个关系
 #[cfg(feature = "thread")]
@@ -15,17 +16,38 @@ pub struct ProcThreadRel {
     pub threads: Vec<ThreadId>,
     /// 已经结束的线程
     pub dead_threads: Vec<(ThreadId, isize)>,
+    /// 阻塞在 wait 上的线程，有子进程结束时唤醒
+    pub waiters: Vec<ThreadId>,
+    /// 进程组 Id
+    pub pgid: ProcId,
+    /// 会话 Id
+    pub sid: ProcId,
+    /// 被暂停的线程，等待 SIGCONT
+    pub stopped: Vec<ThreadId>,
+    /// 暂停了但还没有被 wait 报告的子进程
+    pub stopped_children: Vec<ProcId>,
+    /// 已经结束的线程的运行统计之和，还在运行的线程的统计由线程管理器记录
+    pub stats: TaskStats,
+    /// 已经结束的子进程的运行统计之和
+    pub children_stats: TaskStats,
 }
 
 impl ProcThreadRel {
-    /// new/fork 创建进程时使用
-    pub fn new(parent_pid: ProcId) -> Self {
+    /// new/fork 创建进程时使用，进程组和会话与父进程相同
+    pub fn new(parent_pid: ProcId, pgid: ProcId, sid: ProcId) -> Self {
         Self {
             parent: parent_pid,
             children: Vec::new(),
             dead_children: Vec::new(),
             threads: Vec::new(),
             dead_threads: Vec::new(),
+            waiters: Vec::new(),
+            pgid,
+            sid,
+            stopped: Vec::new(),
+            stopped_children: Vec::new(),
+            stats: TaskStats::new(),
+            children_stats: TaskStats::new(),
         }
     }
     /// 添加子进程 Id
@@ -43,6 +65,18 @@ impl ProcThreadRel {
             let dead_child = self.children.remove(idx);
             self.dead_children.push((dead_child, exit_code));
         }
+        self.stopped_children.retain(|&id| id != child_pid);
+    }
+    /// 取出一个暂停了但还没有报告的子进程，`child_pid` 为 usize::MAX 时取任意一个
+    pub fn take_stopped_child(&mut self, child_pid: ProcId) -> Option<ProcId> {
+        let idx = if child_pid.get_usize() == usize::MAX {
+            self.stopped_children.len().checked_sub(1)?
+        } else {
+            self.stopped_children
+                .iter()
+                .position(|&id| id == child_pid)?
+        };
+        Some(self.stopped_children.remove(idx))
     }
     /// 等待任意一个结束的子进程，直接弹出 dead_children 队首，如果队列为空，则返回 -2
     pub fn wait_any_child(&mut self) -> Option<(ProcId, isize)> {
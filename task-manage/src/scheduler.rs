@@ -1,7 +0,0 @@
-/// Scheduler
-pub trait Schedule<I: Copy + Ord> {
-    /// 入队
-    fn add(&mut self, id: I);
-    /// 出队
-    fn fetch(&mut self) -> Option<I>;
-}
@@ -0,0 +1,95 @@
+use super::{Schedule, DEFAULT_PRIORITY};
+use alloc::collections::{BTreeMap, BTreeSet};
+
+/// 类 CFS 的虚拟运行时间调度。
+///
+/// 每个周期让运行的任务的虚拟运行时间增加 `NICE_0_WEIGHT * GRANULE / 权重`，总是调度虚拟运行时间最小的任务。
+/// 权重与优先级成正比，默认优先级的权重为 [`Cfs::NICE_0_WEIGHT`]。
+/// 新任务和重新就绪的任务不早于当前最小的虚拟运行时间，长时间阻塞的任务不会在醒来后独占处理器。
+pub struct Cfs<I> {
+    tasks: BTreeMap<I, Entry>,
+    /// 按虚拟运行时间排序的就绪任务。
+    ready: BTreeSet<(usize, I)>,
+    /// 调度出的任务的最小虚拟运行时间，单调增加。
+    min_vruntime: usize,
+}
+
+struct Entry {
+    vruntime: usize,
+    weight: usize,
+}
+
+impl<I> Cfs<I> {
+    /// 默认优先级的权重。
+    pub const NICE_0_WEIGHT: usize = 1024;
+    /// 一个周期对应的虚拟运行时间。
+    const GRANULE: usize = 1024;
+
+    /// 空的调度队列。
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            tasks: BTreeMap::new(),
+            ready: BTreeSet::new(),
+            min_vruntime: 0,
+        }
+    }
+}
+
+impl<I> Default for Cfs<I> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<I: Copy + Ord> Schedule<I> for Cfs<I> {
+    fn add(&mut self, id: I) {
+        let min_vruntime = self.min_vruntime;
+        let entry = self.tasks.entry(id).or_insert(Entry {
+            vruntime: min_vruntime,
+            weight: Self::NICE_0_WEIGHT,
+        });
+        entry.vruntime = entry.vruntime.max(min_vruntime);
+        self.ready.insert((entry.vruntime, id));
+    }
+
+    fn fetch(&mut self) -> Option<I> {
+        let (vruntime, id) = self.ready.pop_first()?;
+        self.min_vruntime = self.min_vruntime.max(vruntime);
+        Some(id)
+    }
+
+    /// 有就绪任务的虚拟运行时间更小时让出。
+    fn tick(&mut self, id: I) -> bool {
+        let Some(entry) = self.tasks.get_mut(&id) else {
+            return true;
+        };
+        entry.vruntime += Self::NICE_0_WEIGHT * Self::GRANULE / entry.weight;
+        self.ready
+            .first()
+            .is_some_and(|(vruntime, _)| *vruntime < entry.vruntime)
+    }
+
+    fn set_priority(&mut self, id: I, priority: usize) -> bool {
+        if priority == 0 {
+            return false;
+        }
+        let weight = (priority * Self::NICE_0_WEIGHT / DEFAULT_PRIORITY).max(1);
+        let min_vruntime = self.min_vruntime;
+        self.tasks
+            .entry(id)
+            .or_insert(Entry {
+                vruntime: min_vruntime,
+                weight,
+            })
+            .weight = weight;
+        true
+    }
+
+    fn remove(&mut self, id: I) {
+        if let Some(entry) = self.tasks.remove(&id) {
+            self.ready.remove(&(entry.vruntime, id));
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use super::Schedule;
+use alloc::collections::{BTreeMap, VecDeque};
+
+const LEVELS: usize = 4;
+
+/// 多级反馈队列调度。
+///
+/// 新任务进入最高级队列，总是先调度高级队列中的任务。第 `i` 级的任务累计运行 `2^i` 个周期后降一级，
+/// 主动让出处理器不会清零累计的时间，因此频繁让出也不能一直留在高级队列。
+/// 每 [`Mlfq::BOOST_PERIOD`] 个周期把所有任务提回最高级，避免低级队列中的任务饥饿。
+pub struct Mlfq<I> {
+    queues: [VecDeque<I>; LEVELS],
+    tasks: BTreeMap<I, Entry>,
+    /// 距上次提升经过的周期数。
+    ticks: usize,
+}
+
+#[derive(Clone, Copy, Default)]
+struct Entry {
+    level: usize,
+    /// 在当前级别累计运行的周期数。
+    used: usize,
+}
+
+impl<I> Mlfq<I> {
+    /// 队列的级数。
+    pub const LEVELS: usize = LEVELS;
+    /// 提升所有任务的周期。
+    pub const BOOST_PERIOD: usize = 64;
+
+    /// 空的调度队列。
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            queues: [
+                VecDeque::new(),
+                VecDeque::new(),
+                VecDeque::new(),
+                VecDeque::new(),
+            ],
+            tasks: BTreeMap::new(),
+            ticks: 0,
+        }
+    }
+}
+
+impl<I> Default for Mlfq<I> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<I: Copy + Ord> Mlfq<I> {
+    /// 把任务移到第 `level` 级，清零累计的时间。
+    fn move_to(&mut self, id: I, level: usize) {
+        let entry = self.tasks.entry(id).or_default();
+        let old = core::mem::replace(entry, Entry { level, used: 0 });
+        let queue = &mut self.queues[old.level];
+        if let Some(i) = queue.iter().position(|i| *i == id) {
+            queue.remove(i);
+            self.queues[level].push_back(id);
+        }
+    }
+
+    fn boost(&mut self) {
+        self.ticks = 0;
+        for entry in self.tasks.values_mut() {
+            *entry = Entry::default();
+        }
+        let (top, rest) = self.queues.split_first_mut().unwrap();
+        for queue in rest {
+            top.append(queue);
+        }
+    }
+}
+
+impl<I: Copy + Ord> Schedule<I> for Mlfq<I> {
+    fn add(&mut self, id: I) {
+        let level = self.tasks.entry(id).or_default().level;
+        self.queues[level].push_back(id);
+    }
+
+    fn fetch(&mut self) -> Option<I> {
+        self.queues.iter_mut().find_map(VecDeque::pop_front)
+    }
+
+    fn tick(&mut self, id: I) -> bool {
+        self.ticks += 1;
+        if self.ticks >= Self::BOOST_PERIOD {
+            self.boost();
+            return true;
+        }
+        let entry = self.tasks.entry(id).or_default();
+        entry.used += 1;
+        if entry.used >= 1 << entry.level {
+            entry.used = 0;
+            entry.level = (entry.level + 1).min(Self::LEVELS - 1);
+            true
+        } else {
+            // 更高级的队列中有任务时让出
+            self.queues[..entry.level].iter().any(|q| !q.is_empty())
+        }
+    }
+
+    /// 优先级 `p` 的任务移到第 `LEVELS - 1 - p` 级，`p` 不小于 `LEVELS - 1` 时移到最高级。
+    fn set_priority(&mut self, id: I, priority: usize) -> bool {
+        self.move_to(id, (Self::LEVELS - 1).saturating_sub(priority));
+        true
+    }
+
+    fn remove(&mut self, id: I) {
+        if let Some(entry) = self.tasks.remove(&id) {
+            self.queues[entry.level].retain(|i| *i != id);
+        }
+    }
+}
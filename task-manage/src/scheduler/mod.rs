@@ -0,0 +1,47 @@
+//! 任务调度。
+//!
+//! 调度队列中只有就绪的任务。正在运行的任务由管理器取出，让出处理器时重新 [`Schedule::add`]，
+//! 阻塞时不加回，退出时 [`Schedule::remove`]。调度器按任务 id 记录调度所需的状态，
+//! 阻塞的任务重新就绪时沿用原来的状态。
+
+mod cfs;
+mod mlfq;
+mod round_robin;
+mod stride;
+
+pub use cfs::Cfs;
+pub use mlfq::Mlfq;
+pub use round_robin::RoundRobin;
+pub use stride::Stride;
+
+/// 默认优先级。
+pub const DEFAULT_PRIORITY: usize = 16;
+
+/// Scheduler
+pub trait Schedule<I: Copy + Ord> {
+    /// 入队
+    fn add(&mut self, id: I);
+    /// 出队
+    fn fetch(&mut self) -> Option<I>;
+    /// 正在运行的任务 `id` 用完了一个时钟中断周期，返回是否应该让出处理器。
+    ///
+    /// 不计时的调度器每个周期都让出。
+    #[inline]
+    fn tick(&mut self, id: I) -> bool {
+        let _ = id;
+        true
+    }
+    /// 设置任务的优先级，越大越优先，返回是否设置成功。
+    ///
+    /// 不支持优先级的调度器总是返回 `false`。
+    #[inline]
+    fn set_priority(&mut self, id: I, priority: usize) -> bool {
+        let _ = (id, priority);
+        false
+    }
+    /// 删除任务：从调度队列中移除并丢弃它的调度状态，任务退出时调用。
+    #[inline]
+    fn remove(&mut self, id: I) {
+        let _ = id;
+    }
+}
@@ -0,0 +1,43 @@
+use super::Schedule;
+use alloc::collections::VecDeque;
+
+/// 时间片轮转调度。
+///
+/// 就绪的任务先进先出，每个时钟中断周期都让出处理器。
+pub struct RoundRobin<I> {
+    queue: VecDeque<I>,
+}
+
+impl<I> RoundRobin<I> {
+    /// 空的调度队列。
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            queue: VecDeque::new(),
+        }
+    }
+}
+
+impl<I> Default for RoundRobin<I> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<I: Copy + Ord> Schedule<I> for RoundRobin<I> {
+    #[inline]
+    fn add(&mut self, id: I) {
+        self.queue.push_back(id);
+    }
+
+    #[inline]
+    fn fetch(&mut self) -> Option<I> {
+        self.queue.pop_front()
+    }
+
+    #[inline]
+    fn remove(&mut self, id: I) {
+        self.queue.retain(|i| *i != id);
+    }
+}
@@ -0,0 +1,82 @@
+use super::{Schedule, DEFAULT_PRIORITY};
+use alloc::collections::{BTreeMap, BTreeSet};
+
+/// 步长调度。
+///
+/// 每个任务有一个行程值，每次调度选出行程值最小的任务，让它的行程值增加 `BIG_STRIDE / 优先级`。
+/// 长期来看任务得到的处理器时间与优先级成正比。新任务从当前最小的行程值开始，不会长期独占处理器。
+pub struct Stride<I> {
+    tasks: BTreeMap<I, Entry>,
+    /// 按行程值排序的就绪任务。
+    ready: BTreeSet<(usize, I)>,
+    /// 最近一次调度出的行程值。
+    pass: usize,
+}
+
+struct Entry {
+    pass: usize,
+    priority: usize,
+}
+
+impl<I> Stride<I> {
+    /// 行程值的步长基数。
+    pub const BIG_STRIDE: usize = 1 << 20;
+
+    /// 空的调度队列。
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            tasks: BTreeMap::new(),
+            ready: BTreeSet::new(),
+            pass: 0,
+        }
+    }
+}
+
+impl<I> Default for Stride<I> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<I: Copy + Ord> Schedule<I> for Stride<I> {
+    fn add(&mut self, id: I) {
+        let pass = self.pass;
+        let entry = self.tasks.entry(id).or_insert(Entry {
+            pass,
+            priority: DEFAULT_PRIORITY,
+        });
+        self.ready.insert((entry.pass, id));
+    }
+
+    fn fetch(&mut self) -> Option<I> {
+        let (pass, id) = self.ready.pop_first()?;
+        let entry = self.tasks.get_mut(&id).unwrap();
+        entry.pass += Self::BIG_STRIDE / entry.priority;
+        self.pass = pass;
+        Some(id)
+    }
+
+    /// 与 rCore 实验的 `sys_set_priority` 一致，优先级至少为 2。
+    ///
+    /// 行程值直接按大小比较，不考虑回绕：每次调度最多增加 `BIG_STRIDE / 2`，
+    /// `usize` 的行程值在可以想见的运行时间内不会溢出。
+    fn set_priority(&mut self, id: I, priority: usize) -> bool {
+        if priority < 2 {
+            return false;
+        }
+        let pass = self.pass;
+        self.tasks
+            .entry(id)
+            .or_insert(Entry { pass, priority })
+            .priority = priority;
+        true
+    }
+
+    fn remove(&mut self, id: I) {
+        if let Some(entry) = self.tasks.remove(&id) {
+            self.ready.remove(&(entry.pass, id));
+        }
+    }
+}
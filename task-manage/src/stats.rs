@@ -0,0 +1,59 @@
+use alloc::collections::BTreeMap;
+
+/// 任务的运行统计，时间的单位由内核决定
+#[derive(Clone, Default, Debug)]
+pub struct TaskStats {
+    /// 在用户态运行的时间
+    pub user_time: usize,
+    /// 内核为任务处理陷入的时间
+    pub system_time: usize,
+    /// 被调度运行的次数
+    pub switches: usize,
+    /// 用完时间片被抢占的次数
+    pub preemptions: usize,
+    // 系统调用号到调用次数的映射
+    syscalls: BTreeMap<usize, usize>,
+}
+
+impl TaskStats {
+    /// 新建空的统计
+    pub const fn new() -> Self {
+        Self {
+            user_time: 0,
+            system_time: 0,
+            switches: 0,
+            preemptions: 0,
+            syscalls: BTreeMap::new(),
+        }
+    }
+    /// 记录一次系统调用
+    #[inline]
+    pub fn count_syscall(&mut self, id: usize) {
+        *self.syscalls.entry(id).or_insert(0) += 1;
+    }
+    /// 系统调用 `id` 的次数
+    #[inline]
+    pub fn syscall_count(&self, id: usize) -> usize {
+        self.syscalls.get(&id).copied().unwrap_or(0)
+    }
+    /// 按系统调用号排列的调用次数，没有调用过的系统调用不出现
+    #[inline]
+    pub fn syscalls(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.syscalls.iter().map(|(&id, &count)| (id, count))
+    }
+    /// 主动让出处理器的次数，即被调度运行之后没有被抢占的次数
+    #[inline]
+    pub fn voluntary_switches(&self) -> usize {
+        self.switches.saturating_sub(self.preemptions)
+    }
+    /// 累加另一个任务的统计，用于汇总结束的线程或子进程
+    pub fn add(&mut self, other: &Self) {
+        self.user_time += other.user_time;
+        self.system_time += other.system_time;
+        self.switches += other.switches;
+        self.preemptions += other.preemptions;
+        for (id, count) in other.syscalls() {
+            *self.syscalls.entry(id).or_insert(0) += count;
+        }
+    }
+}
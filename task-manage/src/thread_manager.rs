@@ -7,6 +7,7 @@ use super::id::ProcId;
 use super::manager::Manage;
 use super::scheduler::Schedule;
 use super::ProcThreadRel;
+use super::TaskStats;
 use core::marker::PhantomData;
 
 #[cfg(feature = "thread")]
@@ -20,10 +21,16 @@ pub struct PThreadManager<P, T, MT: Manage<T, ThreadId> + Schedule<ThreadId>, MP
     proc_manager: Option<MP>,
     // 线程所属的进程之间的映射关系
     tid2pid: BTreeMap<ThreadId, ProcId>,
+    // 还在运行的线程的运行统计
+    stats: BTreeMap<ThreadId, TaskStats>,
     // 进程对象管理和调度
     manager: Option<MT>,
     // 当前正在运行的线程 ID
     current: Option<ThreadId>,
+    // init 进程，收养结束的进程留下的子进程
+    init: Option<ProcId>,
+    // 已经回收但还被用作进程组或会话 Id 的进程 Id
+    reaped: Vec<ProcId>,
     phantom_t: PhantomData<T>,
     phantom_p: PhantomData<P>,
 }
@@ -37,16 +44,54 @@ impl<P, T, MT: Manage<T, ThreadId> + Schedule<ThreadId>, MP: Manage<P, ProcId>>
             rel_map: BTreeMap::new(),
             proc_manager: None,
             tid2pid: BTreeMap::new(),
+            stats: BTreeMap::new(),
             manager: None,
             current: None,
+            init: None,
+            reaped: Vec::new(),
             phantom_t: PhantomData::<T>,
             phantom_p: PhantomData::<P>,
         }
     }
+    /// 设置 init 进程，结束的进程留下的子进程都交给它
+    #[inline]
+    pub fn set_init(&mut self, id: ProcId) {
+        self.init = Some(id);
+    }
+    /// 进程的父进程，父进程不存在时返回 None
+    pub fn parent(&self, id: ProcId) -> Option<ProcId> {
+        let parent = self.rel_map.get(&id)?.parent;
+        self.rel_map.contains_key(&parent).then_some(parent)
+    }
+    /// init 进程收养子进程；init 进程自己结束时，子进程不再有父进程
+    fn adopt_orphans(&mut self, children: Vec<ProcId>) {
+        let init = self.init.filter(|init| self.rel_map.contains_key(init));
+        for child in children {
+            self.rel_map.get_mut(&child).unwrap().parent = init.unwrap_or(ProcId::from_usize(0));
+            if let Some(init) = init {
+                self.rel_map.get_mut(&init).unwrap().add_child(child);
+            }
+        }
+    }
+    /// 释放已经回收的进程的 Id；还有进程组或会话以它为 Id 时，推迟到不再使用时释放
+    fn release_pids(&mut self, ids: impl IntoIterator<Item = ProcId>) {
+        self.reaped.extend(ids);
+        let rel_map = &self.rel_map;
+        self.reaped.retain(|&id| {
+            let in_use = rel_map.values().any(|rel| rel.pgid == id || rel.sid == id);
+            if !in_use {
+                id.release();
+            }
+            in_use
+        });
+    }
     /// 找到下一个进程
     pub fn find_next(&mut self) -> Option<&mut T> {
         if let Some(id) = self.manager.as_mut().unwrap().fetch() {
             if let Some(task) = self.manager.as_mut().unwrap().get_mut(id) {
+                if let Some(stats) = self.stats.get_mut(&id) {
+                    stats.switches += 1;
+                }
                 self.current = Some(id);
                 Some(task)
             } else {
@@ -56,6 +101,58 @@ impl<P, T, MT: Manage<T, ThreadId> + Schedule<ThreadId>, MP: Manage<P, ProcId>>
             None
         }
     }
+    /// 当前任务用完了一个时钟中断周期，返回是否应该让出处理器
+    pub fn tick_current(&mut self) -> bool {
+        let Some(id) = self.current else {
+            return false;
+        };
+        let expired = self.manager.as_mut().unwrap().tick(id);
+        if expired {
+            if let Some(stats) = self.stats.get_mut(&id) {
+                stats.preemptions += 1;
+            }
+        }
+        expired
+    }
+    /// 当前线程发起了系统调用 `syscall_id`
+    pub fn count_syscall(&mut self, syscall_id: usize) {
+        if let Some(stats) = self.current.and_then(|id| self.stats.get_mut(&id)) {
+            stats.count_syscall(syscall_id);
+        }
+    }
+    /// 把一段在用户态和内核态运行的时间记到线程上，线程已经结束时什么也不做
+    pub fn charge(&mut self, id: ThreadId, user_time: usize, system_time: usize) {
+        if let Some(stats) = self.stats.get_mut(&id) {
+            stats.user_time += user_time;
+            stats.system_time += system_time;
+        }
+    }
+    /// 线程的运行统计，线程已经结束时返回 None
+    #[inline]
+    pub fn thread_stats(&self, id: ThreadId) -> Option<&TaskStats> {
+        self.stats.get(&id)
+    }
+    /// 进程的运行统计，即它所有线程的统计之和，进程不存在时返回 None
+    pub fn proc_stats(&self, id: ProcId) -> Option<TaskStats> {
+        let rel = self.rel_map.get(&id)?;
+        let mut stats = rel.stats.clone();
+        for tid in &rel.threads {
+            if let Some(thread_stats) = self.stats.get(tid) {
+                stats.add(thread_stats);
+            }
+        }
+        Some(stats)
+    }
+    /// 进程已经结束的子进程的运行统计之和，包括子进程结束时累计的它们的子进程，进程不存在时返回 None
+    #[inline]
+    pub fn children_stats(&self, id: ProcId) -> Option<&TaskStats> {
+        self.rel_map.get(&id).map(|rel| &rel.children_stats)
+    }
+    /// 设置任务的调度优先级，调度器不支持时返回 false
+    #[inline]
+    pub fn set_priority(&mut self, id: ThreadId, priority: usize) -> bool {
+        self.manager.as_mut().unwrap().set_priority(id, priority)
+    }
     /// 设置 manager
     pub fn set_manager(&mut self, manager: MT) {
         self.manager = Some(manager);
@@ -77,9 +174,14 @@ impl<P, T, MT: Manage<T, ThreadId> + Schedule<ThreadId>, MP: Manage<P, ProcId>>
             self.manager.as_mut().unwrap().delete(id);
             // 线程结束时维护与父进程之间的关系
             let pid = self.tid2pid.remove(&id).unwrap();
+            let stats = self.stats.remove(&id);
             let mut flag = false;
             if let Some(current_rel) = self.rel_map.get_mut(&pid) {
                 current_rel.del_thread(id, exit_code);
+                // 结束的线程的运行统计计入进程
+                if let Some(stats) = stats {
+                    current_rel.stats.add(&stats);
+                }
                 // 如果线程数量为 0，则需要把当前线程所属的进程给删除掉（所有等待的线程都已经结束）
                 if current_rel.threads.is_empty() {
                     flag = true;
@@ -97,6 +199,113 @@ impl<P, T, MT: Manage<T, ThreadId> + Schedule<ThreadId>, MP: Manage<P, ProcId>>
             self.current = None;
         }
     }
+    /// 阻塞当前线程，直到所属进程有子进程结束或者被 [`PThreadManager::wake`] 唤醒
+    pub fn make_current_waiting(&mut self) {
+        if let Some(id) = self.current.take() {
+            let pid = self.tid2pid.get(&id).unwrap();
+            self.rel_map.get_mut(pid).unwrap().waiters.push(id);
+        }
+    }
+    /// 唤醒进程中所有阻塞在 wait 上的线程
+    pub fn wake(&mut self, id: ProcId) {
+        if let Some(rel) = self.rel_map.get_mut(&id) {
+            let manager = self.manager.as_mut().unwrap();
+            for tid in rel.waiters.drain(..) {
+                // 等待期间已经结束的线程不再调度
+                if manager.get_mut(tid).is_some() {
+                    manager.add(tid);
+                }
+            }
+        }
+    }
+    /// 暂停当前线程，直到所属进程被 [`PThreadManager::resume`] 继续运行；进程中第一个线程暂停时通知父进程
+    pub fn make_current_stopped(&mut self) {
+        if let Some(id) = self.current.take() {
+            let pid = *self.tid2pid.get(&id).unwrap();
+            let current_rel = self.rel_map.get_mut(&pid).unwrap();
+            current_rel.stopped.push(id);
+            if current_rel.stopped.len() > 1 {
+                return;
+            }
+            let parent_pid = current_rel.parent;
+            if let Some(parent_rel) = self.rel_map.get_mut(&parent_pid) {
+                if !parent_rel.stopped_children.contains(&pid) {
+                    parent_rel.stopped_children.push(pid);
+                }
+                self.wake(parent_pid);
+            }
+        }
+    }
+    /// 让进程中所有暂停的线程继续运行，进程没有暂停时什么也不做
+    pub fn resume(&mut self, id: ProcId) {
+        if let Some(rel) = self.rel_map.get_mut(&id) {
+            if rel.stopped.is_empty() {
+                return;
+            }
+            let parent_pid = rel.parent;
+            let manager = self.manager.as_mut().unwrap();
+            for tid in rel.stopped.drain(..) {
+                if manager.get_mut(tid).is_some() {
+                    manager.add(tid);
+                }
+            }
+            if let Some(parent_rel) = self.rel_map.get_mut(&parent_pid) {
+                parent_rel.stopped_children.retain(|&child| child != id);
+            }
+        }
+    }
+    /// 进程所在的进程组，进程不存在时返回 None
+    #[inline]
+    pub fn pgid(&self, id: ProcId) -> Option<ProcId> {
+        self.rel_map.get(&id).map(|rel| rel.pgid)
+    }
+    /// 进程所在的会话，进程不存在时返回 None
+    #[inline]
+    pub fn sid(&self, id: ProcId) -> Option<ProcId> {
+        self.rel_map.get(&id).map(|rel| rel.sid)
+    }
+    /// 把进程移到同一会话中的进程组 `pgid`，`pgid` 等于 `id` 时以进程为组长新建进程组；会话首进程不能改变进程组
+    pub fn setpgid(&mut self, id: ProcId, pgid: ProcId) -> bool {
+        let Some(sid) = self.sid(id) else {
+            return false;
+        };
+        if sid == id
+            || (pgid != id
+                && !self
+                    .rel_map
+                    .values()
+                    .any(|rel| rel.pgid == pgid && rel.sid == sid))
+        {
+            return false;
+        }
+        self.rel_map.get_mut(&id).unwrap().pgid = pgid;
+        // 原来的进程组可能因此不再使用
+        self.release_pids([]);
+        true
+    }
+    /// 以进程为首新建会话和进程组，进程已经是进程组组长时失败
+    pub fn setsid(&mut self, id: ProcId) -> bool {
+        if self.rel_map.values().any(|rel| rel.pgid == id) {
+            return false;
+        }
+        match self.rel_map.get_mut(&id) {
+            Some(rel) => {
+                rel.pgid = id;
+                rel.sid = id;
+                self.release_pids([]);
+                true
+            }
+            None => false,
+        }
+    }
+    /// 进程组中的所有进程
+    pub fn group(&self, pgid: ProcId) -> Vec<ProcId> {
+        self.rel_map
+            .iter()
+            .filter(|(_, rel)| rel.pgid == pgid)
+            .map(|(&id, _)| id)
+            .collect()
+    }
     /// 某个线程重新入队
     pub fn re_enque(&mut self, id: ThreadId) {
         self.manager.as_mut().unwrap().add(id);
@@ -109,8 +318,15 @@ impl<P, T, MT: Manage<T, ThreadId> + Schedule<ThreadId>, MP: Manage<P, ProcId>>
         if let Some(parent_rel) = self.rel_map.get_mut(&pid) {
             parent_rel.add_thread(id);
             self.tid2pid.insert(id, pid);
+            self.stats.insert(id, TaskStats::new());
         }
     }
+    /// 当前线程所属进程的 Id 和线程的 Id，不在运行任何线程时返回 `None`
+    #[inline]
+    pub fn current_id(&self) -> Option<(ProcId, ThreadId)> {
+        let tid = self.current?;
+        self.tid2pid.get(&tid).map(|&pid| (pid, tid))
+    }
     /// 当前线程
     pub fn current(&mut self) -> Option<&mut T> {
         let id = self.current.unwrap();
@@ -124,10 +340,16 @@ impl<P, T, MT: Manage<T, ThreadId> + Schedule<ThreadId>, MP: Manage<P, ProcId>>
     /// 添加进程
     pub fn add_proc(&mut self, id: ProcId, proc: P, parent: ProcId) {
         self.proc_manager.as_mut().unwrap().insert(id, proc);
-        if let Some(parent_rel) = self.rel_map.get_mut(&parent) {
-            parent_rel.add_child(id);
-        }
-        self.rel_map.insert(id, ProcThreadRel::new(parent));
+        let (pgid, sid) = match self.rel_map.get_mut(&parent) {
+            Some(parent_rel) => {
+                parent_rel.add_child(id);
+                (parent_rel.pgid, parent_rel.sid)
+            }
+            // 没有父进程的进程自成一个会话
+            None => (id, id),
+        };
+        self.rel_map
+            .insert(id, ProcThreadRel::new(parent, pgid, sid));
     }
     /// 查询进程
     pub fn get_proc(&mut self, id: ProcId) -> Option<&mut P> {
@@ -137,40 +359,77 @@ impl<P, T, MT: Manage<T, ThreadId> + Schedule<ThreadId>, MP: Manage<P, ProcId>>
     pub fn del_proc(&mut self, id: ProcId, exit_code: isize) {
         // 删除进程实体
         self.proc_manager.as_mut().unwrap().delete(id);
-        // 进程结束时维护父子关系，进程删除后，所有的子进程交给 0 号进程来维护
-        let current_rel = self.rel_map.remove(&id).unwrap();
+        // 进程结束时维护父子关系，进程删除后，还在运行的子进程交给 init 进程来维护
+        let mut current_rel = self.rel_map.remove(&id).unwrap();
         let parent_pid = current_rel.parent;
         let children = current_rel.children;
-        // 从父进程中删除当前进程
+        // 进程的线程随进程一起回收
+        for tid in current_rel.threads {
+            if let Some(stats) = self.stats.remove(&tid) {
+                current_rel.stats.add(&stats);
+            }
+            tid.release();
+        }
+        for (tid, _) in current_rel.dead_threads {
+            tid.release();
+        }
+        // 已经结束但没有被等待的子进程随当前进程一起回收
+        let mut reaped: Vec<ProcId> = current_rel
+            .dead_children
+            .iter()
+            .map(|&(id, _)| id)
+            .collect();
+        // 从父进程中删除当前进程，唤醒等待子进程结束的线程；没有父进程时当前进程直接回收
         if let Some(parent_rel) = self.rel_map.get_mut(&parent_pid) {
             parent_rel.del_child(id, exit_code);
+            // 进程和它的子进程的运行统计计入父进程
+            parent_rel.children_stats.add(&current_rel.stats);
+            parent_rel.children_stats.add(&current_rel.children_stats);
+            self.wake(parent_pid);
+        } else {
+            reaped.push(id);
         }
-        // 把当前进程的所有子进程转移到 0 号进程
-        for i in children {
-            self.rel_map.get_mut(&i).unwrap().parent = ProcId::from_usize(0);
-            self.rel_map
-                .get_mut(&ProcId::from_usize(0))
-                .unwrap()
-                .add_child(i);
-        }
+        // 还在运行的子进程交给 init 进程
+        self.adopt_orphans(children);
+        self.release_pids(reaped);
     }
     /// wait 系统调用，返回结束的子进程 id 和 exit_code，正在运行的子进程不返回 None，返回 (-2, -1)
+    ///
+    /// 返回的子进程已经回收，它的 Id 可能被分配给新的进程
     pub fn wait(&mut self, child_pid: ProcId) -> Option<(ProcId, isize)> {
         let id = self.current.unwrap();
         let pid = self.tid2pid.get(&id).unwrap();
         let current_rel = self.rel_map.get_mut(pid).unwrap();
-        if child_pid.get_usize() == usize::MAX {
+        let ans = if child_pid.get_usize() == usize::MAX {
             current_rel.wait_any_child()
         } else {
             current_rel.wait_child(child_pid)
+        };
+        if let Some((dead_pid, _)) = ans.filter(|(id, _)| id.get_usize() != -2isize as usize) {
+            self.release_pids([dead_pid]);
         }
+        ans
+    }
+    /// 取出当前进程一个暂停了但还没有报告的子进程，`child_pid` 为 usize::MAX 时取任意一个
+    pub fn wait_stopped(&mut self, child_pid: ProcId) -> Option<ProcId> {
+        let id = self.current.unwrap();
+        let pid = self.tid2pid.get(&id).unwrap();
+        self.rel_map
+            .get_mut(pid)
+            .unwrap()
+            .take_stopped_child(child_pid)
     }
     /// wait_tid 系统调用
     pub fn waittid(&mut self, thread_tid: ThreadId) -> Option<isize> {
         let id = self.current.unwrap();
         let pid = self.tid2pid.get(&id).unwrap();
         let current_rel = self.rel_map.get_mut(pid).unwrap();
-        current_rel.wait_thread(thread_tid)
+        let ans = current_rel.wait_thread(thread_tid);
+        // 等到的线程已经回收，它的 Id 可能被分配给新的线程
+        if ans.is_some() && !current_rel.threads.contains(&thread_tid) {
+            thread_tid.release();
+        }
+        ans
     }
     /// 某个进程的线程数量
     pub fn thread_count(&self, id: ProcId) -> usize {
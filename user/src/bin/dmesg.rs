@@ -0,0 +1,33 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+extern crate alloc;
+
+use alloc::vec;
+use user_lib::{syslog, SYSLOG_ACTION_READ_ALL, SYSLOG_ACTION_SIZE_BUFFER};
+
+#[no_mangle]
+pub extern "C" fn main() -> i32 {
+    let size = syslog(SYSLOG_ACTION_SIZE_BUFFER, &mut []);
+    if size < 0 {
+        println!("dmesg: kernel log is not available");
+        return -1;
+    }
+    let mut buf = vec![0u8; size as usize];
+    let len = syslog(SYSLOG_ACTION_READ_ALL, &mut buf);
+    if len < 0 {
+        println!("dmesg: failed to read kernel log");
+        return -1;
+    }
+    // 逐行输出，每次写入不超过一行
+    for line in core::str::from_utf8(&buf[..len as usize])
+        .unwrap_or("")
+        .lines()
+    {
+        println!("{line}");
+    }
+    0
+}
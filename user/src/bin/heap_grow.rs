@@ -0,0 +1,46 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use user_lib::{brk, sbrk};
+
+/// 远超静态堆的 16 KiB。
+const LEN: usize = 256 << 10;
+
+#[no_mangle]
+pub extern "C" fn main() -> i32 {
+    // 直接调整程序断点
+    let base = brk(0);
+    assert!(base > 0);
+    assert_eq!(sbrk(4096), base);
+    assert_eq!(brk(0), base + 4096);
+    let page = unsafe { core::slice::from_raw_parts_mut(base as *mut u8, 4096) };
+    assert!(page.iter().all(|b| *b == 0));
+    page.fill(0x5a);
+    // 堆底以下的地址不合法
+    assert_eq!(brk(1), base + 4096);
+    // 堆不能长进用户栈
+    let local = 0u8;
+    assert_eq!(brk(&local as *const u8 as _), base + 4096);
+    assert_eq!(brk(usize::MAX), base + 4096);
+    // 页帧不足时不调整，已经分配的页帧退还给内核
+    for _ in 0..2 {
+        assert_eq!(brk(base as usize + (1 << 30)), base + 4096);
+    }
+
+    // 分配器用完静态堆后从程序断点扩展
+    let mut v: Vec<usize> = Vec::new();
+    for i in 0..LEN / core::mem::size_of::<usize>() {
+        v.push(i);
+    }
+    assert!(v.iter().enumerate().all(|(i, x)| i == *x));
+    assert!(brk(0) > base + LEN as isize);
+    assert!(page.iter().all(|b| *b == 0x5a));
+    println!("heap_grow passed!");
+    0
+}
@@ -3,28 +3,38 @@
 
 extern crate user_lib;
 
-use user_lib::{exec, fork, sched_yield, wait};
+use user_lib::{exec, exit, fork, sched_yield, wait};
 
-#[no_mangle]
-extern "C" fn main() -> i32 {
-    if fork() == 0 {
+/// 启动一个新的 shell，返回它的进程号。
+fn spawn_shell() -> isize {
+    let pid = fork();
+    if pid == 0 {
         // exec("user_shell\0", &[core::ptr::null::<u8>()]);
         exec("user_shell");
-    } else {
-        loop {
-            let mut exit_code: i32 = 0;
-            let pid = wait(&mut exit_code);
-            if pid == -1 {
-                sched_yield();
-                continue;
-            }
+        exit(-4);
+    }
+    pid
+}
 
-            // println!(
-            //     "[initproc] Released a zombie process, pid={}, exit_code={}",
-            //     pid,
-            //     exit_code,
-            // );
+#[no_mangle]
+extern "C" fn main() -> i32 {
+    let mut shell = spawn_shell();
+    loop {
+        let mut exit_code: i32 = 0;
+        let pid = wait(&mut exit_code);
+        if pid < 0 {
+            sched_yield();
+            continue;
         }
+        // shell 退出后重新启动，控制台上总有一个 shell
+        if pid == shell {
+            shell = spawn_shell();
+        }
+
+        // println!(
+        //     "[initproc] Released a zombie process, pid={}, exit_code={}",
+        //     pid,
+        //     exit_code,
+        // );
     }
-    0
 }
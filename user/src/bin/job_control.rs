@@ -0,0 +1,83 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::*;
+
+/// 子进程在自己的进程组中死循环，父进程向进程组发送信号暂停、继续并杀死它。
+/// 等待终端输入的子进程也能暂停、继续和杀死。
+#[no_mangle]
+pub extern "C" fn main() -> i32 {
+    let pid = getpid();
+    let pgid = getpgid(0);
+    assert!(pgid > 0);
+    // 不能加入本会话中不存在的进程组
+    assert_eq!(setpgid(0, 1000), -EPERM);
+
+    let child = fork();
+    if child == 0 {
+        assert_eq!(setpgid(0, 0), 0);
+        assert_eq!(getpgid(0), getpid());
+        #[allow(clippy::empty_loop)]
+        loop {}
+    }
+    assert!(child > 0);
+    // 父进程也设置一次，不依赖子进程先运行
+    assert_eq!(setpgid(child, child), 0);
+    assert_eq!(getpgid(child), child);
+    assert_eq!(getpgid(0), pgid);
+
+    let mut status = 0;
+    assert_eq!(kill(-child, SignalNo::SIGTSTP), 0);
+    assert_eq!(wait4(child, &mut status, WUNTRACED), child);
+    assert!(WIFSTOPPED(status));
+    // 暂停只报告一次
+    assert_eq!(wait4(child, &mut status, WNOHANG | WUNTRACED), 0);
+    println!("job_control: child {child} stopped");
+
+    assert_eq!(kill(-child, SignalNo::SIGCONT), 0);
+    sleep(50);
+    assert_eq!(kill(-child, SignalNo::SIGKILL), 0);
+    assert_eq!(waitpid(child, &mut status), child);
+    assert!(WIFSIGNALED(status));
+    assert_eq!(WTERMSIG(status), SignalNo::SIGKILL as i32);
+    // 进程组中没有进程了
+    assert_eq!(kill(-child, SignalNo::SIGCONT), -1);
+
+    // 阻塞在终端输入上的子进程同样能被暂停、继续和杀死
+    let child = fork();
+    if child == 0 {
+        let buf = [0u8; 1];
+        loop {
+            read(STDIN, &buf);
+        }
+    }
+    sleep(50);
+    assert_eq!(kill(child, SignalNo::SIGTSTP), 0);
+    assert_eq!(wait4(child, &mut status, WUNTRACED), child);
+    assert!(WIFSTOPPED(status));
+    assert_eq!(kill(child, SignalNo::SIGCONT), 0);
+    sleep(50);
+    assert_eq!(kill(child, SignalNo::SIGKILL), 0);
+    assert_eq!(waitpid(child, &mut status), child);
+    assert!(WIFSIGNALED(status));
+    assert_eq!(WTERMSIG(status), SignalNo::SIGKILL as i32);
+
+    // 新会话的首进程同时是新进程组的组长，不能再新建会话
+    let child = fork();
+    if child == 0 {
+        // 子进程继承父进程的进程组
+        assert_eq!(getpgid(0), pgid);
+        assert_eq!(setsid(), getpid());
+        assert_eq!(getpgid(0), getpid());
+        assert_eq!(setsid(), -EPERM);
+        exit(0);
+    }
+    assert_eq!(waitpid(child, &mut status), child);
+    assert!(WIFEXITED(status) && WEXITSTATUS(status) == 0);
+    assert_eq!(getpid(), pid);
+    println!("Test job_control OK!");
+    0
+}
@@ -0,0 +1,37 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+extern crate alloc;
+
+use alloc::vec;
+use user_lib::{
+    ioctl, syslog, STDIN, SYSLOG_ACTION_CLEAR, SYSLOG_ACTION_READ_ALL, SYSLOG_ACTION_SIZE_BUFFER,
+    SYSLOG_ACTION_SIZE_UNREAD, TCGETS,
+};
+
+#[no_mangle]
+pub extern "C" fn main() -> i32 {
+    let size = syslog(SYSLOG_ACTION_SIZE_BUFFER, &mut []);
+    assert!(size > 0);
+    assert_eq!(syslog(SYSLOG_ACTION_CLEAR, &mut []), 0);
+    assert_eq!(syslog(SYSLOG_ACTION_SIZE_UNREAD, &mut []), 0);
+
+    // 用无效的指针让内核打印一条错误日志
+    assert_eq!(ioctl(STDIN, TCGETS, 0), -1);
+    let mut buf = vec![0u8; size as usize];
+    let len = syslog(SYSLOG_ACTION_READ_ALL, &mut buf);
+    assert_eq!(len, syslog(SYSLOG_ACTION_SIZE_UNREAD, &mut []));
+    let log = core::str::from_utf8(&buf[..len as usize]).unwrap();
+    assert!(log
+        .lines()
+        .any(|line| line.ends_with("[ERROR] ptr not writeable")));
+
+    // 缓冲区放不下时只读最近的完整记录
+    let mut small = [0u8; 8];
+    assert_eq!(syslog(SYSLOG_ACTION_READ_ALL, &mut small), 0);
+    println!("kernel_log passed!");
+    0
+}
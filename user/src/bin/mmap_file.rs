@@ -0,0 +1,137 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, exit, fork, mmap, msync, munmap, open, read, wait, write, OpenFlags};
+use user_lib::{
+    SignalNo, EINVAL, ENOMEM, MAP_ANONYMOUS, MAP_PRIVATE, MAP_SHARED, MS_SYNC, PROT_NONE,
+    PROT_READ, PROT_WRITE, WIFSIGNALED, WTERMSIG,
+};
+
+const FILE: &str = "mmapfile\0";
+/// 跨越两页的文件长度。
+const LEN: usize = 4096 + 100;
+
+/// 读写文件用的缓冲区，对齐保证不跨页。
+#[repr(align(64))]
+struct Chunk([u8; 64]);
+
+#[inline]
+fn pattern(i: usize) -> u8 {
+    (i % 251) as u8
+}
+
+/// 从头读出文件的第 `pos` 个字节。
+fn byte_at(pos: usize) -> u8 {
+    let fd = open(FILE, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let mut chunk = Chunk([0; 64]);
+    let mut offset = 0;
+    loop {
+        let len = read(fd as usize, &chunk.0) as usize;
+        assert!(len > 0);
+        if pos < offset + len {
+            close(fd as usize);
+            return chunk.0[pos - offset];
+        }
+        offset += len;
+        chunk.0.fill(0);
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn main() -> i32 {
+    // 准备文件
+    let fd = open(FILE, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    let mut chunk = Chunk([0; 64]);
+    let mut written = 0;
+    while written < LEN {
+        let len = (LEN - written).min(chunk.0.len());
+        for (i, b) in chunk.0[..len].iter_mut().enumerate() {
+            *b = pattern(written + i);
+        }
+        assert_eq!(write(fd as usize, &chunk.0[..len]), len as isize);
+        written += len;
+    }
+    close(fd as usize);
+
+    // 共享映射：内容来自文件，修改写回文件
+    let fd = open(FILE, OpenFlags::RDWR);
+    assert!(fd > 0);
+    let addr = mmap(0, LEN, PROT_READ | PROT_WRITE, MAP_SHARED, fd as _, 0);
+    assert!(addr > 0);
+    let data = unsafe { core::slice::from_raw_parts_mut(addr as *mut u8, LEN) };
+    assert!(data.iter().enumerate().all(|(i, b)| *b == pattern(i)));
+    data[1] = 0xaa;
+    assert_eq!(msync(addr as usize, LEN, MS_SYNC), 0);
+    assert_eq!(byte_at(1), 0xaa);
+    data[4096 + 1] = 0xbb;
+    assert_eq!(munmap(addr as usize, LEN), 0);
+    assert_eq!(byte_at(4096 + 1), 0xbb);
+
+    // 私有映射：修改不写回文件
+    let addr = mmap(0, LEN, PROT_READ | PROT_WRITE, MAP_PRIVATE, fd as _, 4096);
+    assert!(addr > 0);
+    let data = unsafe { core::slice::from_raw_parts_mut(addr as *mut u8, 100) };
+    assert_eq!(data[1], 0xbb);
+    data[2] = 0xcc;
+    assert_eq!(munmap(addr as usize, LEN), 0);
+    assert_eq!(byte_at(4096 + 2), pattern(4096 + 2));
+    close(fd as usize);
+
+    // 共享的匿名映射：初始为 0，父子进程可见彼此的修改
+    let addr = mmap(
+        0,
+        4096,
+        PROT_READ | PROT_WRITE,
+        MAP_SHARED | MAP_ANONYMOUS,
+        -1,
+        0,
+    );
+    assert!(addr > 0);
+    let flag = unsafe { &mut *(addr as *mut usize) };
+    assert_eq!(*flag, 0);
+    *flag = 1;
+    if fork() == 0 {
+        *flag = 2;
+        exit(0);
+    }
+    let mut exit_code: i32 = 0;
+    assert!(wait(&mut exit_code) > 0);
+    assert_eq!(*flag, 2);
+    assert_eq!(munmap(addr as usize, 4096), 0);
+
+    // 只写的映射也可以读
+    let anonymous = MAP_PRIVATE | MAP_ANONYMOUS;
+    let addr = mmap(0, 4096, PROT_WRITE, anonymous, -1, 0);
+    assert!(addr > 0);
+    let word = unsafe { &mut *(addr as *mut usize) };
+    *word = 3;
+    assert_eq!(*word, 3);
+    assert_eq!(munmap(addr as usize, 4096), 0);
+
+    // PROT_NONE 的映射占据地址范围，访问时进程被 SIGSEGV 杀死
+    let addr = mmap(0, 4096, PROT_NONE, anonymous, -1, 0);
+    assert!(addr > 0);
+    if fork() == 0 {
+        let word = unsafe { core::ptr::read_volatile(addr as *const usize) };
+        exit(word as _);
+    }
+    assert!(wait(&mut exit_code) > 0);
+    assert!(WIFSIGNALED(exit_code));
+    assert_eq!(WTERMSIG(exit_code), SignalNo::SIGSEGV as i32);
+    assert_eq!(munmap(addr as usize, 4096), 0);
+
+    // 错误码：地址未对齐；地址空间放不下
+    assert_eq!(mmap(1, 4096, PROT_READ, anonymous, -1, 0), -EINVAL);
+    assert_eq!(
+        mmap(0, usize::MAX >> 1, PROT_READ, anonymous, -1, 0),
+        -ENOMEM
+    );
+
+    println!("mmap_file passed!");
+    0
+}
@@ -0,0 +1,87 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use user_lib::{exit, fork, sched_yield, shmat, shmctl, shmdt, shmget, wait};
+use user_lib::{IPC_CREAT, IPC_PRIVATE, IPC_RMID};
+
+const BUFFER_SIZE: usize = 8;
+const PRODUCER_COUNT: usize = 4;
+const NUMBER_PER_PRODUCER: usize = 100;
+
+/// 放在共享内存段里的环形缓冲区。
+#[repr(C)]
+struct Channel {
+    /// 生产者之间互斥
+    lock: AtomicBool,
+    /// 累计写入的数量
+    front: AtomicUsize,
+    /// 累计读出的数量
+    tail: AtomicUsize,
+    buffer: [usize; BUFFER_SIZE],
+}
+
+fn producer(channel: &mut Channel, id: usize) -> ! {
+    let mut sent = 0;
+    while sent < NUMBER_PER_PRODUCER {
+        while channel.lock.swap(true, Ordering::Acquire) {
+            sched_yield();
+        }
+        let front = channel.front.load(Ordering::Relaxed);
+        if front - channel.tail.load(Ordering::Acquire) < BUFFER_SIZE {
+            channel.buffer[front % BUFFER_SIZE] = id;
+            channel.front.store(front + 1, Ordering::Release);
+            sent += 1;
+        }
+        channel.lock.store(false, Ordering::Release);
+        sched_yield();
+    }
+    exit(0);
+    unreachable!()
+}
+
+fn consumer(channel: &mut Channel) {
+    let mut received = [0usize; PRODUCER_COUNT];
+    for _ in 0..PRODUCER_COUNT * NUMBER_PER_PRODUCER {
+        let tail = channel.tail.load(Ordering::Relaxed);
+        while channel.front.load(Ordering::Acquire) == tail {
+            sched_yield();
+        }
+        let id = channel.buffer[tail % BUFFER_SIZE];
+        print!("{} ", id);
+        received[id] += 1;
+        channel.tail.store(tail + 1, Ordering::Release);
+    }
+    println!();
+    assert!(received.iter().all(|&n| n == NUMBER_PER_PRODUCER));
+}
+
+#[no_mangle]
+pub extern "C" fn main() -> i32 {
+    let shmid = shmget(IPC_PRIVATE, core::mem::size_of::<Channel>(), IPC_CREAT);
+    assert!(shmid >= 0);
+    let addr = shmat(shmid as usize, 0, 0);
+    assert!(addr > 0);
+    // 共享内存段初始为全零，正是缓冲区的初始状态
+    let channel = unsafe { &mut *(addr as *mut Channel) };
+    for id in 0..PRODUCER_COUNT {
+        let pid = fork();
+        if pid == 0 {
+            producer(channel, id);
+        }
+        assert!(pid > 0);
+    }
+    consumer(channel);
+    let mut exit_code: i32 = 0;
+    for _ in 0..PRODUCER_COUNT {
+        assert!(wait(&mut exit_code) > 0);
+        assert_eq!(exit_code, 0);
+    }
+    assert_eq!(shmdt(addr as usize), 0);
+    assert_eq!(shmctl(shmid as usize, IPC_RMID), 0);
+    println!("mpsc_shm passed!");
+    0
+}
@@ -0,0 +1,38 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::*;
+
+/// 子进程先于孙进程结束，孙进程应该被 init 进程收养，结束后由 init 进程回收。
+#[no_mangle]
+pub extern "C" fn main() -> i32 {
+    let pid = fork();
+    if pid == 0 {
+        let me = getpid();
+        if fork() == 0 {
+            // 等父进程结束
+            while getppid() == me {
+                sleep(10);
+            }
+            let parent = getppid();
+            assert!(parent > 0);
+            println!("orphan: adopted by process {parent}");
+            println!("Test orphan OK!");
+            exit(0);
+        }
+        // 不等待孙进程，直接结束
+        exit(3);
+    }
+    assert!(pid > 0);
+    let mut status = 0;
+    assert_eq!(waitpid(pid, &mut status), pid);
+    assert!(WIFEXITED(status));
+    assert_eq!(WEXITSTATUS(status), 3);
+    // 孙进程不是当前进程的子进程
+    assert_eq!(wait(&mut status), -ECHILD);
+    sleep(100);
+    0
+}
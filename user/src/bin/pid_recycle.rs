@@ -0,0 +1,75 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::*;
+
+/// 临时降低的进程 Id 上限，只容纳 init 进程、shell 和少数几个子进程。
+const LIMIT: usize = 8;
+/// 每个子进程扩展的堆，所有子进程加起来远超物理内存，结束的进程不归还页帧就会耗尽。
+const HEAP: isize = 1 << 20;
+
+/// 回收的子进程 Id 不再指向任何进程，新的子进程也不会马上拿到刚释放的 Id，它占用的页帧也已经归还。
+///
+/// 降低进程 Id 的上限之后 Id 很快用完，`fork` 返回 `-EAGAIN`，回收子进程之后释放的 Id 可以再分配。
+#[no_mangle]
+pub extern "C" fn main() -> i32 {
+    let mut status = 0;
+    let mut pids = [0; 100];
+    for i in 0..pids.len() {
+        let pid = fork();
+        if pid == 0 {
+            assert!(sbrk(HEAP) > 0);
+            exit(i as _);
+        }
+        assert!(pid > 0);
+        // 释放的 Id 要等分配绕回一圈才会再用到
+        assert!(!pids[..i].contains(&pid));
+        assert_eq!(waitpid(pid, &mut status), pid);
+        assert_eq!(WEXITSTATUS(status), i as i32);
+        // 过期的 Id 查不到进程
+        assert_eq!(getpgid(pid), -ESRCH);
+        pids[i] = pid;
+    }
+    println!("pid_recycle: last pid {}", pids[pids.len() - 1]);
+
+    // 上限至少要容纳 init 进程
+    assert_eq!(pid_max(1), -EINVAL);
+    let old = pid_max(LIMIT);
+    assert!(old as usize > LIMIT);
+    assert_eq!(pid_max(0), LIMIT as isize);
+    // 结束但还没有回收的子进程占着 Id
+    let mut children = [0; LIMIT];
+    let mut count = 0;
+    loop {
+        let pid = fork();
+        if pid == 0 {
+            exit(0);
+        }
+        if pid == -EAGAIN {
+            break;
+        }
+        assert!(pid > 0 && (pid as usize) < LIMIT);
+        children[count] = pid;
+        count += 1;
+    }
+    println!("pid_recycle: {count} children exhausted pid_max {LIMIT}");
+    assert!(count > 0);
+    // 回收一个子进程，它的 Id 是唯一空闲的 Id
+    let released = children[count - 1];
+    assert_eq!(waitpid(released, &mut status), released);
+    let pid = fork();
+    if pid == 0 {
+        exit(0);
+    }
+    assert_eq!(pid, released);
+    children[count - 1] = pid;
+    for &pid in &children[..count] {
+        assert_eq!(waitpid(pid, &mut status), pid);
+    }
+    assert_eq!(pid_max(old as _), LIMIT as isize);
+    println!("Test pid_recycle OK!");
+    0
+}
@@ -0,0 +1,28 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::*;
+
+/// 子进程不做系统调用地死循环，只有时钟中断能让父进程重新运行并杀死它。
+#[no_mangle]
+pub extern "C" fn main() -> i32 {
+    let pid = fork();
+    if pid == 0 {
+        #[allow(clippy::empty_loop)]
+        loop {}
+    }
+    assert!(pid > 0);
+    // 让子进程先运行，没有抢占时父进程不会再被调度
+    sleep(100);
+    println!("preempt: parent ran while child spinning");
+    assert_eq!(kill(pid, SignalNo::SIGKILL), 0);
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid, &mut exit_code), pid);
+    assert!(WIFSIGNALED(exit_code));
+    assert_eq!(WTERMSIG(exit_code), SignalNo::SIGKILL as i32);
+    println!("Test preempt OK!");
+    0
+}
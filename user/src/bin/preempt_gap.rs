@@ -0,0 +1,43 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{clock_gettime, ClockId, TimeSpec};
+
+/// 观察的时长，以微秒为单位，短于同时运行的 `11sleep`。
+const PERIOD: usize = 500_000;
+
+/// 单调时钟，以微秒为单位。
+fn now() -> usize {
+    let mut time = TimeSpec::ZERO;
+    clock_gettime(ClockId::CLOCK_MONOTONIC, &mut time as *mut _ as _);
+    time.tv_sec * 1_000_000 + time.tv_nsec / 1_000
+}
+
+/// 和其他程序同时运行时，不让出处理器的计算也会被时钟中断打断，
+/// 两次读时钟之间出现远长于计算本身的间隔。
+///
+/// 这一章的系统调用不切换程序，只有时钟中断能让其他程序运行。
+#[no_mangle]
+pub extern "C" fn main() -> i32 {
+    let end = now() + PERIOD;
+    let (mut shortest, mut longest) = (usize::MAX, 0);
+    let mut last = now();
+    while last < end {
+        let mut sum = 0usize;
+        for i in 0..10_000 {
+            sum = core::hint::black_box(sum.wrapping_add(i));
+        }
+        core::hint::black_box(sum);
+        let time = now();
+        shortest = shortest.min(time - last);
+        longest = longest.max(time - last);
+        last = time;
+    }
+    println!("preempt_gap: steps took {shortest} us to {longest} us");
+    assert!(longest > shortest * 5);
+    println!("Test preempt_gap OK!");
+    0
+}
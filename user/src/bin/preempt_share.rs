@@ -0,0 +1,61 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::*;
+
+/// 不做系统调用的计算量。
+const ITERATIONS: usize = 20_000_000;
+
+/// 单调时钟，以微秒为单位。
+fn now() -> usize {
+    let mut time = TimeSpec::ZERO;
+    clock_gettime(ClockId::CLOCK_MONOTONIC, &mut time as *mut _ as _);
+    time.tv_sec * 1_000_000 + time.tv_nsec / 1_000
+}
+
+/// 已经使用的处理器时间，以微秒为单位。
+fn cpu_time() -> usize {
+    let mut info = TaskInfo::ZERO;
+    assert_eq!(task_info(0, &mut info), 0);
+    info.utime + info.stime
+}
+
+/// 两个子进程同时做不含系统调用的计算，只有时钟中断能在它们之间切换。
+///
+/// 两个子进程交替推进时，各自经过的时间都明显长于自己使用的处理器时间。
+#[no_mangle]
+pub extern "C" fn main() -> i32 {
+    let mut pids = [0; 2];
+    for pid in pids.iter_mut() {
+        *pid = fork();
+        if *pid == 0 {
+            let (wall, cpu) = (now(), cpu_time());
+            let mut sum = 0usize;
+            for i in 0..ITERATIONS {
+                sum = core::hint::black_box(sum.wrapping_add(i));
+            }
+            let (wall, cpu) = (now() - wall, cpu_time() - cpu);
+            core::hint::black_box(sum);
+            // 以退出码报告经过的时间与处理器时间之比，放大 10 倍
+            exit((wall * 10 / cpu.max(1)).min(255) as _);
+        }
+        assert!(*pid > 0);
+    }
+    for pid in pids {
+        let mut status = 0;
+        assert_eq!(waitpid(pid, &mut status), pid);
+        assert!(WIFEXITED(status));
+        let ratio = WEXITSTATUS(status);
+        println!(
+            "preempt_share: child {pid} took {}.{}x its processor time",
+            ratio / 10,
+            ratio % 10
+        );
+        assert!(ratio >= 13);
+    }
+    println!("Test preempt_share OK!");
+    0
+}
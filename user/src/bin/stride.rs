@@ -0,0 +1,66 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::*;
+
+/// 子进程的优先级。
+const PRIORITIES: [usize; 3] = [4, 8, 12];
+/// 统计处理器时间的时长，以毫秒为单位。
+const PERIOD: usize = 1000;
+
+fn now() -> usize {
+    let mut time = TimeSpec::ZERO;
+    clock_gettime(ClockId::CLOCK_MONOTONIC, &mut time as *mut _ as _);
+    time.tv_sec * 1000 + time.tv_nsec / 1_000_000
+}
+
+/// 已经使用的处理器时间，以微秒为单位。
+fn cpu_time() -> usize {
+    let mut info = TaskInfo::ZERO;
+    assert_eq!(task_info(0, &mut info), 0);
+    info.utime + info.stime
+}
+
+/// 几个优先级不同的子进程同时运行，步长调度下各自得到的处理器时间与优先级成正比。
+#[no_mangle]
+pub extern "C" fn main() -> i32 {
+    // 优先级至少为 2
+    assert_eq!(set_priority(1), -EINVAL);
+    assert_eq!(set_priority(-1), -EINVAL);
+
+    // 所有子进程都创建之后再开始统计
+    let start = now() + 200;
+    let mut pids = [0; PRIORITIES.len()];
+    for (pid, &priority) in pids.iter_mut().zip(&PRIORITIES) {
+        *pid = fork();
+        if *pid == 0 {
+            assert_eq!(set_priority(priority as _), priority as isize);
+            while now() < start {}
+            let before = cpu_time();
+            while now() < start + PERIOD {}
+            // 以退出码报告处理器时间占总时长的百分比
+            exit(((cpu_time() - before) / 10 / PERIOD) as _);
+        }
+        assert!(*pid > 0);
+    }
+    let mut shares = [0; PRIORITIES.len()];
+    for (share, &pid) in shares.iter_mut().zip(&pids) {
+        let mut status = 0;
+        assert_eq!(waitpid(pid, &mut status), pid);
+        assert!(WIFEXITED(status));
+        *share = WEXITSTATUS(status) as usize;
+    }
+    let total_share: usize = shares.iter().sum();
+    let total_priority: usize = PRIORITIES.iter().sum();
+    for (&share, &priority) in shares.iter().zip(&PRIORITIES) {
+        println!("stride: priority {priority} got {share}% of the processor");
+        // 允许 30% 的误差
+        let expected = total_share * priority / total_priority;
+        assert!(share * 10 >= expected * 7 && share * 10 <= expected * 13);
+    }
+    println!("Test stride OK!");
+    0
+}
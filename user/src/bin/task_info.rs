@@ -0,0 +1,69 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::*;
+
+const PAGE_SIZE: usize = 4096;
+
+/// 两个相邻的页，用来放跨页的结构体。
+#[repr(C, align(4096))]
+struct Pages([u8; 2 * PAGE_SIZE]);
+
+static mut PAGES: Pages = Pages([0; 2 * PAGE_SIZE]);
+
+/// 内核统计每个进程的运行时间、调度次数和系统调用次数，子进程结束后计入父进程。
+#[no_mangle]
+pub extern "C" fn main() -> i32 {
+    for _ in 0..10 {
+        getpid();
+    }
+    let mut info = TaskInfo::ZERO;
+    assert_eq!(task_info(0, &mut info), 0);
+    assert!(info.syscall_times[SyscallId::GETPID.0] >= 10);
+    assert!(info.syscalls > info.syscall_times[SyscallId::GETPID.0] as usize);
+    assert!(info.switches > 0);
+
+    // 跨页的缓冲区也要完整写入
+    let straddle = unsafe {
+        &mut *((core::ptr::addr_of_mut!(PAGES) as *mut u8).add(PAGE_SIZE - 8) as *mut TaskInfo)
+    };
+    assert_eq!(task_info(0, straddle), 0);
+    assert!(straddle.syscall_times[SyscallId::GETPID.0] >= 10);
+    assert!(straddle.syscalls > info.syscalls);
+
+    // 子进程不做系统调用地计算一段时间，只能被时钟中断抢占
+    let pid = fork();
+    if pid == 0 {
+        let mut sum = 0usize;
+        for i in 0..20_000_000 {
+            sum = core::hint::black_box(sum.wrapping_add(i));
+        }
+        exit((sum & 1) as _);
+    }
+    assert!(pid > 0);
+    assert_eq!(task_info(pid, &mut info), 0);
+    assert_eq!(info.syscalls, 0);
+    let mut status = 0;
+    assert_eq!(waitpid(pid, &mut status), pid);
+    // 回收的子进程查不到统计，它的统计计入父进程
+    assert_eq!(task_info(pid, &mut info), -ESRCH);
+    let mut usage = Rusage::default();
+    assert_eq!(getrusage(RUSAGE_CHILDREN, &mut usage), 0);
+    println!(
+        "task_info: child ran {} us, preempted {} times",
+        usage.ru_utime.as_microsecond(),
+        usage.ru_nivcsw
+    );
+    assert!(usage.ru_utime.as_microsecond() > 0);
+    assert!(usage.ru_nivcsw > 0);
+    assert_eq!(getrusage(RUSAGE_SELF, &mut usage), 0);
+    assert!(usage.ru_nvcsw > 0);
+    let mut tms = Tms::default();
+    assert!(times(&mut tms) > 0);
+    assert!(tms.tms_cutime > 0);
+    println!("Test task_info OK!");
+    0
+}
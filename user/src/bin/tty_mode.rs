@@ -0,0 +1,38 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{ioctl, Termios, ENOTTY, STDIN, STDOUT, TCGETS, TCSETS};
+
+fn get() -> Termios {
+    let mut termios = Termios::default();
+    assert_eq!(ioctl(STDIN, TCGETS, &mut termios as *mut _ as _), 0);
+    termios
+}
+
+fn set(termios: Termios) {
+    assert_eq!(ioctl(STDIN, TCSETS, &termios as *const _ as _), 0);
+}
+
+#[no_mangle]
+pub extern "C" fn main() -> i32 {
+    // 默认是规范模式，开启回显和信号
+    let cooked = get();
+    let all = Termios::ISIG | Termios::ICANON | Termios::ECHO;
+    assert_eq!(cooked.lflag & all, all);
+
+    // 切换到不回显的原始模式再恢复
+    set(Termios {
+        lflag: cooked.lflag & !(Termios::ICANON | Termios::ECHO),
+    });
+    assert_eq!(get().lflag & all, Termios::ISIG);
+    set(cooked);
+    assert_eq!(get().lflag & all, all);
+
+    // 标准输出不是终端
+    assert_eq!(ioctl(STDOUT, TCGETS, 0), -ENOTTY);
+    println!("tty_mode passed!");
+    0
+}
@@ -1,63 +1,200 @@
-#![no_std]
-#![no_main]
-#![allow(clippy::println_empty_string)]
-
-extern crate alloc;
-
-#[macro_use]
-extern crate user_lib;
-
-const LF: u8 = 0x0au8;
-const CR: u8 = 0x0du8;
-const DL: u8 = 0x7fu8;
-const BS: u8 = 0x08u8;
-
-use alloc::string::String;
-use user_lib::{exec, fork, getchar, waitpid};
-
-#[no_mangle]
-pub extern "C" fn main() -> i32 {
-    println!("Rust user shell");
-    let mut line: String = String::new(); // 记录着当前输入的命令
-    print!(">> ");
-    loop {
-        let c = getchar();
-        match c {
-            LF | CR => {
-                // 换行
-                println!();
-                if !line.is_empty() {
-                    let pid = fork();
-                    if pid == 0 {
-                        // child process
-                        if exec(line.as_str()) == -1 {
-                            println!("Error when executing!");
-                            return -4;
-                        }
-                        unreachable!();
-                    } else {
-                        let mut exit_code: i32 = 0;
-                        let exit_pid = waitpid(pid as isize, &mut exit_code);
-                        assert_eq!(pid, exit_pid);
-                        println!("Shell: Process {} exited with code {}", pid, exit_code);
-                    }
-                    line.clear();
-                }
-                print!(">> ");
-            }
-            BS | DL => {
-                // backspace
-                if !line.is_empty() {
-                    print!("{}", BS as char);
-                    print!(" ");
-                    print!("{}", BS as char);
-                    line.pop();
-                }
-            }
-            _ => {
-                print!("{}", c as char);
-                line.push(c as char);
-            }
-        }
-    }
-}
+#![no_std]
+#![no_main]
+#![allow(clippy::println_empty_string)]
+
+#[macro_use]
+extern crate user_lib;
+
+extern crate alloc;
+
+use alloc::{string::String, vec::Vec};
+use user_lib::{
+    exec, fork, getpgid, kill, read_stdin, setpgid, sigprocmask, tcsetpgrp, wait4, SignalNo,
+    EAGAIN, WEXITSTATUS, WIFSIGNALED, WIFSTOPPED, WNOHANG, WTERMSIG, WUNTRACED,
+};
+
+/// 作业状态。
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    Running,
+    Stopped,
+}
+
+/// 作业，每个作业在以它的进程为组长的进程组中运行。
+struct Job {
+    id: usize,
+    pgid: isize,
+    state: State,
+    command: String,
+}
+
+impl Job {
+    fn print(&self) {
+        let state = match self.state {
+            State::Running => "Running",
+            State::Stopped => "Stopped",
+        };
+        println!("[{}] {state:<8} {}", self.id, self.command);
+    }
+}
+
+/// shell 屏蔽的信号，终端的 Ctrl-C、Ctrl-Z 只作用于前台作业。
+const SHELL_MASK: usize = (1 << SignalNo::SIGINT as usize) | (1 << SignalNo::SIGTSTP as usize);
+
+#[no_mangle]
+pub extern "C" fn main() -> i32 {
+    println!("Rust user shell");
+    // shell 自成一个进程组并占据终端；终端不支持前台进程组时也就没有 Ctrl-Z，不需要屏蔽信号
+    setpgid(0, 0);
+    let job_control = tcsetpgrp(getpgid(0)) == 0;
+    if job_control {
+        sigprocmask(SHELL_MASK);
+    }
+    let mut jobs: Vec<Job> = Vec::new();
+    // 终端负责回显和行编辑，每次读到一整行
+    let mut buf = [0u8; 256];
+    loop {
+        reap(&mut jobs);
+        print!(">> ");
+        let n = read_stdin(&mut buf);
+        if n <= 0 {
+            // 空行上的 Ctrl-D
+            println!();
+            continue;
+        }
+        let line = core::str::from_utf8(&buf[..n as usize]).unwrap_or("");
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (name, arg) = line.split_once(' ').unwrap_or((line, ""));
+        match name {
+            "jobs" => jobs.iter().for_each(Job::print),
+            "fg" | "bg" => {
+                let Some(i) = find_job(&jobs, arg.trim()) else {
+                    println!("{name}: no such job");
+                    continue;
+                };
+                if name == "fg" {
+                    let job = jobs.remove(i);
+                    println!("{}", job.command);
+                    run_foreground(job, &mut jobs);
+                } else {
+                    let job = &mut jobs[i];
+                    if job.state == State::Stopped {
+                        kill(-job.pgid, SignalNo::SIGCONT);
+                        job.state = State::Running;
+                    }
+                    job.print();
+                }
+            }
+            _ => {
+                let (command, background) = match line.strip_suffix('&') {
+                    Some(command) => (command.trim_end(), true),
+                    None => (line, false),
+                };
+                let pid = fork();
+                if pid == 0 {
+                    // child process
+                    // 父子进程都设置进程组，无论谁先运行，作业都在自己的进程组中执行
+                    setpgid(0, 0);
+                    if job_control {
+                        sigprocmask(0);
+                    }
+                    if exec(command) == -1 {
+                        println!("Error when executing!");
+                        return -4;
+                    }
+                    unreachable!();
+                }
+                if pid < 0 {
+                    println!("Shell: fork failed");
+                    continue;
+                }
+                setpgid(pid, pid);
+                let job = Job {
+                    id: jobs.iter().map(|job| job.id).max().unwrap_or(0) + 1,
+                    pgid: pid,
+                    state: State::Running,
+                    command: command.into(),
+                };
+                if background {
+                    println!("[{}] {}", job.id, pid);
+                    jobs.push(job);
+                } else {
+                    run_foreground(job, &mut jobs);
+                }
+            }
+        }
+    }
+}
+
+/// 按作业号查找作业，没有给出作业号时取最近的作业。
+fn find_job(jobs: &[Job], arg: &str) -> Option<usize> {
+    if arg.is_empty() {
+        return jobs.len().checked_sub(1);
+    }
+    let id: usize = arg.trim_start_matches('%').parse().ok()?;
+    jobs.iter().position(|job| job.id == id)
+}
+
+/// 把作业交给终端前台，等到它结束或暂停后收回终端；暂停的作业放回作业表。
+fn run_foreground(mut job: Job, jobs: &mut Vec<Job>) {
+    tcsetpgrp(job.pgid);
+    if job.state == State::Stopped {
+        kill(-job.pgid, SignalNo::SIGCONT);
+        job.state = State::Running;
+    }
+    let mut status: i32 = 0;
+    let pid = loop {
+        match wait4(job.pgid, &mut status, WUNTRACED) {
+            ret if ret == -EAGAIN => continue,
+            ret => break ret,
+        }
+    };
+    tcsetpgrp(getpgid(0));
+    if pid != job.pgid {
+        return;
+    }
+    if WIFSTOPPED(status) {
+        job.state = State::Stopped;
+        job.print();
+        jobs.push(job);
+    } else {
+        report(pid, status);
+    }
+}
+
+/// 回收结束了的后台作业，记下被暂停的后台作业。
+fn reap(jobs: &mut Vec<Job>) {
+    jobs.retain_mut(|job| {
+        let mut status: i32 = 0;
+        if wait4(job.pgid, &mut status, WNOHANG | WUNTRACED) != job.pgid {
+            return true;
+        }
+        if WIFSTOPPED(status) {
+            job.state = State::Stopped;
+            job.print();
+            return true;
+        }
+        println!("[{}] Done     {}", job.id, job.command);
+        report(job.pgid, status);
+        false
+    });
+}
+
+fn report(pid: isize, status: i32) {
+    if WIFSIGNALED(status) {
+        println!(
+            "Shell: Process {} killed by signal {}",
+            pid,
+            WTERMSIG(status)
+        );
+    } else {
+        println!(
+            "Shell: Process {} exited with code {}",
+            pid,
+            WEXITSTATUS(status)
+        );
+    }
+}
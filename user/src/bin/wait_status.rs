@@ -0,0 +1,37 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::*;
+
+#[no_mangle]
+pub extern "C" fn main() -> i32 {
+    let mut status = 0;
+    // 正常退出的子进程
+    let pid = fork();
+    if pid == 0 {
+        sleep(100);
+        exit(7);
+    }
+    assert_eq!(wait4(pid, &mut status, WNOHANG), 0);
+    assert_eq!(waitpid(pid, &mut status), pid);
+    assert!(WIFEXITED(status));
+    assert_eq!(WEXITSTATUS(status), 7);
+    println!("wait_status: child {pid} exited with code 7");
+    // 访存异常被杀死的子进程
+    let pid = fork();
+    if pid == 0 {
+        unsafe { core::ptr::null_mut::<u8>().write_volatile(0) };
+        unreachable!();
+    }
+    assert_eq!(waitpid(pid, &mut status), pid);
+    assert!(WIFSIGNALED(status));
+    assert_eq!(WTERMSIG(status), SignalNo::SIGSEGV as i32);
+    println!("wait_status: child {pid} killed by SIGSEGV");
+    // 没有子进程了
+    assert_eq!(wait(&mut status), -ECHILD);
+    println!("Test wait_status OK!");
+    0
+}
@@ -1,4 +1,5 @@
-﻿use alloc::alloc::handle_alloc_error;
+use crate::sbrk;
+use alloc::alloc::handle_alloc_error;
 use core::{
     alloc::{GlobalAlloc, Layout},
     ptr::NonNull,
@@ -6,6 +7,8 @@ use core::{
 use customizable_buddy::{BuddyAllocator, LinkedListBuddy, UsizeBuddy};
 
 /// 初始化全局分配器和内核堆分配器。
+///
+/// 静态的 16 KiB 用完后，再通过 `sbrk` 向内核申请。
 pub fn init() {
     // 托管空间 16 KiB
     const MEMORY_SIZE: usize = 16 << 10;
@@ -19,6 +22,24 @@ pub fn init() {
     }
 }
 
+/// 通过 `sbrk` 扩展堆，把新的区域转移给分配器，返回是否成功。
+///
+/// 伙伴分配器只能分配对齐的块，扩展的长度取所需块的 2 倍，保证其中包含一个完整的块。
+fn grow(layout: Layout) -> bool {
+    const PAGE_SIZE: usize = 4096;
+    // 每次至少扩展 16 KiB
+    const MIN_GROW: usize = 16 << 10;
+    let block = layout.size().max(layout.align()).next_power_of_two();
+    let size = ((block << 1).max(MIN_GROW) + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
+    match sbrk(size as _) {
+        -1 => false,
+        base => {
+            unsafe { HEAP.transfer(NonNull::new_unchecked(base as _), size) };
+            true
+        }
+    }
+}
+
 type MutAllocator<const N: usize> = BuddyAllocator<N, UsizeBuddy, LinkedListBuddy>;
 static mut HEAP: MutAllocator<32> = MutAllocator::new();
 
@@ -28,12 +49,14 @@ struct Global;
 static GLOBAL: Global = Global;
 
 unsafe impl GlobalAlloc for Global {
-    #[inline]
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-        if let Ok((ptr, _)) = HEAP.allocate_layout::<u8>(layout) {
-            ptr.as_ptr()
-        } else {
-            handle_alloc_error(layout)
+        loop {
+            if let Ok((ptr, _)) = HEAP.allocate_layout::<u8>(layout) {
+                return ptr.as_ptr();
+            }
+            if !grow(layout) {
+                handle_alloc_error(layout)
+            }
         }
     }
 
@@ -39,10 +39,40 @@ fn panic_handler(panic_info: &core::panic::PanicInfo) -> ! {
 
 pub fn getchar() -> u8 {
     let mut c = [0u8; 1];
-    read(STDIN, &mut c);
+    read_stdin(&mut c);
     c[0]
 }
 
+/// 从标准输入读取，还没有输入时让出处理器等待。
+///
+/// 终端在规范模式下按行读取，返回 0 表示文件结束。
+pub fn read_stdin(buf: &mut [u8]) -> isize {
+    loop {
+        match read(STDIN, buf) {
+            n if n == -EAGAIN => {
+                sched_yield();
+            }
+            n => return n,
+        }
+    }
+}
+
+/// 终端的前台进程组，失败时返回错误码的相反数。
+pub fn tcgetpgrp() -> isize {
+    let mut pgid: i32 = 0;
+    match ioctl(STDIN, TIOCGPGRP, &mut pgid as *mut _ as _) {
+        0 => pgid as _,
+        err => err,
+    }
+}
+
+/// 把终端的前台进程组设为 `pgid`，它必须与调用者在同一会话中。
+#[inline]
+pub fn tcsetpgrp(pgid: isize) -> isize {
+    let pgid = pgid as i32;
+    ioctl(STDIN, TIOCSPGRP, &pgid as *const _ as _)
+}
+
 struct Console;
 
 impl rcore_console::Console for Console {
@@ -55,6 +85,25 @@ impl rcore_console::Console for Console {
     fn put_str(&self, s: &str) {
         syscall::write(STDOUT, s.as_bytes());
     }
+
+    /// 支持线程的内核把线程号放在 `tp` 里，不支持线程的内核里它总是 0。
+    #[inline]
+    fn flow_id(&self) -> Option<usize> {
+        let tid: usize;
+        unsafe { core::arch::asm!("mv {}, tp", out(reg) tid) };
+        Some(tid)
+    }
+}
+
+/// 把程序断点移动 `increment` 字节，返回原来的程序断点，失败时返回 -1。
+pub fn sbrk(increment: isize) -> isize {
+    let old = brk(0);
+    let new = old + increment;
+    if brk(new as _) == new {
+        old
+    } else {
+        -1
+    }
 }
 
 pub fn sleep(period_ms: usize) {
@@ -1,4 +1,5 @@
 mod fs_pack;
+mod symbols;
 mod user;
 
 #[macro_use]
@@ -58,9 +59,12 @@ struct BuildArgs {
     /// features
     #[clap(short, long)]
     features: Option<String>,
-    /// log level
+    /// log filter, e.g. `warn,easy_fs=trace,kernel_vm=debug`
     #[clap(long)]
     log: Option<String>,
+    /// time slice in microseconds for ch4~ch8, 10 ms by default
+    #[clap(long)]
+    quantum: Option<u64>,
     /// build in release mode
     #[clap(long)]
     release: bool,
@@ -85,16 +89,50 @@ impl BuildArgs {
             }
             _ => unreachable!(),
         };
+        let elf = TARGET
+            .join(if self.release { "release" } else { "debug" })
+            .join(&package);
         // 生成
+        self.build(&package, &env);
+        if (2..=8).contains(&self.ch) {
+            // 提取函数符号，带着符号表重新链接，直到符号表不再变化
+            let path = elf.with_file_name("symbols.asm");
+            env.insert("KERNEL_SYMBOLS", path.as_os_str().to_os_string());
+            const PASSES: usize = 3;
+            let mut table = symbols::generate(&elf);
+            let mut converged = false;
+            for _ in 0..PASSES {
+                fs::write(&path, &table).unwrap();
+                self.build(&package, &env);
+                let next = symbols::generate(&elf);
+                if next == table {
+                    converged = true;
+                    break;
+                }
+                table = next;
+            }
+            // 不收敛时链接进内核的符号表与函数的实际地址不符，回溯会打印错误的函数名
+            assert!(
+                converged,
+                "kernel symbol table still changes after {PASSES} relinks"
+            );
+        }
+        elf
+    }
+
+    fn build(&self, package: &str, env: &HashMap<&str, OsString>) {
         let mut build = Cargo::build();
         build
-            .package(&package)
+            .package(package)
             .optional(&self.features, |cargo, features| {
                 cargo.features(false, features.split_whitespace());
             })
             .optional(&self.log, |cargo, log| {
                 cargo.env("LOG", log);
             })
+            .optional(&self.quantum, |cargo, quantum| {
+                cargo.env("QUANTUM", quantum.to_string());
+            })
             .conditional(self.release, |cargo| {
                 cargo.release();
             })
@@ -103,9 +141,6 @@ impl BuildArgs {
             build.env(key, value);
         }
         build.invoke();
-        TARGET
-            .join(if self.release { "release" } else { "debug" })
-            .join(package)
     }
 }
 
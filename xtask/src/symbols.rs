@@ -0,0 +1,50 @@
+use std::{fmt::Write, fs, path::Path};
+use xmas_elf::{
+    sections::SectionData,
+    symbol_table::{Entry, Type},
+    ElfFile,
+};
+
+/// 从内核 ELF 中提取函数符号，生成 `.ksymbols` 段的汇编。
+///
+/// 表头是表项数，之后每项是函数地址和函数名的地址，按地址升序排列。
+pub fn generate(elf: &Path) -> String {
+    let data = fs::read(elf).unwrap();
+    let elf = ElfFile::new(&data).unwrap();
+    let mut funcs = Vec::new();
+    for section in elf.section_iter() {
+        if let Ok(SectionData::SymbolTable64(symbols)) = section.get_data(&elf) {
+            for symbol in symbols {
+                if symbol.get_type() == Ok(Type::Func) && symbol.size() > 0 {
+                    let name = symbol.get_name(&elf).unwrap();
+                    funcs.push((
+                        symbol.value(),
+                        format!("{:#}", rustc_demangle::demangle(name)),
+                    ));
+                }
+            }
+        }
+    }
+    funcs.sort();
+    funcs.dedup_by_key(|(addr, _)| *addr);
+
+    let mut asm = String::new();
+    writeln!(
+        asm,
+        "\
+    .section .ksymbols, \"a\"
+    .align 3
+    .global __ksymbols
+__ksymbols:
+    .quad {}",
+        funcs.len()
+    )
+    .unwrap();
+    for (i, (addr, _)) in funcs.iter().enumerate() {
+        writeln!(asm, "    .quad {addr:#x}, ksym_{i}").unwrap();
+    }
+    for (i, (_, name)) in funcs.iter().enumerate() {
+        writeln!(asm, "ksym_{i}:\n    .string {name:?}").unwrap();
+    }
+    asm
+}
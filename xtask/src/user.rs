@@ -13,6 +13,7 @@ struct Cases {
 pub struct CasesInfo {
     base: u64,
     step: u64,
+    names: Vec<String>,
     bins: Vec<PathBuf>,
 }
 
@@ -29,12 +30,14 @@ impl Cases {
             CasesInfo {
                 base,
                 step,
+                names: names.clone(),
                 bins: cases,
             }
         } else {
             CasesInfo {
                 base: 0,
                 step: 0,
+                names: vec![],
                 bins: vec![],
             }
         }
@@ -75,7 +78,12 @@ pub fn build_for(ch: u8, release: bool) {
         .unwrap()
         .remove(&format!("ch{ch}"))
         .unwrap_or_default();
-    let CasesInfo { base, step, bins } = cases.build(release);
+    let CasesInfo {
+        base,
+        step,
+        names,
+        bins,
+    } = cases.build(release);
     if bins.is_empty() {
         return;
     }
@@ -97,9 +105,9 @@ apps:
     )
     .unwrap();
 
-    (0..bins.len()).for_each(|i| writeln!(ld, "    .quad app_{i}_start").unwrap());
-
-    writeln!(ld, "    .quad app_{}_end", bins.len() - 1).unwrap();
+    // 每个应用程序一项：起始地址、结束地址、名字
+    (0..bins.len())
+        .for_each(|i| writeln!(ld, "    .quad app_{i}_start, app_{i}_end, app_{i}_name").unwrap());
 
     bins.iter().enumerate().for_each(|(i, path)| {
         writeln!(
@@ -112,20 +120,12 @@ app_{i}_end:",
         .unwrap();
     });
 
-    if ch == 5 {
-        writeln!(
-            ld,
-            "
-    .align 3
-    .section .data
-    .global app_names
-app_names:"
-        )
-        .unwrap();
-        bins.iter().enumerate().for_each(|(_, path)| {
-            writeln!(ld, "    .string {:?}", path.file_name().unwrap()).unwrap();
-        });
-    } else if ch >= 6 {
+    writeln!(ld, "\n    .section .rodata").unwrap();
+    names.iter().enumerate().for_each(|(i, name)| {
+        writeln!(ld, "app_{i}_name:\n    .string {name:?}").unwrap();
+    });
+
+    if ch >= 6 {
         easy_fs_pack(
             &cases.cases.unwrap(),
             TARGET